@@ -0,0 +1,13 @@
+#include <assert.h>
+int main() {
+  struct {
+    _Bool ok;
+    double d;
+    int *p[2];
+    union { int i; char c; } u;
+  } qwe;
+  qwe.ok = 1;
+  qwe.u.i = 0;
+  qwe.p[0] = 0;
+  assert(qwe.ok && qwe.u.c == 0 && !qwe.p[0]);
+}
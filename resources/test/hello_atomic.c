@@ -0,0 +1,15 @@
+#include <assert.h>
+
+int x = 0;
+
+int main() {
+__CPROVER_ASYNC_1:
+  {
+    __CPROVER_atomic_begin();
+    x = 1;
+    x = 2;
+    __CPROVER_atomic_end();
+  }
+  assert(x != 1);
+  return 0;
+}
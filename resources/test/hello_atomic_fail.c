@@ -0,0 +1,13 @@
+#include <assert.h>
+
+int x = 0;
+
+int main() {
+__CPROVER_ASYNC_1:
+  {
+    x = 1;
+    x = 2;
+  }
+  assert(x != 1);
+  return 0;
+}
@@ -0,0 +1,11 @@
+#include <assert.h>
+#include <math.h>
+float nondet_float();
+int main() {
+  float a = nondet_float();
+  __CPROVER_assume(a > 1.0f && a < 2.0f);
+  double b = a * 2.5 - 1.0;
+  assert(b / 2.0 > 0.5);
+  assert(!isnan(b) && !isinf(b));
+  assert(nearbyint(1.5) == 2.0);
+}
@@ -0,0 +1,7 @@
+#include <assert.h>
+#include <math.h>
+float nondet_float();
+int main() {
+  float a = nondet_float();
+  assert(!isnan(a + 1.0f));
+}
@@ -0,0 +1,6 @@
+#include <stdlib.h>
+
+int main() {
+  int *p = malloc(sizeof(int));
+  return 0;
+}
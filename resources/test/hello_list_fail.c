@@ -0,0 +1,15 @@
+#include <assert.h>
+#include <stddef.h>
+struct node {
+  int value;
+  struct node *next;
+};
+int main() {
+  struct node c = {3, NULL};
+  struct node b = {2, &c};
+  struct node a = {1, &b};
+  int sum = 0;
+  for (struct node *n = &a; n != NULL; n = n->next)
+    sum += n->value;
+  assert(sum == 5);
+}
@@ -0,0 +1,12 @@
+#include <assert.h>
+#include <stdlib.h>
+
+int main() {
+  int *p = malloc(4 * sizeof(int));
+  if (p == NULL)
+    return 0;
+  p[3] = 42;
+  assert(p[3] == 42);
+  free(p);
+  return 0;
+}
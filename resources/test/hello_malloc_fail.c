@@ -0,0 +1,10 @@
+#include <stdlib.h>
+
+int main() {
+  int *p = malloc(4 * sizeof(int));
+  if (p == NULL)
+    return 0;
+  p[4] = 42;
+  free(p);
+  return 0;
+}
@@ -0,0 +1,18 @@
+#include <assert.h>
+
+int counter = 0;
+
+int main() {
+  int step = 1;
+__CPROVER_ASYNC_1:
+  {
+    __CPROVER_atomic_begin();
+    counter += step;
+    __CPROVER_atomic_end();
+  }
+  __CPROVER_atomic_begin();
+  counter += step;
+  __CPROVER_atomic_end();
+  assert(counter >= 1 && counter <= 2);
+  return 0;
+}
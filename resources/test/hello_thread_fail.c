@@ -0,0 +1,12 @@
+#include <assert.h>
+
+int counter = 0;
+
+int main() {
+  int step = 1;
+__CPROVER_ASYNC_1:
+  counter += step;
+  counter += step;
+  assert(counter == 2);
+  return 0;
+}
@@ -0,0 +1,13 @@
+#include <assert.h>
+union value {
+  int i;
+  char c[4];
+};
+enum color { RED, GREEN = 5, BLUE };
+int main() {
+  union value v;
+  v.i = 0;
+  v.c[0] = 1;
+  enum color c = GREEN;
+  assert(v.i == 0 || c == BLUE);
+}
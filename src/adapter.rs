@@ -1,28 +1,66 @@
 // This module is responsible to convert between CBMC <=> ESBMC
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
-use crate::bytereader::ByteReader;
+use crate::architecture;
+use crate::bytes;
 use crate::bytewriter::ByteWriter;
-use crate::cbmc::{CBMCFunction, CBMCInstruction, CBMCParseResult, CBMCSymbol};
-use crate::esbmc::ESBMCParseResult;
+use crate::cbmc::{
+    CBMCFunction, CBMCInstruction, CBMCInstructionKind, CBMCLayout, CBMCParseResult, CBMCSymbol,
+    NO_TARGET_NUMBER,
+};
+use crate::contracts;
+use crate::esbmc::{ESBMCInstructionKind, ESBMCParseResult};
+use crate::heap;
+use crate::intrinsics;
 use crate::irep::Irept;
-use log::trace;
-
-pub fn cbmc2esbmc(entrypoint: &str, input: &str, output: &str) {
+use crate::rules::RuleSet;
+use crate::threads;
+use log::{trace, warn};
+
+pub fn cbmc2esbmc(
+    entrypoint: Option<&str>,
+    input: &str,
+    output: &str,
+    rules: &RuleSet,
+) -> Result<(), String> {
     trace!("cbmc2esbmc mode, {} {}", input, output);
 
-    let result = crate::cbmc::process_cbmc_file(input, entrypoint);
+    let mut result = crate::cbmc::process_cbmc_file(input).map_err(|e| e.to_string())?;
+    result.entrypoint = result.find_entrypoint(entrypoint)?;
+    crate::architecture::print_esbmc_flags(&result.symbols_irep, output)?;
+
+    let converted = cbmc_to_esbmc(result, rules)?;
     std::fs::remove_file(output).ok();
+    ByteWriter::write_to_file(converted.symbols_irep, converted.functions_irep, output)
+        .map_err(|e| format!("Could not write file {}: {}", output, e))
+}
+
+pub fn esbmc2cbmc(
+    input: &str,
+    output: &str,
+    layout: CBMCLayout,
+    rules: &RuleSet,
+) -> Result<(), String> {
+    trace!("esbmc2cbmc mode, {} {}", input, output);
 
-    let converted = ESBMCParseResult::from(result);
-    //let converted = result;
-    std::fs::remove_file(&output).ok();
-    ByteWriter::write_to_file(converted.symbols_irep, converted.functions_irep, output);
+    let result = crate::esbmc::process_esbmc_file(input).map_err(|e| e.to_string())?;
+    let converted = esbmc_to_cbmc(result, rules)?;
+    std::fs::remove_file(output).ok();
+    ByteWriter::write_cbmc_to_file(
+        converted.symbols_irep,
+        converted.functions_irep,
+        output,
+        layout,
+    )
+    .map_err(|e| format!("Could not write file {}: {}", output, e))
 }
 
+// Types that refer to a type symbol, ESBMC only knows about the expanded ones
+const TAG_TYPES: [&str; 3] = ["struct_tag", "union_tag", "c_enum_tag"];
+
 trait IrepAdapter {
-    fn to_esbmc_irep(self, entrypoint: &str) -> Irept;
+    fn to_esbmc_irep(self, entrypoint: &str, rules: &RuleSet) -> Result<Irept, String>;
 }
 
 pub fn irep_contains(irep: &Irept, id: &str) -> bool {
@@ -36,13 +74,13 @@ pub fn irep_contains(irep: &Irept, id: &str) -> bool {
         }
     }
 
-    for (_, v) in &irep.named_subt {
+    for v in irep.named_subt.values() {
         if irep_contains(v, id) {
             return true;
         }
     }
 
-    for (_, v) in &irep.comments {
+    for v in irep.comments.values() {
         if irep_contains(v, id) {
             return true;
         }
@@ -50,155 +88,372 @@ pub fn irep_contains(irep: &Irept, id: &str) -> bool {
     false
 }
 
-impl From<CBMCParseResult> for ESBMCParseResult {
-    fn from(data: CBMCParseResult) -> Self {
-        let mut adapted = ESBMCParseResult {
-            reader: (data.reader),
-            symbols_irep: Vec::with_capacity(data.symbols_irep.len()),
-            functions_irep: Vec::with_capacity(data.functions_irep.len()),
-        };
-        let entrypoint = &data.entrypoint;
+// Identifier of a tag that could not be replaced by its type, e.g. the
+// tag of a type that is not in the symbol table
+fn unresolved_tag(irep: &Irept) -> Option<String> {
+    if TAG_TYPES.contains(&irep.id.as_str()) {
+        return Some(
+            irep.named_subt
+                .get("identifier")
+                .map_or(irep.id.clone(), |identifier| identifier.id.clone()),
+        );
+    }
+    irep.subt
+        .iter()
+        .chain(irep.named_subt.values())
+        .chain(irep.comments.values())
+        .find_map(unresolved_tag)
+}
 
-        // First, we need to walk through the symbols and map all the
-        // ref-types into concrete types
+impl TryFrom<CBMCParseResult> for ESBMCParseResult {
+    type Error = String;
 
-        let mut type_cache: HashMap<Irept, Irept> = HashMap::new();
+    fn try_from(data: CBMCParseResult) -> Result<Self, String> {
+        cbmc_to_esbmc(data, &RuleSet::default())
+    }
+}
 
-        for mut sym in data.symbols_irep {
-            if sym.is_type && sym.stype.id == "struct" {
-                let tagname = Irept::from(format!("tag-{}", &sym.base_name));
-                sym.stype.fix_type(&type_cache);
-                type_cache.insert(tagname, sym.stype.clone());
-            }
-            adapted.symbols_irep.push(sym.to_esbmc_irep(entrypoint));
-        }
+// Fails on the expressions that ESBMC cannot represent
+pub fn cbmc_to_esbmc(
+    mut data: CBMCParseResult,
+    rules: &RuleSet,
+) -> Result<ESBMCParseResult, String> {
+    let pointer_width = architecture::pointer_width(&data.symbols_irep);
+    contracts::lower_contracts(
+        &mut data.symbols_irep,
+        &mut data.functions_irep,
+        &data.entrypoint,
+    );
+    threads::lower_threads(
+        &mut data.symbols_irep,
+        &mut data.functions_irep,
+        pointer_width,
+    )?;
+    // The models read object_size, which is left to the heap
+    intrinsics::model_intrinsics(
+        &mut data.symbols_irep,
+        &mut data.functions_irep,
+        pointer_width,
+    )?;
+    heap::cbmc_heap_to_esbmc(
+        &mut data.symbols_irep,
+        &mut data.functions_irep,
+        &data.entrypoint,
+        pointer_width,
+    )?;
+    bytes::cbmc_bytes_to_esbmc(
+        &mut data.symbols_irep,
+        &mut data.functions_irep,
+        pointer_width,
+    )?;
+    // Calls to undefined functions would not be checked by ESBMC
+    let unsupported =
+        intrinsics::lower_intrinsics(&mut data.functions_irep, intrinsics::cbmc_intrinsic);
+    if !unsupported.is_empty() {
+        return Err(format!(
+            "Intrinsics without ESBMC counterpart: {}",
+            unsupported.into_iter().collect::<Vec<String>>().join(", ")
+        ));
+    }
 
-        // A symbol might have been defined later, we need to check everything again
-        for symbol in &mut adapted.symbols_irep {
-            symbol.fix_type(&type_cache);
-            if irep_contains(symbol, "struct_tag") {
-                panic!("Tag should have been filtered for {}", symbol.to_string());
-            }
+    let mut adapted = ESBMCParseResult {
+        reader: (data.reader),
+        symbols_irep: Vec::with_capacity(data.symbols_irep.len()),
+        functions_irep: Vec::with_capacity(data.functions_irep.len()),
+    };
+    let entrypoint = &data.entrypoint;
+
+    // First, we need to walk through the symbols and map all the
+    // ref-types into concrete types
+
+    let mut type_cache: HashMap<Irept, Irept> = HashMap::new();
+
+    for mut sym in data.symbols_irep {
+        if sym.is_type && ["struct", "union", "c_enum"].contains(&sym.stype.id.as_str()) {
+            let tagname = Irept::from(format!("tag-{}", &sym.base_name));
+            sym.stype
+                .fix_type(&type_cache)
+                .map_err(|e| format!("Symbol {}: {}", sym.name, e))?;
+            type_cache.insert(tagname, sym.stype.clone());
+        }
+        adapted
+            .symbols_irep
+            .push(sym.to_esbmc_irep(entrypoint, rules)?);
+    }
 
-            assert_ne!(symbol.named_subt["type"].id, "c_bool");
+    // A symbol might have been defined later, we need to check everything again
+    for symbol in &mut adapted.symbols_irep {
+        symbol
+            .fix_type(&type_cache)
+            .map_err(|e| format!("Symbol {}: {}", symbol.named_subt["name"].id, e))?;
+        let name = &symbol.named_subt["name"].id;
+        if let Some(tag) = unresolved_tag(symbol) {
+            return Err(format!("Symbol {}: unresolved tag {}", name, tag));
+        }
+        if symbol.named_subt["type"].id == "c_bool" {
+            return Err(format!("Symbol {}: unresolved type c_bool", name));
         }
+    }
 
-        // NOTE: ESBMC/CBMC uses the number offset of the function as the target
-        //       which is fine for most cases. But CBMC for some reason likes to
-        //       start from 1 and have a target number associated to the instruction.
-        //       So we first parse everything and then fix the target numbers
-        for mut foo in data.functions_irep {
-            let mut target_revmap: HashMap<u32, u32> = HashMap::new();
+    // NOTE: ESBMC/CBMC uses the number offset of the function as the target
+    //       which is fine for most cases. But CBMC for some reason likes to
+    //       start from 1 and have a target number associated to the instruction.
+    //       So we first parse everything and then fix the target numbers
+    for mut function in data.functions_irep {
+        let mut target_revmap: HashMap<u32, u32> = HashMap::new();
 
-            for (index, inst) in &mut foo.instructions.iter().enumerate() {
-                target_revmap.insert(inst.target_number, (index) as u32);
-            }
+        for (index, inst) in &mut function.instructions.iter().enumerate() {
+            target_revmap.insert(inst.target_number, (index) as u32);
+        }
 
-            // lets fix the targets
-            for f in &mut foo.instructions {
-                for t in &mut f.targets {
-                    let unsigned_value: u32 = t.id.parse().unwrap();
-                    let target_fixed = target_revmap.get(&unsigned_value).unwrap().to_string();
-                    t.id = target_fixed;
-                }
+        // lets fix the targets
+        for f in &mut function.instructions {
+            for t in &mut f.targets {
+                let unsigned_value: u32 = t.id.parse().unwrap();
+                let target_fixed = target_revmap.get(&unsigned_value).unwrap().to_string();
+                t.id = target_fixed;
             }
-
-            let function_name = esbmcfixes::fix_name(&foo.name, entrypoint);
-            let mut function_irep = foo.to_esbmc_irep(entrypoint);
-            function_irep.fix_type(&type_cache);
-            adapted.functions_irep.push((function_name, function_irep));
         }
 
-        adapted
+        let function_name = esbmcfixes::fix_name(&function.name, entrypoint);
+        let mut function_irep = function.to_esbmc_irep(entrypoint, rules)?;
+        function_irep
+            .fix_type(&type_cache)
+            .map_err(|e| format!("Function {}: {}", function_name, e))?;
+        if let Some(tag) = unresolved_tag(&function_irep) {
+            return Err(format!(
+                "Function {}: unresolved tag {}",
+                function_name, tag
+            ));
+        }
+        adapted.functions_irep.push((function_name, function_irep));
     }
+
+    Ok(adapted)
 }
 
 mod esbmcfixes {
-    use super::HashSet;
     use super::Irept;
+    use super::RuleSet;
+
+    // Globals renamed by crate::intrinsics. ESBMC identifiers get the "c:@"
+    // prefix while the base name is kept as is.
+    use crate::intrinsics::esbmc_name as renamed;
+
     pub fn fix_name(name: &str, entry: &str) -> String {
         if name == entry {
             return "__ESBMC_main".to_string();
         }
-        return String::from(name);
+        if let Some(esbmc) = renamed(name) {
+            return format!("c:@{}", esbmc);
+        }
+        String::from(name)
+    }
+
+    pub fn fix_base_name(name: &str, entry: &str) -> String {
+        match renamed(name) {
+            Some(esbmc) => String::from(esbmc),
+            None => fix_name(name, entry),
+        }
+    }
+
+    // Types whose constants are a plain bit pattern of `width` bits
+    const BITVECTORS: [&str; 6] = [
+        "signedbv",
+        "unsignedbv",
+        "c_bool",
+        "c_enum",
+        "pointer",
+        "floatbv",
+    ];
+
+    // CBMC keeps bit-vector constants as hexadecimal strings without
+    // leading zeros (negative numbers are already in two's complement, but
+    // a leading '-' is accepted too), ESBMC keeps exactly `width` bits.
+    // Returns None if the value is not hexadecimal or does not fit.
+    pub fn hex_to_binary(value: &str, width: usize) -> Option<String> {
+        let (negative, digits) = match value.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, value),
+        };
+        if digits.is_empty() {
+            return None;
+        }
+
+        let mut bits = Vec::with_capacity(digits.len() * 4);
+        for c in digits.chars() {
+            let digit = c.to_digit(16)?;
+            bits.extend((0..4).rev().map(|i| digit & (1 << i) != 0));
+        }
+        let first_one = bits.iter().position(|b| *b).unwrap_or(bits.len());
+        let bits = &bits[first_one..];
+        if bits.len() > width {
+            return None;
+        }
+
+        let mut result = vec![false; width - bits.len()];
+        result.extend_from_slice(bits);
+        if negative {
+            // Two's complement: invert and add one
+            for bit in result.iter_mut() {
+                *bit = !*bit;
+            }
+            for bit in result.iter_mut().rev() {
+                *bit = !*bit;
+                if *bit {
+                    break;
+                }
+            }
+        }
+        Some(result.iter().map(|b| if *b { '1' } else { '0' }).collect())
     }
 
-    pub fn fix_expression(irep: &mut Irept) {
-        if irep.id == "side_effect" {
-            irep.id = "sideeffect".to_string();
+    pub fn fix_constant(irep: &mut Irept) -> Result<(), String> {
+        let ctype = irep.named_subt.get("type").cloned().unwrap_or_default();
+        if !BITVECTORS.contains(&ctype.id.as_str()) {
+            return Ok(());
         }
+        let width: usize = match ctype.named_subt.get("width").map(|w| w.id.parse()) {
+            Some(Ok(width)) => width,
+            _ => return Ok(()),
+        };
+        let value = match irep.named_subt.get("value") {
+            Some(value) => value.id.clone(),
+            None => return Ok(()),
+        };
 
-        if irep.id == "constant"
-            && !["pointer", "bool"].contains(&irep.named_subt["type"].id.as_str())
+        // Null pointers are kept by name and values might already be binary
+        // (e.g. array sizes are fixed twice)
+        if value == "NULL" || (value.len() == width && value.chars().all(|c| c == '0' || c == '1'))
         {
-            // Value ID might be hexa representation or binary, we want the binary one!
-            if 32 != irep.named_subt["value"].id.len() {
-                let number = u64::from_str_radix(&irep.named_subt["value"].id, 16).unwrap();
-                irep.named_subt.insert(
-                    String::from("value"),
-                    Irept::from(format!("{:032b}", number)),
-                );
+            return Ok(());
+        }
+
+        match hex_to_binary(&value, width) {
+            Some(bits) => {
+                irep.named_subt
+                    .insert(String::from("value"), Irept::from(bits));
+            }
+            None => {
+                return Err(format!(
+                    "Constant {} does not fit into {} bits of {}",
+                    value, width, ctype.id
+                ))
             }
         }
+        Ok(())
+    }
 
-        let expressions: HashSet<String> = HashSet::from(
-            [
-                "if",
-                "member",
-                "typecast",
-                "notequal",
-                "and",
-                "or",
-                "mod",
-                "not",
-                "*",
-                "/",
-                "+",
-                "-",
-                "=",
-                "<",
-                ">",
-                "overflow_result-+",
-                "overflow_result--",
-                "overflow_result-*",
-                "overflow_result-shr",
-                "lshr",
-                "ashr",
-                "shl",
-                "address_of",
-                "index",
-                "byte_extract_little_endian",
-                "pointer_object",
-                "array_of",
-                "sideeffect",
-                "dereference",
-                "object_size",
-                "bitand",
-                "struct",
-                "return",
-            ]
-            .map(|x| x.to_string()),
-        );
+    // Operators of the overflow_result expressions, ESBMC has the same
+    // overflow predicates (e.g. overflow-+)
+    const OVERFLOW_OPERATORS: [&str; 4] = ["+", "-", "*", "shl"];
+
+    // ESBMC has no overflow_result-op, it becomes the struct of its type:
+    // {a op b, overflow-op(a, b)}. ESBMC has no overflow predicate for the
+    // other operators (e.g. shr).
+    pub fn lower_overflow_result(irep: &mut Irept) -> Result<(), String> {
+        let operator = &irep.id["overflow_result-".len()..];
+        if !OVERFLOW_OPERATORS.contains(&operator) || irep.subt.len() != 2 {
+            return Err(format!(
+                "{} with {} operands is not supported by ESBMC",
+                irep.id,
+                irep.subt.len()
+            ));
+        }
+        // The result is the first component, which has the type of the
+        // first operand when the struct is behind a tag
+        let result_type = irep
+            .named_subt
+            .get("type")
+            .and_then(|t| t.named_subt.get("components"))
+            .and_then(|c| c.subt.first())
+            .or(irep.subt.first())
+            .and_then(|c| c.named_subt.get("type"))
+            .cloned()
+            .ok_or_else(|| format!("{} without the type of its result", irep.id))?;
+
+        let mut result = Irept::from(operator);
+        result.subt = irep.subt.clone();
+        result.named_subt.insert(String::from("type"), result_type);
+
+        let mut overflow = Irept::from(format!("overflow-{}", operator));
+        overflow.subt = std::mem::take(&mut irep.subt);
+        overflow
+            .named_subt
+            .insert(String::from("type"), Irept::from("bool"));
 
-        // NOTE: In CBMC both the expression and the type can be named
-        // "array". And even worse, "array" is an umbrella expressions
-        // which can also mean index!
-        let array_has_operand = irep.id == "array"
-            && irep.named_subt.contains_key("type")
-            && irep.named_subt["type"].id == "array"
-            && irep.subt.len() != 0;
+        irep.id = String::from("struct");
+        irep.subt = vec![result, overflow];
+        Ok(())
+    }
 
-        let is_function_call = irep.id == "arguments" && irep.subt.len() != 0;
+    // CBMC quantifiers bind a tuple of variables, ESBMC ones a single
+    // symbol: forall (x, y). p becomes forall x. forall y. p
+    pub fn lower_quantifier(irep: &mut Irept) -> Result<(), String> {
+        if irep.subt.len() != 2 {
+            return Err(format!(
+                "{} with {} operands is not supported by ESBMC",
+                irep.id,
+                irep.subt.len()
+            ));
+        }
+        // Already lowered (e.g. the nested ones)
+        if irep.subt[0].id == "symbol" {
+            return Ok(());
+        }
+        if irep.subt[0].id != "tuple" || irep.subt[0].subt.is_empty() {
+            return Err(format!(
+                "{} binding a {} is not supported by ESBMC, only symbols can be bound",
+                irep.id, irep.subt[0].id
+            ));
+        }
+        if let Some(variable) = irep.subt[0].subt.iter().find(|v| v.id != "symbol") {
+            return Err(format!(
+                "{} binding a {} is not supported by ESBMC, only symbols can be bound",
+                irep.id, variable.id
+            ));
+        }
 
-        if expressions.contains(&irep.id) || array_has_operand || is_function_call {
-            let mut operands = Irept::default();
-            operands.subt = irep.subt.clone();
-            irep.named_subt.insert("operands".to_string(), operands);
-            irep.subt.clear();
+        let variables = std::mem::take(&mut irep.subt[0].subt);
+        let mut predicate = std::mem::take(&mut irep.subt[1]);
+        for variable in variables[1..].iter().rev() {
+            let mut quantifier = Irept::from(irep.id.as_str());
+            quantifier
+                .named_subt
+                .insert(String::from("type"), Irept::from("bool"));
+            quantifier.subt = vec![variable.clone(), predicate];
+            predicate = quantifier;
+        }
+        irep.subt = vec![variables[0].clone(), predicate];
+        Ok(())
+    }
+
+    pub fn fix_expression(irep: &mut Irept, rules: &RuleSet) -> Result<(), String> {
+        if irep.id == "symbol" {
+            if let Some(identifier) = irep.named_subt.get_mut("identifier") {
+                if let Some(esbmc) = renamed(&identifier.id) {
+                    identifier.id = format!("c:@{}", esbmc);
+                }
+            }
+        }
+
+        if irep.id == "constant" {
+            fix_constant(irep)?;
         }
 
+        if irep.id.starts_with("overflow_result-") {
+            lower_overflow_result(irep)?;
+        }
+
+        if irep.id == "forall" || irep.id == "exists" {
+            lower_quantifier(irep)?;
+        }
+
+        // Renames, operands and so on (see resources/rules.json)
+        rules.apply(irep);
+
         for sub in &mut irep.subt {
-            fix_expression(sub)
+            fix_expression(sub, rules)?;
         }
 
         for (k, v) in &mut irep.named_subt {
@@ -207,15 +462,89 @@ mod esbmcfixes {
                     sub.id = "component".to_string();
                 }
             }
-            fix_expression(v);
+            fix_expression(v, rules)?;
+        }
+
+        // e.g. the size of a malloc
+        for v in irep.comments.values_mut() {
+            fix_expression(v, rules)?;
         }
+        Ok(())
+    }
+}
+
+// Instruction kinds that mean the same for both tools, the rest is lowered
+// by esbmc_instruction_kind and cbmc_instruction_kind
+const INSTRUCTION_KINDS: [(CBMCInstructionKind, ESBMCInstructionKind); 17] = [
+    (
+        CBMCInstructionKind::NoInstructionType,
+        ESBMCInstructionKind::NoInstructionType,
+    ),
+    (CBMCInstructionKind::Goto, ESBMCInstructionKind::Goto),
+    (CBMCInstructionKind::Assume, ESBMCInstructionKind::Assume),
+    (CBMCInstructionKind::Assert, ESBMCInstructionKind::Assert),
+    (CBMCInstructionKind::Other, ESBMCInstructionKind::Other),
+    (CBMCInstructionKind::Skip, ESBMCInstructionKind::Skip),
+    (
+        CBMCInstructionKind::Location,
+        ESBMCInstructionKind::Location,
+    ),
+    (
+        CBMCInstructionKind::EndFunction,
+        ESBMCInstructionKind::EndFunction,
+    ),
+    (
+        CBMCInstructionKind::AtomicBegin,
+        ESBMCInstructionKind::AtomicBegin,
+    ),
+    (
+        CBMCInstructionKind::AtomicEnd,
+        ESBMCInstructionKind::AtomicEnd,
+    ),
+    (
+        CBMCInstructionKind::SetReturnValue,
+        ESBMCInstructionKind::Return,
+    ),
+    (CBMCInstructionKind::Assign, ESBMCInstructionKind::Assign),
+    (CBMCInstructionKind::Decl, ESBMCInstructionKind::Decl),
+    (CBMCInstructionKind::Dead, ESBMCInstructionKind::Dead),
+    (
+        CBMCInstructionKind::FunctionCall,
+        ESBMCInstructionKind::FunctionCall,
+    ),
+    (CBMCInstructionKind::Throw, ESBMCInstructionKind::Throw),
+    (CBMCInstructionKind::Catch, ESBMCInstructionKind::Catch),
+];
+
+// START_THREAD and END_THREAD are left only if the threads were not lowered
+fn esbmc_instruction_kind(kind: CBMCInstructionKind) -> Result<ESBMCInstructionKind, String> {
+    if let Some((_, esbmc)) = INSTRUCTION_KINDS.iter().find(|(cbmc, _)| *cbmc == kind) {
+        return Ok(*esbmc);
     }
+    match kind {
+        // Gotos whose target was not known yet while building the program,
+        // they have it by the time it is written.
+        CBMCInstructionKind::IncompleteGoto => Ok(ESBMCInstructionKind::Goto),
+        _ => Err(format!(
+            "{} instructions are not supported by ESBMC",
+            kind.name()
+        )),
+    }
+}
+
+// None for the instructions that CBMC does not have, these become a SKIP
+fn cbmc_instruction_kind(kind: ESBMCInstructionKind) -> Option<CBMCInstructionKind> {
+    INSTRUCTION_KINDS
+        .iter()
+        .find(|(_, esbmc)| *esbmc == kind)
+        .map(|(cbmc, _)| *cbmc)
 }
 
 impl IrepAdapter for CBMCInstruction {
-    fn to_esbmc_irep(self, entrypoint: &str) -> Irept {
+    fn to_esbmc_irep(self, _entrypoint: &str, rules: &RuleSet) -> Result<Irept, String> {
         let mut result = Irept::default();
-        assert_ne!(self.instr_type, 19);
+        let kind = CBMCInstructionKind::from_u32(self.instr_type)
+            .ok_or_else(|| format!("Unknown instruction kind {}", self.instr_type))?;
 
         // In ESBMC code arguments are expected to be inside the "operands"
         let mut code = self.code;
@@ -225,8 +554,15 @@ impl IrepAdapter for CBMCInstruction {
         code.named_subt.insert("operands".to_string(), operands);
 
         // Some checks
-        if code.id != "nil" && code.named_subt.get("statement").unwrap().id == "assign" {
-            assert_eq!(2, code.named_subt.get("operands").unwrap().subt.len());
+        if code.id != "nil" {
+            let statement = code
+                .named_subt
+                .get("statement")
+                .ok_or_else(|| format!("{:?} without statement", kind))?;
+            let count = code.named_subt["operands"].subt.len();
+            if statement.id == "assign" && count != 2 {
+                return Err(format!("assign with {} operands", count));
+            }
         }
         result.named_subt.insert("code".to_string(), code);
 
@@ -235,11 +571,11 @@ impl IrepAdapter for CBMCInstruction {
             .insert("location".to_string(), self.source_location);
         result.named_subt.insert(
             "typeid".to_string(),
-            Irept::from(self.instr_type.to_string()),
+            Irept::from((esbmc_instruction_kind(kind)? as u32).to_string()),
         );
         result.named_subt.insert("guard".to_string(), self.guard);
 
-        if self.targets.len() != 0 {
+        if !self.targets.is_empty() {
             let mut t_ireps = Irept::default();
             for target in self.targets {
                 t_ireps.subt.push(target);
@@ -247,7 +583,7 @@ impl IrepAdapter for CBMCInstruction {
             result.named_subt.insert("targets".to_string(), t_ireps);
         }
 
-        if self.labels.len() != 0 {
+        if !self.labels.is_empty() {
             let mut l_ireps = Irept::default();
             for label in self.labels {
                 l_ireps.subt.push(Irept::from(label));
@@ -260,25 +596,46 @@ impl IrepAdapter for CBMCInstruction {
             .named_subt
             .insert("function".to_string(), self.function);
 
-        esbmcfixes::fix_expression(&mut result);
-        result
+        esbmcfixes::fix_expression(&mut result, rules)?;
+        Ok(result)
     }
 }
 
 impl IrepAdapter for CBMCFunction {
-    fn to_esbmc_irep(self, entrypoint: &str) -> Irept {
+    fn to_esbmc_irep(self, entrypoint: &str, rules: &RuleSet) -> Result<Irept, String> {
         let mut result = Irept::from("goto-program");
-        for instr in self.instructions {
-            if instr.code.id == "nil" || instr.code.named_subt["statement"].id != "output" {
-                result.subt.push(instr.to_esbmc_irep(entrypoint));
+        let mut outputs = 0;
+        for mut instr in self.instructions {
+            // ESBMC does not know about output statements (e.g. the value
+            // returned by the entrypoint), they are kept as a SKIP so that
+            // targets do not change.
+            if instr
+                .code
+                .named_subt
+                .get("statement")
+                .is_some_and(|s| s.id == "output")
+            {
+                instr.instr_type = CBMCInstructionKind::Skip as u32;
+                instr.code = Irept::from("nil");
+                outputs += 1;
             }
+            let instr = instr
+                .to_esbmc_irep(entrypoint, rules)
+                .map_err(|e| format!("Function {}: {}", self.name, e))?;
+            result.subt.push(instr);
         }
-        result
+        if outputs > 0 {
+            warn!(
+                "{}: replaced {} output statement(s) by SKIP",
+                self.name, outputs
+            );
+        }
+        Ok(result)
     }
 }
 
 impl IrepAdapter for CBMCSymbol {
-    fn to_esbmc_irep(self, entrypoint: &str) -> Irept {
+    fn to_esbmc_irep(self, entrypoint: &str, rules: &RuleSet) -> Result<Irept, String> {
         let mut result = Irept::default();
         result.named_subt.insert("type".to_string(), self.stype);
         result.named_subt.insert("symvalue".to_string(), self.value);
@@ -296,9 +653,7 @@ impl IrepAdapter for CBMCSymbol {
             .insert("mode".to_string(), Irept::from(&self.mode));
 
         let name = esbmcfixes::fix_name(self.name.as_str(), entrypoint);
-        let basename = esbmcfixes::fix_name(self.base_name.as_str(), entrypoint);
-
-        assert_ne!(basename, "num::verify::checked_unchecked_add_i8");
+        let basename = esbmcfixes::fix_base_name(self.base_name.as_str(), entrypoint);
 
         if self.is_type {
             result
@@ -351,9 +706,405 @@ impl IrepAdapter for CBMCSymbol {
             .insert("name".to_string(), Irept::from(name));
 
         // Fix flags
-        esbmcfixes::fix_expression(&mut result);
-        result
+        esbmcfixes::fix_expression(&mut result, rules)
+            .map_err(|e| format!("Symbol {}: {}", self.name, e))?;
+        Ok(result)
+    }
+}
+
+impl TryFrom<ESBMCParseResult> for CBMCParseResult {
+    type Error = String;
+
+    fn try_from(data: ESBMCParseResult) -> Result<Self, String> {
+        esbmc_to_cbmc(data, &RuleSet::default())
+    }
+}
+
+// The rules are the ones used to go into ESBMC, they are inverted here.
+// Fails on the instructions that CBMC cannot read.
+pub fn esbmc_to_cbmc(data: ESBMCParseResult, rules: &RuleSet) -> Result<CBMCParseResult, String> {
+    let rules = rules.inverted();
+    let mut adapted = CBMCParseResult {
+        reader: data.reader,
+        symbols_irep: Vec::with_capacity(data.symbols_irep.len()),
+        functions_irep: Vec::with_capacity(data.functions_irep.len()),
+        entrypoint: String::from("__CPROVER__start"),
+        layout: CBMCLayout::default(),
+    };
+
+    // ESBMC refers to tagged types through a "symbol" type, CBMC needs
+    // to know upfront if the tag is for a struct or for an union. Other
+    // symbols may be left without a type too (e.g. called functions),
+    // CBMC needs the one of the symbol table.
+    let mut tag_kinds: HashMap<String, String> = HashMap::new();
+    let mut types: HashMap<String, Irept> = HashMap::new();
+    for symbol in &data.symbols_irep {
+        let name = &symbol.named_subt["name"].id;
+        if symbol
+            .named_subt
+            .get("is_type")
+            .is_some_and(|x| x.id == "1")
+        {
+            let kind = match symbol.named_subt["type"].id.as_str() {
+                "union" => "union_tag",
+                _ => "struct_tag",
+            };
+            tag_kinds.insert(name.clone(), kind.to_string());
+        } else if let Some(stype) = symbol.named_subt.get("type") {
+            types.insert(cbmcfixes::fix_name(name), stype.clone());
+        }
+    }
+
+    // ESBMC programs only know their architecture if they come from CBMC.
+    // Its symbols are integers, so they can be converted before knowing
+    // the width of the pointers.
+    let settings: Vec<CBMCSymbol> = data
+        .symbols_irep
+        .iter()
+        .filter(|s| architecture::is_setting(&s.named_subt["name"].id))
+        .map(|s| {
+            let width = architecture::DEFAULT_POINTER_WIDTH;
+            esbmc_symbol_to_cbmc(s.clone(), &tag_kinds, &types, width, &rules)
+        })
+        .collect();
+    let pointer_width = architecture::pointer_width(&settings);
+
+    for symbol in data.symbols_irep {
+        adapted.symbols_irep.push(esbmc_symbol_to_cbmc(
+            symbol,
+            &tag_kinds,
+            &types,
+            pointer_width,
+            &rules,
+        ));
+    }
+
+    for (name, irep) in data.functions_irep {
+        let function =
+            esbmc_function_to_cbmc(&name, irep, &tag_kinds, &types, pointer_width, &rules)
+                .map_err(|e| format!("Function {}: {}", name, e))?;
+        adapted.functions_irep.push(function);
+    }
+
+    heap::esbmc_heap_to_cbmc(&mut adapted.symbols_irep, &mut adapted.functions_irep);
+    for name in
+        intrinsics::lower_intrinsics(&mut adapted.functions_irep, intrinsics::esbmc_intrinsic)
+    {
+        warn!("{} has no CBMC counterpart, its calls are kept as is", name);
     }
+
+    Ok(adapted)
+}
+
+mod cbmcfixes {
+    use super::HashMap;
+    use super::Irept;
+    use super::RuleSet;
+    use crate::intrinsics::cbmc_name;
+
+    // Accepts both ESBMC identifiers and base names
+    pub fn fix_name(name: &str) -> String {
+        if name == "__ESBMC_main" {
+            return "__CPROVER__start".to_string();
+        }
+        let base_name = name.strip_prefix("c:@").unwrap_or(name);
+        if let Some(cbmc) = cbmc_name(base_name) {
+            return String::from(cbmc);
+        }
+        String::from(name)
+    }
+
+    // ESBMC keeps a rounding mode for every typecast, CBMC only for the
+    // ones from or into floats (see floatbv_typecast in rules.json)
+    fn fix_typecast(irep: &mut Irept) {
+        let is_float = |e: &Irept| e.named_subt.get("type").is_some_and(|t| t.id == "floatbv");
+        if !is_float(irep) && !irep.subt.first().is_some_and(is_float) {
+            irep.named_subt.remove("rounding_mode");
+        }
+    }
+
+    // CBMC keeps bit-vector constants as hexadecimal strings without
+    // leading zeros, ESBMC keeps all the bits.
+    pub fn binary_to_hex(bits: &str) -> String {
+        let padding = "0".repeat((4 - bits.len() % 4) % 4);
+        let padded = format!("{}{}", padding, bits);
+        let hex: String = padded
+            .as_bytes()
+            .chunks(4)
+            .map(|nibble| {
+                let value = nibble
+                    .iter()
+                    .fold(0, |acc, bit| (acc << 1) | (bit - b'0') as u32);
+                char::from_digit(value, 16).unwrap().to_ascii_uppercase()
+            })
+            .collect();
+
+        match hex.trim_start_matches('0') {
+            "" => String::from("0"),
+            trimmed => String::from(trimmed),
+        }
+    }
+
+    fn fix_constant(irep: &mut Irept) {
+        let width = match irep
+            .named_subt
+            .get("type")
+            .and_then(|t| t.named_subt.get("width"))
+        {
+            Some(w) => w.id.parse::<usize>().unwrap_or(0),
+            None => return,
+        };
+
+        let value = &irep.named_subt["value"].id;
+        if value.len() != width || !value.bytes().all(|c| c == b'0' || c == b'1') {
+            return;
+        }
+        let hex = binary_to_hex(value);
+        irep.named_subt
+            .insert(String::from("value"), Irept::from(hex));
+    }
+
+    // Fields that only hold a name, e.g. a component named "pointer"
+    const NAMES: [&str; 4] = ["name", "pretty_name", "#pretty_name", "identifier"];
+
+    // Undo every rewrite done by esbmcfixes and Irept::fix_type. Types and
+    // expressions are fixed in the same pass as their shapes do not collide.
+    pub fn fix_expression(
+        irep: &mut Irept,
+        tag_kinds: &HashMap<String, String>,
+        types: &HashMap<String, Irept>,
+        pointer_width: u64,
+        rules: &RuleSet,
+    ) {
+        // CBMC does not have a separate comment section
+        let comments: Vec<(String, Irept)> = irep.comments.drain().collect();
+        irep.named_subt.extend(comments);
+
+        if let Some(location) = irep.named_subt.remove("#location") {
+            irep.named_subt
+                .insert("#source_location".to_string(), location);
+        }
+
+        if let Some(operands) = irep.named_subt.remove("operands") {
+            irep.subt = operands.subt;
+        }
+
+        if irep.id == "symbol" {
+            if let Some(identifier) = irep.named_subt.get_mut("identifier") {
+                identifier.id = fix_name(&identifier.id);
+            }
+        }
+
+        if irep.id == "typecast" {
+            fix_typecast(irep);
+        }
+
+        // The bound symbol of ESBMC quantifiers becomes a tuple
+        if (irep.id == "forall" || irep.id == "exists")
+            && irep.subt.first().is_some_and(|s| s.id == "symbol")
+        {
+            let mut variables = Irept::from("tuple");
+            variables
+                .named_subt
+                .insert(String::from("type"), Irept::from("tuple"));
+            variables.subt = vec![irep.subt.remove(0)];
+            irep.subt.insert(0, variables);
+        }
+
+        rules.apply(irep);
+
+        if irep.id == "constant" && irep.named_subt.contains_key("value") {
+            fix_constant(irep);
+        }
+
+        // Types
+        if ["pointer", "array"].contains(&irep.id.as_str()) {
+            if let Some(subtype) = irep.named_subt.remove("subtype") {
+                // Pointers from CBMC keep their subtype inside an irep
+                // without id (see Irept::fix_type)
+                irep.subt = match subtype.id.is_empty() && subtype.subt.len() == 1 {
+                    true => subtype.subt,
+                    false => vec![subtype],
+                };
+            }
+        }
+
+        if irep.id == "pointer" && !irep.named_subt.contains_key("width") {
+            irep.named_subt
+                .insert("width".to_string(), Irept::from(pointer_width.to_string()));
+        }
+
+        if irep.id == "code" {
+            if let Some(mut arguments) = irep.named_subt.remove("arguments") {
+                if !irep.named_subt.contains_key("parameters") {
+                    arguments.id = String::default();
+                    for argument in &mut arguments.subt {
+                        argument.id = "parameter".to_string();
+                    }
+                    irep.named_subt.insert("parameters".to_string(), arguments);
+                }
+            }
+        }
+
+        // A "symbol" without a type is either a reference to a tagged type
+        // or a symbol whose type is in the symbol table
+        if irep.id == "symbol" && !irep.named_subt.contains_key("type") {
+            let identifier = irep
+                .named_subt
+                .get("identifier")
+                .map(|identifier| identifier.id.clone())
+                .unwrap_or_default();
+            if let Some(kind) = tag_kinds.get(&identifier) {
+                irep.id = kind.clone();
+            } else if let Some(stype) = types.get(&identifier) {
+                irep.named_subt.insert(String::from("type"), stype.clone());
+            }
+        }
+
+        for sub in &mut irep.subt {
+            fix_expression(sub, tag_kinds, types, pointer_width, rules);
+        }
+
+        for (k, v) in irep.named_subt.iter_mut() {
+            if !NAMES.contains(&k.as_str()) {
+                fix_expression(v, tag_kinds, types, pointer_width, rules);
+            }
+        }
+    }
+}
+
+fn esbmc_symbol_to_cbmc(
+    mut irep: Irept,
+    tag_kinds: &HashMap<String, String>,
+    types: &HashMap<String, Irept>,
+    pointer_width: u64,
+    rules: &RuleSet,
+) -> CBMCSymbol {
+    cbmcfixes::fix_expression(&mut irep, tag_kinds, types, pointer_width, rules);
+
+    let mut take = |key: &str| irep.named_subt.remove(key).unwrap_or_default();
+    let mut result = CBMCSymbol::default();
+    result.stype = take("type");
+    result.value = take("symvalue");
+    result.location = take("location");
+    result.name = cbmcfixes::fix_name(&take("name").id);
+    result.module = take("module").id;
+    result.base_name = cbmcfixes::fix_name(&take("base_name").id);
+    result.mode = take("mode").id;
+    result.pretty_name = result.base_name.clone();
+
+    let flag = |key: &str| irep.named_subt.get(key).is_some_and(|x| x.id == "1");
+    result.is_type = flag("is_type");
+    result.is_macro = flag("is_macro");
+    result.is_parameter = flag("is_parameter");
+    result.is_lvalue = flag("lvalue");
+    result.is_static_lifetime = flag("static_lifetime");
+    result.is_file_local = flag("file_local");
+    result.is_extern = flag("is_extern");
+    result.flags = result.pack_flags();
+    result
+}
+
+fn esbmc_instruction_to_cbmc(
+    mut irep: Irept,
+    tag_kinds: &HashMap<String, String>,
+    types: &HashMap<String, Irept>,
+    pointer_width: u64,
+    rules: &RuleSet,
+) -> Result<CBMCInstruction, String> {
+    cbmcfixes::fix_expression(&mut irep, tag_kinds, types, pointer_width, rules);
+
+    let mut take = |key: &str| irep.named_subt.remove(key).unwrap_or_default();
+    let mut code = take("code");
+    let source_location = take("location");
+    let typeid = take("typeid").id;
+    let kind = typeid
+        .parse()
+        .ok()
+        .and_then(ESBMCInstructionKind::from_u32)
+        .ok_or_else(|| format!("Unknown instruction kind {}", typeid))?;
+    let instr_type = match cbmc_instruction_kind(kind) {
+        Some(kind) => kind,
+        None => {
+            warn!(
+                "{} instructions are not supported by CBMC, replaced by SKIP",
+                kind.name()
+            );
+            code = Irept::from("nil");
+            CBMCInstructionKind::Skip
+        }
+    } as u32;
+    let guard = take("guard");
+    let targets = take("targets").subt;
+    let labels = take("labels").subt.into_iter().map(|l| l.id).collect();
+    let function = take("function");
+
+    Ok(CBMCInstruction {
+        code,
+        source_location,
+        instr_type,
+        guard,
+        target_number: NO_TARGET_NUMBER,
+        targets,
+        labels,
+        function,
+    })
+}
+
+fn esbmc_function_to_cbmc(
+    name: &str,
+    irep: Irept,
+    tag_kinds: &HashMap<String, String>,
+    types: &HashMap<String, Irept>,
+    pointer_width: u64,
+    rules: &RuleSet,
+) -> Result<CBMCFunction, String> {
+    let mut function = CBMCFunction {
+        name: cbmcfixes::fix_name(name),
+        instructions: irep
+            .subt
+            .into_iter()
+            .map(|i| esbmc_instruction_to_cbmc(i, tag_kinds, types, pointer_width, rules))
+            .collect::<Result<Vec<CBMCInstruction>, String>>()?,
+    };
+
+    // NOTE: ESBMC targets are instruction offsets while CBMC targets are
+    //       target numbers (starting from 1) assigned to every instruction
+    //       that is the target of a goto.
+    let count = function.instructions.len();
+    let offsets = function
+        .instructions
+        .iter()
+        .map(|i| {
+            i.targets
+                .iter()
+                .map(|t| match t.id.parse() {
+                    Ok(offset) if offset < count => Ok(offset),
+                    _ => Err(format!("Invalid target {}", t.id)),
+                })
+                .collect::<Result<Vec<usize>, String>>()
+        })
+        .collect::<Result<Vec<Vec<usize>>, String>>()?;
+    let mut targeted: Vec<usize> = offsets.iter().flatten().copied().collect();
+    targeted.sort();
+    targeted.dedup();
+
+    let target_map: HashMap<usize, u32> = targeted
+        .into_iter()
+        .enumerate()
+        .map(|(number, offset)| (offset, number as u32 + 1))
+        .collect();
+
+    for (offset, (instr, targets)) in function.instructions.iter_mut().zip(offsets).enumerate() {
+        if let Some(number) = target_map.get(&offset) {
+            instr.target_number = *number;
+        }
+        for (t, target) in instr.targets.iter_mut().zip(targets) {
+            t.id = target_map[&target].to_string();
+        }
+    }
+
+    Ok(function)
 }
 
 #[derive(Clone, Debug)]
@@ -361,22 +1112,31 @@ enum Component {
     Struct {
         components: Vec<(String, Component)>,
     },
+    Union {
+        components: Vec<(String, Component)>,
+    },
     Unsigned {
         width: usize,
     },
     Signed {
         width: usize,
     },
+    Float {
+        width: usize,
+    },
+    Bool,
     Void,
     Pointer {
         to: Box<Component>,
     },
+    Array {
+        size: usize,
+        of: Box<Component>,
+    },
 }
 
-impl Component {}
-
-fn from_struct(components: Vec<(String, Component)>) -> Irept {
-    let mut result = Irept::from("struct");
+fn from_components(id: &str, components: Vec<(String, Component)>) -> Irept {
+    let mut result = Irept::from(id);
     let mut subt: Irept = Irept::from("components");
     for (name, component) in components {
         let mut irep = Irept::from("component");
@@ -409,26 +1169,69 @@ fn from_signed(width: usize) -> Irept {
     result
 }
 
-fn from_pointer(to: Box<Component>) -> Irept {
+// The name only has the total width, the fraction is the IEEE 754 one
+fn from_float(width: usize) -> Irept {
+    let fraction = match width {
+        16 => 10,
+        32 => 23,
+        64 => 52,
+        80 => 64,
+        128 => 112,
+        _ => unreachable!("checked while parsing"),
+    };
+    let mut result = Irept::from("floatbv");
+    result
+        .named_subt
+        .insert("width".to_string(), Irept::from(width.to_string()));
+    result
+        .named_subt
+        .insert("f".to_string(), Irept::from(fraction.to_string()));
+    result
+}
+
+fn from_pointer(to: Component) -> Irept {
     let mut result = Irept::from("pointer");
     result
         .named_subt
-        .insert("subtype".to_string(), Irept::from(*to));
+        .insert("subtype".to_string(), Irept::from(to));
+    result
+}
+
+fn from_array(size: usize, of: Component) -> Irept {
+    let mut constant = Irept::from("constant");
+    constant
+        .named_subt
+        .insert("type".to_string(), from_unsigned(64));
+    constant
+        .named_subt
+        .insert("value".to_string(), Irept::from(format!("{:064b}", size)));
+
+    let mut result = Irept::from("array");
+    result
+        .named_subt
+        .insert("subtype".to_string(), Irept::from(of));
+    result.named_subt.insert("size".to_string(), constant);
     result
 }
 
 impl From<Component> for Irept {
     fn from(data: Component) -> Self {
         match data {
-            Component::Struct { components } => from_struct(components),
+            Component::Struct { components } => from_components("struct", components),
+            Component::Union { components } => from_components("union", components),
             Component::Unsigned { width } => from_unsigned(width),
             Component::Signed { width } => from_signed(width),
+            Component::Float { width } => from_float(width),
+            Component::Bool => Irept::from("bool"),
             Component::Void => Irept::from("empty"),
-            Component::Pointer { to } => from_pointer(to),
+            Component::Pointer { to } => from_pointer(*to),
+            Component::Array { size, of } => from_array(size, *of),
         }
     }
 }
 
+// Parser for the names CBMC gives to anonymous types (see type2name.cpp),
+// e.g. ST[S32'a'|*{SYM#0={ST[U8'b']}}'c'|ARR4{F64}'d']
 #[derive(Clone, Debug)]
 struct Anon2Struct {
     bytes: Vec<u8>,
@@ -437,174 +1240,237 @@ struct Anon2Struct {
 }
 
 impl Anon2Struct {
-    // Basic LL(k) parser.
-    fn parse_component(&mut self) -> Component {
-        assert!(self.counter + 3 <= self.bytes.len());
-        if &self.bytes[self.counter..self.counter + 3] == "ST[".as_bytes() {
-            self.counter = self.counter + 3;
-            return self.parse_struct();
-        } else if &self.bytes[self.counter..self.counter + 3] == "SYM".as_bytes() {
-            self.counter = self.counter + 3;
-            return self.parse_sym();
-        } else if &self.bytes[self.counter..self.counter + 1] == "S".as_bytes() {
-            self.counter = self.counter + 1;
-            return self.parse_signed();
-        } else if &self.bytes[self.counter..self.counter + 1] == "U".as_bytes() {
-            self.counter = self.counter + 1;
-            return self.parse_unsigned();
-        } else if &self.bytes[self.counter..self.counter + 1] == "V".as_bytes() {
-            self.counter = self.counter + 1;
-            return Component::Void;
-        } else if &self.bytes[self.counter..self.counter + 2] == "*{".as_bytes() {
-            self.counter = self.counter + 2;
-            return self.parse_pointer();
-        }
-        panic!("Missing something?");
-    }
-
-    fn parse_pointer(&mut self) -> Component {
-        let component = self.parse_component();
-        assert!(&self.bytes[self.counter..self.counter + 1] == "}".as_bytes());
-        self.counter = self.counter + 1;
-        Component::Pointer {
-            to: Box::from(component),
-        }
-    }
-
-    fn parse_unsigned(&mut self) -> Component {
-        let mut id: Vec<u8> = Vec::new();
-        let _ = loop {
-            let char = &self.bytes[self.counter..self.counter + 1];
-
-            self.counter = self.counter + 1;
-            if char == "'".as_bytes() {
-                self.counter = self.counter - 1;
-                break;
-            }
-            id.push(char[0]);
+    fn error<T>(&self, expected: &str) -> Result<T, String> {
+        let found = match self.bytes.get(self.counter) {
+            Some(c) => format!("'{}'", *c as char),
+            None => String::from("end of name"),
         };
+        Err(format!(
+            "expected {} but found {} at {}",
+            expected, found, self.counter
+        ))
+    }
 
-        let identifier = String::from_utf8_lossy(&id).to_string();
-        let width: usize = identifier.as_str().parse().unwrap();
-        Component::Unsigned { width }
+    fn consume(&mut self, token: &str) -> bool {
+        if self.bytes[self.counter..].starts_with(token.as_bytes()) {
+            self.counter += token.len();
+            return true;
+        }
+        false
     }
 
-    fn parse_signed(&mut self) -> Component {
-        let mut id: Vec<u8> = Vec::new();
-        let _ = loop {
-            let char = &self.bytes[self.counter..self.counter + 1];
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.consume(token) {
+            return Ok(());
+        }
+        self.error(&format!("'{}'", token))
+    }
 
-            self.counter = self.counter + 1;
-            if char == "'".as_bytes() {
-                self.counter = self.counter - 1;
-                break;
+    // Basic LL(k) parser.
+    fn parse_component(&mut self) -> Result<Component, String> {
+        // Qualifiers (const, restrict, volatile, transparent union) do not
+        // change the layout
+        while ["c", "r", "v", "t"].iter().any(|q| self.consume(q)) {}
+
+        if self.consume("ST[") {
+            return Ok(Component::Struct {
+                components: self.parse_components()?,
+            });
+        } else if self.consume("UN[") {
+            return Ok(Component::Union {
+                components: self.parse_components()?,
+            });
+        } else if self.consume("SYM") {
+            return self.parse_sym();
+        } else if self.consume("ARR") {
+            let size = self.parse_number()?;
+            return Ok(Component::Array {
+                size,
+                of: Box::from(self.parse_subtype()?),
+            });
+        } else if self.consume("CB") {
+            // Same as fix_type does for c_bool
+            return Ok(Component::Signed {
+                width: self.parse_number()?,
+            });
+        } else if self.consume("S") {
+            return Ok(Component::Signed {
+                width: self.parse_number()?,
+            });
+        } else if self.consume("U") {
+            return Ok(Component::Unsigned {
+                width: self.parse_number()?,
+            });
+        } else if self.consume("F") {
+            let width = self.parse_number()?;
+            if ![16, 32, 64, 80, 128].contains(&width) {
+                return Err(format!("unsupported float width {}", width));
             }
-            id.push(char[0]);
-        };
-
-        let identifier = String::from_utf8_lossy(&id).to_string();
-        let width: usize = identifier.as_str().parse().unwrap();
-        Component::Signed { width }
+            return Ok(Component::Float { width });
+        } else if self.consume("B") {
+            return Ok(Component::Bool);
+        } else if self.consume("V") {
+            return Ok(Component::Void);
+        } else if self.consume("*") {
+            return Ok(Component::Pointer {
+                to: Box::from(self.parse_subtype()?),
+            });
+        }
+        self.error("a type")
     }
 
-    fn parse_name(&mut self) -> String {
-        self.counter = self.counter + 1;
-        let mut id: Vec<u8> = Vec::new();
-        let _ = loop {
-            let char = &self.bytes[self.counter..self.counter + 1];
+    fn parse_subtype(&mut self) -> Result<Component, String> {
+        self.expect("{")?;
+        let component = self.parse_component()?;
+        self.expect("}")?;
+        Ok(component)
+    }
 
-            self.counter = self.counter + 1;
-            if char == "'".as_bytes() {
-                break;
+    fn parse_number(&mut self) -> Result<usize, String> {
+        let start = self.counter;
+        while self.bytes.get(self.counter).is_some_and(u8::is_ascii_digit) {
+            self.counter += 1;
+        }
+        match String::from_utf8_lossy(&self.bytes[start..self.counter]).parse() {
+            Ok(number) => Ok(number),
+            Err(_) => {
+                self.counter = start;
+                self.error("a number")
             }
-            id.push(char[0]);
-        };
+        }
+    }
 
-        String::from_utf8_lossy(&id).to_string()
+    fn parse_name(&mut self) -> Result<String, String> {
+        self.expect("'")?;
+        let start = self.counter;
+        while self.bytes.get(self.counter).is_some_and(|c| *c != b'\'') {
+            self.counter += 1;
+        }
+        let name = String::from_utf8_lossy(&self.bytes[start..self.counter]).to_string();
+        self.expect("'")?;
+        Ok(name)
     }
 
-    fn parse_struct(&mut self) -> Component {
+    fn parse_components(&mut self) -> Result<Vec<(String, Component)>, String> {
         let mut components: Vec<(String, Component)> = Vec::new();
-        let _ = loop {
-            let char = &self.bytes[self.counter..self.counter + 1];
-            if char == "]".as_bytes() {
-                self.counter = self.counter + 1;
-                break;
-            } else if char == "|".as_bytes() {
-                self.counter = self.counter + 1;
-            };
-
-            let component = self.parse_component();
-            assert!(&self.bytes[self.counter..self.counter + 1] == "'".as_bytes());
-            let name = self.parse_name();
+        while !self.consume("]") {
+            if !components.is_empty() {
+                self.expect("|")?;
+            }
+            let component = self.parse_component()?;
+            let name = self.parse_name()?;
             components.push((name, component));
-        };
-
-        Component::Struct { components }
+        }
+        Ok(components)
     }
 
-    fn parse_sym(&mut self) -> Component {
-        let mut id: Vec<u8> = Vec::new();
-        let result = loop {
-            let char = &self.bytes[self.counter..self.counter + 1];
-
-            self.counter = self.counter + 1;
-            if char == "'".as_bytes() || char == "}".as_bytes() {
-                self.counter = self.counter - 1;
-                break false;
-            }
-            if char == "=".as_bytes() {
-                self.counter = self.counter + 1;
-                break true;
-            }
-            id.push(char[0]);
-        };
-
-        let identifier = String::from_utf8_lossy(&id).to_string();
-        if result {
-            let component = self.parse_component();
-            self.counter = self.counter + 1;
+    // SYM#<n>={<type>} defines a symbol which can be referred later as SYM#<n>
+    fn parse_sym(&mut self) -> Result<Component, String> {
+        self.expect("#")?;
+        let identifier = self.parse_number()?.to_string();
+        if self.consume("=") {
+            let component = self.parse_subtype()?;
             self.cache.insert(identifier, component.clone());
-            return component;
+            return Ok(component);
         }
 
-        return self.cache[&identifier].clone();
+        match self.cache.get(&identifier) {
+            Some(component) => Ok(component.clone()),
+            None => Err(format!(
+                "undefined symbol #{} at {}",
+                identifier, self.counter
+            )),
+        }
     }
 }
 
 impl Irept {
+    // ESBMC has no parser for the anon naming conventions. Types that cannot
+    // be parsed are kept as is, as they might still be found in the cache.
     pub fn expand_anon_struct(&mut self) {
         if self.named_subt.contains_key("components") {
             return;
         }
-        // ESBMC has no parser for this anon naming conventions.
-        let identifier = self.named_subt["identifier"].id.as_bytes();
-        if identifier.len() < 11 || &identifier[0..10] != "tag-#anon#".as_bytes() {
-            return;
-        }
-        panic!("Got anon struct {}", self.to_string());
+        let identifier = self.named_subt["identifier"].id.clone();
+        let name = match identifier.strip_prefix("tag-#anon#") {
+            Some(name) => name,
+            None => return,
+        };
         let mut parser = Anon2Struct {
-            bytes: Vec::from(identifier),
-            counter: 10,
+            bytes: Vec::from(name.as_bytes()),
+            counter: 0,
             cache: HashMap::new(),
         };
-        let parsed_struct = Irept::from(parser.parse_component());
-        let components = parsed_struct.named_subt.get("components").unwrap().clone();
+        let parsed = match parser.parse_component() {
+            Ok(component) if parser.counter == parser.bytes.len() => Irept::from(component),
+            Ok(_) => {
+                warn!(
+                    "Could not parse {}: trailing {}",
+                    identifier, parser.counter
+                );
+                return;
+            }
+            Err(e) => {
+                warn!("Could not parse {}: {}", identifier, e);
+                return;
+            }
+        };
+        if !["struct", "union"].contains(&parsed.id.as_str()) {
+            warn!("Could not parse {}: not a struct or union", identifier);
+            return;
+        }
+
+        let components = parsed.named_subt["components"].clone();
         self.named_subt.insert("components".to_string(), components);
+        self.id = parsed.id;
+    }
 
-        self.id = "struct".to_string();
+    // Pointers are the only place where a type can refer to itself. Instead
+    // of expanding the tag we keep it as a "symbol" type which ESBMC resolves
+    // through the symbol table. Anonymous tags are still expanded as their
+    // names already describe the whole type.
+    fn fix_tag_reference(&mut self) -> bool {
+        if !["struct_tag", "union_tag"].contains(&self.id.as_str()) {
+            return false;
+        }
+        match self.named_subt.get("identifier") {
+            Some(identifier) if !identifier.id.starts_with("tag-#anon#") => {
+                self.id = "symbol".to_string();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // CBMC keeps the underlying type as subtype, older versions only had a width
+    fn fix_enum(&mut self) {
+        let subtype = match self.named_subt.remove("subtype") {
+            Some(subtype) => subtype,
+            None if !self.subt.is_empty() => self.subt.remove(0),
+            None => {
+                let mut signed = Irept::from("signedbv");
+                if let Some(width) = self.named_subt.remove("width") {
+                    signed.named_subt.insert("width".to_string(), width);
+                }
+                signed
+            }
+        };
+        *self = subtype;
     }
 
     pub fn fix_struct(&mut self) {
         self.id = "component".to_string();
     }
 
-    pub fn fix_type(&mut self, cache: &HashMap<Irept, Irept>) {
-        //
+    pub fn fix_type(&mut self, cache: &HashMap<Irept, Irept>) -> Result<(), String> {
+        // ESBMC has no enums, they are just their underlying integer type
+        if self.id == "c_enum" {
+            self.fix_enum();
+        }
 
         if self.id == "c_bool" {
             self.id = String::from("signedbv");
-            return;
+            return Ok(());
         }
 
         if self.id == "code" && self.named_subt.contains_key("parameters") {
@@ -622,7 +1488,9 @@ impl Irept {
 
         if self.id == "pointer" && !self.named_subt.contains_key("subtype") {
             for v in &mut self.subt {
-                v.fix_type(cache);
+                if !v.fix_tag_reference() {
+                    v.fix_type(cache)?;
+                }
             }
             let mut operands = Irept::default();
             operands.subt = self.subt.clone();
@@ -630,69 +1498,73 @@ impl Irept {
             self.subt.clear();
         }
 
-        if self.id == "array" && !self.named_subt.contains_key("subtype") && self.subt.len() > 0 {
+        if self.id == "array" && !self.named_subt.contains_key("subtype") && !self.subt.is_empty() {
             let magic = self.subt[0].clone();
             self.named_subt.insert("subtype".to_string(), magic);
             self.subt.clear();
             // NOTE: For some unknown reason, CBMC can't decide whether array
             //sizes should be in binary or in hexa :)
             for (k, v) in &mut self.named_subt {
-                if k == "size" {
-                    if v.named_subt.contains_key("value") {
-                        esbmcfixes::fix_expression(v);
-                    }
+                if k == "size" && v.named_subt.contains_key("value") {
+                    esbmcfixes::fix_constant(v)?;
                 }
             }
         }
 
-        if self.id != "struct_tag" {
+        if !TAG_TYPES.contains(&self.id.as_str()) {
             for v in &mut self.subt {
-                v.fix_type(cache);
+                v.fix_type(cache)?;
             }
 
-            for (_, v) in &mut self.named_subt {
-                v.fix_type(cache);
+            for v in self.named_subt.values_mut() {
+                v.fix_type(cache)?;
             }
 
-            for (_, v) in &mut self.comments {
-                v.fix_type(cache);
+            for v in self.comments.values_mut() {
+                v.fix_type(cache)?;
             }
 
-            return;
+            // Enum constants could only be fixed once their type was known
+            if self.id == "constant" {
+                esbmcfixes::fix_constant(self)?;
+            }
+            return Ok(());
         }
 
         if !self.named_subt.contains_key("identifier") {
-            return;
+            return Ok(());
         }
 
         if !cache.contains_key(&self.named_subt["identifier"]) {
             trace!("Cache miss {}", self.to_string());
             self.expand_anon_struct();
             //self.fix_type(cache);
-            return;
+            return Ok(());
         }
 
         *self = cache[&self.named_subt["identifier"]].clone();
 
         // redo cache
-        if irep_contains(self, "struct_tag") {
+        if TAG_TYPES.iter().any(|tag| irep_contains(self, tag)) {
             for v in &mut self.subt {
-                v.fix_type(cache);
+                v.fix_type(cache)?;
             }
 
-            for (_, v) in &mut self.named_subt {
-                v.fix_type(cache);
+            for v in self.named_subt.values_mut() {
+                v.fix_type(cache)?;
             }
 
-            for (_, v) in &mut self.comments {
-                v.fix_type(cache);
+            for v in self.comments.values_mut() {
+                v.fix_type(cache)?;
             }
         }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::rules::RuleSet;
     use std::process::Command;
 
     fn generate_cbmc_gbf(input_c: &str, output_goto: &str) {
@@ -700,7 +1572,7 @@ mod tests {
             Ok(v) => v,
             Err(err) => panic!("Could not get GOTO_CC bin. {}", err),
         };
-        assert!(input_c.len() != 0);
+        assert!(!input_c.is_empty());
         println!("Invoking cbmc with: {}", input_c);
 
         let output = Command::new(goto_cc)
@@ -712,14 +1584,8 @@ mod tests {
 
         if !output.status.success() {
             println!("CBMC exited with {}", output.status);
-            println!(
-                "\tSTDOUT: {}",
-                String::from_utf8_lossy(&output.stdout).to_string()
-            );
-            println!(
-                "\tSTDERR: {}",
-                String::from_utf8_lossy(&output.stderr).to_string()
-            );
+            println!("\tSTDOUT: {}", String::from_utf8_lossy(&output.stdout));
+            println!("\tSTDERR: {}", String::from_utf8_lossy(&output.stderr));
             panic!("GOTO-CC failed");
         }
     }
@@ -738,21 +1604,26 @@ mod tests {
 
         if !output.status.success() {
             println!("ESBMC exited with {}", output.status);
-            println!(
-                "\tSTDOUT: {}",
-                String::from_utf8_lossy(&output.stdout).to_string()
-            );
-            println!(
-                "\tSTDERR: {}",
-                String::from_utf8_lossy(&output.stderr).to_string()
-            );
+            println!("\tSTDOUT: {}", String::from_utf8_lossy(&output.stdout));
+            println!("\tSTDERR: {}", String::from_utf8_lossy(&output.stderr));
         }
         assert_eq!(status, output.status.code().unwrap());
     }
 
-    use crate::cbmc;
+    use super::{
+        cbmc_instruction_kind, cbmcfixes, esbmc_function_to_cbmc, esbmc_instruction_kind,
+        esbmcfixes, irep_contains, IrepAdapter, TAG_TYPES,
+    };
+    use crate::bytereader::ByteReader;
+    use crate::cbmc::{
+        CBMCFunction, CBMCInstruction, CBMCInstructionKind, CBMCLayout, CBMCParseResult,
+        CBMCSymbol, NO_TARGET_NUMBER,
+    };
     use crate::cbmc2esbmc;
-    use crate::ByteWriter;
+    use crate::esbmc::{ESBMCInstructionKind, ESBMCParseResult};
+    use crate::irep::Irept;
+    use crate::resources::get_test_path;
+    use std::collections::HashMap;
 
     fn run_test(input_c: &str, args: &[&str], expected: i32) {
         let cargo_dir = match std::env::var("CARGO_MANIFEST_DIR") {
@@ -766,7 +1637,13 @@ mod tests {
 
         generate_cbmc_gbf(test_path.to_str().unwrap(), cbmc_gbf.as_str());
 
-        cbmc2esbmc("__CPROVER__start", cbmc_gbf.as_str(), esbmc_gbf.as_str());
+        cbmc2esbmc(
+            None,
+            cbmc_gbf.as_str(),
+            esbmc_gbf.as_str(),
+            &RuleSet::default(),
+        )
+        .unwrap();
         run_esbmc_gbf(&esbmc_gbf, args, expected);
         std::fs::remove_file(&cbmc_gbf).ok();
         std::fs::remove_file(&esbmc_gbf).ok();
@@ -782,14 +1659,1009 @@ mod tests {
 
         let esbmc_gbf = format!("{}.goto", input_goto); // TODO: generate UUID!
         cbmc2esbmc(
-            "__CPROVER__start",
+            None,
             test_path.to_str().unwrap(),
             esbmc_gbf.as_str(),
-        );
+            &RuleSet::default(),
+        )
+        .unwrap();
         run_esbmc_gbf(&esbmc_gbf, args, expected);
         std::fs::remove_file(&esbmc_gbf).ok();
     }
 
+    #[test]
+    fn binary_to_hex() {
+        assert_eq!(
+            cbmcfixes::binary_to_hex("00000000000000000000000000000000"),
+            "0"
+        );
+        assert_eq!(cbmcfixes::binary_to_hex("00101010"), "2A");
+        assert_eq!(cbmcfixes::binary_to_hex("11111111"), "FF");
+        assert_eq!(cbmcfixes::binary_to_hex("100000000"), "100");
+    }
+
+    #[test]
+    fn hex_to_binary() {
+        assert_eq!(esbmcfixes::hex_to_binary("0", 8).unwrap(), "00000000");
+        assert_eq!(
+            esbmcfixes::hex_to_binary("2A", 16).unwrap(),
+            "0000000000101010"
+        );
+        // i8 -1 is written as FF, CBMC already uses two's complement
+        assert_eq!(esbmcfixes::hex_to_binary("FF", 8).unwrap(), "11111111");
+        assert_eq!(esbmcfixes::hex_to_binary("-1", 8).unwrap(), "11111111");
+        assert_eq!(esbmcfixes::hex_to_binary("-80", 8).unwrap(), "10000000");
+        assert_eq!(esbmcfixes::hex_to_binary("100", 8), None);
+        assert_eq!(esbmcfixes::hex_to_binary("0G", 8), None);
+
+        let i64_min = esbmcfixes::hex_to_binary("8000000000000000", 64).unwrap();
+        assert_eq!(i64_min, format!("1{}", "0".repeat(63)));
+        let u128_max = esbmcfixes::hex_to_binary(&"F".repeat(32), 128).unwrap();
+        assert_eq!(u128_max, "1".repeat(128));
+        assert_eq!(cbmcfixes::binary_to_hex(&u128_max), "F".repeat(32));
+    }
+
+    #[test]
+    fn constant_widths() {
+        let constant = |value: &str, id: &str, width: &str| {
+            let mut ctype = Irept::from(id);
+            ctype
+                .named_subt
+                .insert(String::from("width"), Irept::from(width));
+            let mut irep = Irept::from("constant");
+            irep.named_subt
+                .insert(String::from("value"), Irept::from(value));
+            irep.named_subt.insert(String::from("type"), ctype);
+            esbmcfixes::fix_expression(&mut irep, &RuleSet::default()).unwrap();
+            irep.named_subt["value"].id.clone()
+        };
+        assert_eq!(constant("FF", "signedbv", "8"), "11111111");
+        assert_eq!(constant("1", "unsignedbv", "16"), "0000000000000001");
+        assert_eq!(constant("1", "c_bool", "8"), "00000001");
+        assert_eq!(
+            constant("FFFFFFFFFFFFFFFF", "unsignedbv", "64"),
+            "1".repeat(64)
+        );
+        assert_eq!(
+            constant("10000000000000000", "unsignedbv", "128"),
+            format!("{}1{}", "0".repeat(63), "0".repeat(64))
+        );
+        assert_eq!(constant("NULL", "pointer", "64"), "NULL");
+        // Already translated
+        assert_eq!(constant("00000011", "unsignedbv", "8"), "00000011");
+
+        // Constants that do not fit are reported with their symbol
+        let mut ctype = Irept::from("unsignedbv");
+        ctype
+            .named_subt
+            .insert(String::from("width"), Irept::from("8"));
+        let mut value = Irept::from("constant");
+        value
+            .named_subt
+            .insert(String::from("value"), Irept::from("1FF"));
+        value.named_subt.insert(String::from("type"), ctype.clone());
+        let symbol = CBMCSymbol {
+            name: String::from("main::x"),
+            stype: ctype,
+            value,
+            ..Default::default()
+        };
+        assert_eq!(
+            symbol
+                .to_esbmc_irep("main", &RuleSet::default())
+                .err()
+                .unwrap(),
+            "Symbol main::x: Constant 1FF does not fit into 8 bits of unsignedbv"
+        );
+    }
+
+    #[test]
+    fn unresolved_tags() {
+        // Not in the symbol table, and an anonymous struct that cannot be
+        // parsed from its name
+        for identifier in ["tag-foo", "tag-#anon#?"] {
+            let mut stype = Irept::from("struct_tag");
+            stype
+                .named_subt
+                .insert(String::from("identifier"), Irept::from(identifier));
+            let symbol = CBMCSymbol {
+                name: String::from("x"),
+                base_name: String::from("x"),
+                stype,
+                value: Irept::from("nil"),
+                ..Default::default()
+            };
+            let result = CBMCParseResult {
+                reader: ByteReader::from(vec![]),
+                symbols_irep: vec![symbol],
+                functions_irep: vec![],
+                entrypoint: String::from("main"),
+                layout: CBMCLayout::default(),
+            };
+            assert_eq!(
+                ESBMCParseResult::try_from(result).err(),
+                Some(format!("Symbol x: unresolved tag {}", identifier))
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_instructions() {
+        let lower = |code: Irept| {
+            let function = CBMCFunction {
+                name: String::from("main"),
+                instructions: vec![CBMCInstruction {
+                    code,
+                    source_location: Irept::from("nil"),
+                    instr_type: CBMCInstructionKind::Assign as u32,
+                    guard: Irept::from("true"),
+                    target_number: NO_TARGET_NUMBER,
+                    targets: vec![],
+                    labels: vec![],
+                    function: Irept::default(),
+                }],
+            };
+            function
+                .to_esbmc_irep("main", &RuleSet::default())
+                .err()
+                .unwrap()
+        };
+
+        let mut code = Irept::from("code");
+        assert_eq!(
+            lower(code.clone()),
+            "Function main: Assign without statement"
+        );
+        code.named_subt
+            .insert(String::from("statement"), Irept::from("assign"));
+        code.subt = vec![Irept::from("nil")];
+        assert_eq!(lower(code), "Function main: assign with 1 operands");
+    }
+
+    #[test]
+    fn unsupported_intrinsics() {
+        let mut function = Irept::from("symbol");
+        function
+            .named_subt
+            .insert(String::from("identifier"), Irept::from("__CPROVER_cover"));
+        let mut code = Irept::from("code");
+        code.named_subt
+            .insert(String::from("statement"), Irept::from("function_call"));
+        code.subt = vec![Irept::from("nil"), function, Irept::from("arguments")];
+        let mut call = CBMCInstruction {
+            code,
+            source_location: Irept::from("nil"),
+            instr_type: CBMCInstructionKind::FunctionCall as u32,
+            guard: Irept::from("true"),
+            target_number: NO_TARGET_NUMBER,
+            targets: vec![],
+            labels: vec![],
+            function: Irept::default(),
+        };
+        let result = CBMCParseResult {
+            reader: ByteReader::from(vec![]),
+            symbols_irep: vec![],
+            functions_irep: vec![CBMCFunction {
+                name: String::from("main"),
+                instructions: vec![call.clone()],
+            }],
+            entrypoint: String::from("main"),
+            layout: CBMCLayout::default(),
+        };
+        assert_eq!(
+            ESBMCParseResult::try_from(result).err().unwrap(),
+            "Intrinsics without ESBMC counterpart: __CPROVER_cover"
+        );
+
+        // Others have an operational model, which reads the object size
+        // through ESBMC
+        let p = Irept::symbol("main::p", Irept::pointer(Irept::from("empty"), 64));
+        call.code.subt[1] = Irept::symbol("__CPROVER_havoc_object", Irept::from("code"));
+        call.code.subt[2] = Irept {
+            id: String::from("arguments"),
+            subt: vec![p],
+            ..Default::default()
+        };
+        let result = CBMCParseResult {
+            reader: ByteReader::from(vec![]),
+            symbols_irep: vec![],
+            functions_irep: vec![CBMCFunction {
+                name: String::from("main"),
+                instructions: vec![call],
+            }],
+            entrypoint: String::from("main"),
+            layout: CBMCLayout::default(),
+        };
+        let converted = ESBMCParseResult::try_from(result).unwrap();
+        assert!(converted
+            .symbols_irep
+            .iter()
+            .any(|s| s.named_subt["name"].id == "c:@F@__ESBMC_get_object_size"));
+    }
+
+    fn float_type(width: &str, f: &str) -> Irept {
+        let mut ftype = Irept::from("floatbv");
+        ftype
+            .named_subt
+            .insert(String::from("width"), Irept::from(width));
+        ftype.named_subt.insert(String::from("f"), Irept::from(f));
+        ftype
+    }
+
+    #[test]
+    fn float_constants() {
+        let mut irep = Irept::from("constant");
+        irep.named_subt
+            .insert(String::from("value"), Irept::from("3F800000"));
+        irep.named_subt
+            .insert(String::from("type"), float_type("32", "23"));
+        esbmcfixes::fix_expression(&mut irep, &RuleSet::default()).unwrap();
+        assert_eq!(
+            irep.named_subt["value"].id,
+            "00111111100000000000000000000000"
+        );
+        assert_eq!(irep.named_subt["type"].named_subt["f"].id, "23");
+
+        cbmcfixes::fix_expression(
+            &mut irep,
+            &HashMap::new(),
+            &HashMap::new(),
+            64,
+            &RuleSet::default().inverted(),
+        );
+        assert_eq!(irep.named_subt["value"].id, "3F800000");
+    }
+
+    #[test]
+    fn byte_operators() {
+        for id in [
+            "byte_extract_little_endian",
+            "byte_extract_big_endian",
+            "byte_update_little_endian",
+            "byte_update_big_endian",
+        ] {
+            let mut irep = Irept::from(id);
+            irep.subt = vec![Irept::from("source"), Irept::from("offset")];
+            if id.starts_with("byte_update") {
+                irep.subt.push(Irept::from("value"));
+            }
+            let original = irep.clone();
+
+            // The endianness stays in the name
+            esbmcfixes::fix_expression(&mut irep, &RuleSet::default()).unwrap();
+            assert_eq!(irep.id, id);
+            assert_eq!(irep.named_subt["operands"].subt, original.subt);
+
+            cbmcfixes::fix_expression(
+                &mut irep,
+                &HashMap::new(),
+                &HashMap::new(),
+                64,
+                &RuleSet::default().inverted(),
+            );
+            assert_eq!(irep, original);
+        }
+    }
+
+    #[test]
+    fn quantifiers() {
+        let int = || {
+            let mut result = Irept::from("signedbv");
+            result
+                .named_subt
+                .insert(String::from("width"), Irept::from("32"));
+            result
+        };
+        let (x, y) = (
+            Irept::symbol("main::x", int()),
+            Irept::symbol("main::y", int()),
+        );
+        let mut predicate = Irept::from("=");
+        predicate.subt = vec![x.clone(), y.clone()];
+        predicate
+            .named_subt
+            .insert(String::from("type"), Irept::from("bool"));
+        let mut variables = Irept::from("tuple");
+        variables.subt = vec![x.clone(), y.clone()];
+
+        for quantifier in ["forall", "exists"] {
+            // forall (x, y). x = y is forall x. forall y. x = y
+            let mut irep = Irept::from(quantifier);
+            irep.subt = vec![variables.clone(), predicate.clone()];
+            irep.named_subt
+                .insert(String::from("type"), Irept::from("bool"));
+            esbmcfixes::fix_expression(&mut irep, &RuleSet::default()).unwrap();
+            let operands = &irep.named_subt["operands"].subt;
+            assert_eq!(operands[0].named_subt["identifier"].id, "main::x");
+            assert_eq!(operands[1].id, quantifier);
+            let inner = &operands[1].named_subt["operands"].subt;
+            assert_eq!(inner[0].named_subt["identifier"].id, "main::y");
+            assert_eq!(inner[1].id, "=");
+
+            // Going back, every quantifier binds a tuple
+            cbmcfixes::fix_expression(
+                &mut irep,
+                &HashMap::new(),
+                &HashMap::new(),
+                64,
+                &RuleSet::default().inverted(),
+            );
+            assert_eq!(irep.subt[0].id, "tuple");
+            assert_eq!(irep.subt[0].subt[0], x);
+            assert_eq!(irep.subt[1].subt[0].id, "tuple");
+            assert_eq!(irep.subt[1].subt[1], predicate);
+        }
+    }
+
+    #[test]
+    fn quantifiers_unsupported() {
+        let mut variables = Irept::from("tuple");
+        variables.subt = vec![Irept::from("member")];
+        let mut irep = Irept::from("forall");
+        irep.subt = vec![variables, Irept::from("true")];
+        let assertion = CBMCInstruction {
+            code: Irept::from("nil"),
+            source_location: Irept::from("nil"),
+            instr_type: CBMCInstructionKind::Assert as u32,
+            guard: irep,
+            target_number: NO_TARGET_NUMBER,
+            targets: vec![],
+            labels: vec![],
+            function: Irept::default(),
+        };
+        let function = CBMCFunction {
+            name: String::from("main"),
+            instructions: vec![assertion],
+        };
+        let result = function.to_esbmc_irep("main", &RuleSet::default());
+        assert!(result.is_err());
+        assert_eq!(
+            result.err().unwrap(),
+            "Function main: forall binding a member is not supported by ESBMC, only symbols can be bound"
+        );
+    }
+
+    #[test]
+    fn float_operators() {
+        let mut rm_type = Irept::from("signedbv");
+        rm_type
+            .named_subt
+            .insert(String::from("width"), Irept::from("32"));
+        let rounding_mode = Irept::symbol("__CPROVER_rounding_mode", rm_type);
+
+        for (cbmc, esbmc) in [
+            ("floatbv_plus", "ieee_add"),
+            ("floatbv_minus", "ieee_sub"),
+            ("floatbv_mult", "ieee_mul"),
+            ("floatbv_div", "ieee_div"),
+        ] {
+            let mut irep = Irept::from(cbmc);
+            irep.subt = vec![
+                Irept::symbol("a", float_type("64", "52")),
+                Irept::symbol("b", float_type("64", "52")),
+                rounding_mode.clone(),
+            ];
+            irep.named_subt
+                .insert(String::from("type"), float_type("64", "52"));
+            let original = irep.clone();
+
+            esbmcfixes::fix_expression(&mut irep, &RuleSet::default()).unwrap();
+            assert_eq!(irep.id, esbmc);
+            assert!(irep.subt.is_empty());
+            assert_eq!(irep.named_subt["operands"].subt.len(), 2);
+            assert_eq!(
+                irep.named_subt["rounding_mode"].named_subt["identifier"].id,
+                "c:@__ESBMC_rounding_mode"
+            );
+
+            cbmcfixes::fix_expression(
+                &mut irep,
+                &HashMap::new(),
+                &HashMap::new(),
+                64,
+                &RuleSet::default().inverted(),
+            );
+            assert_eq!(irep, original);
+        }
+
+        // ESBMC might leave the rounding mode implicit
+        let mut irep = Irept::from("ieee_add");
+        let mut operands = Irept::default();
+        operands.subt = vec![
+            Irept::symbol("a", float_type("32", "23")),
+            Irept::symbol("b", float_type("32", "23")),
+        ];
+        irep.named_subt.insert(String::from("operands"), operands);
+        cbmcfixes::fix_expression(
+            &mut irep,
+            &HashMap::new(),
+            &HashMap::new(),
+            64,
+            &RuleSet::default().inverted(),
+        );
+        assert_eq!(irep.id, "floatbv_plus");
+        assert_eq!(irep.subt[2], rounding_mode);
+
+        // Integer casts never carry a rounding mode in CBMC
+        let mut irep = Irept::from("typecast");
+        irep.named_subt
+            .insert(String::from("type"), Irept::from("signedbv"));
+        let mut operands = Irept::default();
+        operands.subt = vec![Irept::symbol("x", Irept::from("unsignedbv"))];
+        irep.named_subt.insert(String::from("operands"), operands);
+        irep.named_subt
+            .insert(String::from("rounding_mode"), rounding_mode.clone());
+        cbmcfixes::fix_expression(
+            &mut irep,
+            &HashMap::new(),
+            &HashMap::new(),
+            64,
+            &RuleSet::default().inverted(),
+        );
+        assert_eq!(irep.id, "typecast");
+        assert!(!irep.named_subt.contains_key("rounding_mode"));
+    }
+
+    #[test]
+    fn float_predicates() {
+        let mut irep = Irept::from("sign");
+        irep.subt = vec![Irept::symbol("a", float_type("32", "23"))];
+        esbmcfixes::fix_expression(&mut irep, &RuleSet::default()).unwrap();
+        assert_eq!(irep.id, "signbit");
+        assert_eq!(irep.named_subt["operands"].subt.len(), 1);
+
+        let mut irep = Irept::from("floatbv_round_to_integral");
+        irep.subt = vec![
+            Irept::symbol("a", float_type("64", "52")),
+            Irept::symbol("__CPROVER_rounding_mode", Irept::from("signedbv")),
+        ];
+        esbmcfixes::fix_expression(&mut irep, &RuleSet::default()).unwrap();
+        assert_eq!(irep.id, "nearbyint");
+        assert_eq!(irep.named_subt["operands"].subt.len(), 1);
+
+        assert_eq!(
+            esbmcfixes::fix_name("__CPROVER_rounding_mode", "main"),
+            "c:@__ESBMC_rounding_mode"
+        );
+        assert_eq!(
+            esbmcfixes::fix_base_name("__CPROVER_rounding_mode", "main"),
+            "__ESBMC_rounding_mode"
+        );
+        assert_eq!(
+            cbmcfixes::fix_name("c:@__ESBMC_rounding_mode"),
+            "__CPROVER_rounding_mode"
+        );
+        assert_eq!(
+            cbmcfixes::fix_name("__ESBMC_rounding_mode"),
+            "__CPROVER_rounding_mode"
+        );
+    }
+
+    #[test]
+    fn union_and_enum_tags() {
+        let tag = |id: &str, identifier: &str| {
+            let mut irep = Irept::from(id);
+            irep.named_subt
+                .insert(String::from("identifier"), Irept::from(identifier));
+            irep
+        };
+        let mut int_type = Irept::from("signedbv");
+        int_type
+            .named_subt
+            .insert(String::from("width"), Irept::from("32"));
+
+        let mut components = Irept::default();
+        let mut component = Irept::default();
+        component
+            .named_subt
+            .insert(String::from("name"), Irept::from("i"));
+        component
+            .named_subt
+            .insert(String::from("type"), int_type.clone());
+        components.subt.push(component);
+        let mut union_type = Irept::from("union");
+        union_type
+            .named_subt
+            .insert(String::from("components"), components);
+
+        let mut enum_type = Irept::from("c_enum");
+        enum_type
+            .named_subt
+            .insert(String::from("width"), Irept::from("32"));
+        enum_type.subt.push(int_type.clone());
+
+        let mut cache = HashMap::new();
+        union_type.fix_type(&cache).unwrap();
+        cache.insert(Irept::from("tag-value"), union_type);
+        enum_type.fix_type(&cache).unwrap();
+        assert_eq!(enum_type, int_type);
+        cache.insert(Irept::from("tag-color"), enum_type);
+
+        // v.i
+        let mut member = Irept::from("member");
+        member
+            .subt
+            .push(Irept::symbol("v", tag("union_tag", "tag-value")));
+        member
+            .named_subt
+            .insert(String::from("component_name"), Irept::from("i"));
+        member
+            .named_subt
+            .insert(String::from("type"), int_type.clone());
+        esbmcfixes::fix_expression(&mut member, &RuleSet::default()).unwrap();
+        member.fix_type(&cache).unwrap();
+        let union_symbol = &member.named_subt["operands"].subt[0];
+        assert_eq!(union_symbol.named_subt["type"].id, "union");
+        assert_eq!(
+            union_symbol.named_subt["type"].named_subt["components"].subt[0].id,
+            "component"
+        );
+
+        // BLUE
+        let mut constant = Irept::from("constant");
+        constant
+            .named_subt
+            .insert(String::from("value"), Irept::from("6"));
+        constant
+            .named_subt
+            .insert(String::from("type"), tag("c_enum_tag", "tag-color"));
+        esbmcfixes::fix_expression(&mut constant, &RuleSet::default()).unwrap();
+        constant.fix_type(&cache).unwrap();
+        assert_eq!(constant.named_subt["type"], int_type);
+        assert_eq!(
+            constant.named_subt["value"].id,
+            format!("{}110", "0".repeat(29))
+        );
+    }
+
+    #[test]
+    fn recursive_tags() {
+        // struct node { int value; struct node *next; }
+        let mut node_tag = Irept::from("struct_tag");
+        node_tag
+            .named_subt
+            .insert(String::from("identifier"), Irept::from("tag-node"));
+        let mut next_type = Irept::from("pointer");
+        next_type.subt.push(node_tag.clone());
+
+        let mut components = Irept::default();
+        for (name, ctype) in [("value", Irept::from("signedbv")), ("next", next_type)] {
+            let mut component = Irept::default();
+            component
+                .named_subt
+                .insert(String::from("name"), Irept::from(name));
+            component.named_subt.insert(String::from("type"), ctype);
+            components.subt.push(component);
+        }
+        let mut node_type = Irept::from("struct");
+        node_type
+            .named_subt
+            .insert(String::from("components"), components);
+
+        let mut cache = HashMap::new();
+        node_type.fix_type(&cache).unwrap();
+        cache.insert(Irept::from("tag-node"), node_type);
+
+        let mut head = Irept::symbol("head", node_tag);
+        head.fix_type(&cache).unwrap();
+        let next = &head.named_subt["type"].named_subt["components"].subt[1];
+        let subtype = &next.named_subt["type"].named_subt["subtype"];
+        assert!(irep_contains(subtype, "symbol"));
+        assert!(!irep_contains(subtype, "struct"));
+        assert!(!irep_contains(&head, "struct_tag"));
+    }
+
+    #[test]
+    fn recursive_tags_kani() {
+        // Kani types refer to themselves through pointers and unions
+        let result = crate::cbmc::process_cbmc_file(&get_test_path("first_steps.rs.goto")).unwrap();
+        let converted = ESBMCParseResult::try_from(result).unwrap();
+        for (_, function) in &converted.functions_irep {
+            for tag in TAG_TYPES {
+                assert!(!irep_contains(function, tag));
+            }
+        }
+    }
+
+    #[test]
+    fn anon_structs() {
+        let expand = |name: &str| {
+            let mut irep = Irept::from("struct_tag");
+            irep.named_subt.insert(
+                String::from("identifier"),
+                Irept::from(format!("tag-#anon#{}", name)),
+            );
+            irep.expand_anon_struct();
+            irep
+        };
+        let component_type = |irep: &Irept, index: usize| {
+            irep.named_subt["components"].subt[index].named_subt["type"].clone()
+        };
+
+        let irep = expand("ST[U64'max_elems'|*{SYM#0={ST[U8'is_writable'|*{V}'lb']}}'elems']");
+        assert_eq!(irep.id, "struct");
+        assert_eq!(irep.named_subt["components"].subt.len(), 2);
+        let elems = component_type(&irep, 1);
+        assert_eq!(elems.id, "pointer");
+        assert_eq!(elems.named_subt["subtype"].id, "struct");
+
+        let irep =
+            expand("ST[B'b'|CB8'c'|F32'f'|F64'd'|ARR4{S32}'a'|UN[S32'i'|U8'c']'u'|*{*{V}}'p']");
+        let ids: Vec<String> = (0..7).map(|i| component_type(&irep, i).id).collect();
+        assert_eq!(
+            ids,
+            ["bool", "signedbv", "floatbv", "floatbv", "array", "union", "pointer"]
+        );
+        assert_eq!(component_type(&irep, 3).named_subt["f"].id, "52");
+        let array = component_type(&irep, 4);
+        assert_eq!(array.named_subt["subtype"].id, "signedbv");
+        assert_eq!(
+            array.named_subt["size"].named_subt["value"].id,
+            format!("{:064b}", 4)
+        );
+        let pointer = component_type(&irep, 6);
+        assert_eq!(pointer.named_subt["subtype"].id, "pointer");
+
+        // Symbols are shared
+        let irep = expand("ST[SYM#0={ST[S32'x']}'a'|SYM#0'b'|cvS16'c']");
+        assert_eq!(component_type(&irep, 0), component_type(&irep, 1));
+        assert_eq!(component_type(&irep, 2).id, "signedbv");
+
+        // Errors are not fatal, the tag is kept
+        for name in ["ST[S32'x'", "ST[SYM#1'x']", "ST[Q'x']", "ST[F12'x']", "S32"] {
+            assert_eq!(expand(name).id, "struct_tag");
+        }
+    }
+
+    #[test]
+    fn anon_structs_contract() {
+        // Contracts instrumentation uses lots of anonymous structs
+        let result = crate::cbmc::process_cbmc_file(&get_test_path("mul_contract.goto")).unwrap();
+        let converted = ESBMCParseResult::try_from(result).unwrap();
+        for (_, function) in &converted.functions_irep {
+            for tag in TAG_TYPES {
+                assert!(!irep_contains(function, tag));
+            }
+        }
+    }
+
+    #[test]
+    fn esbmc_to_cbmc_targets() {
+        let mut function = Irept::from("goto-program");
+        for targets in [vec![], vec!["3"], vec!["0"], vec![]] {
+            let mut instr = Irept::default();
+            instr
+                .named_subt
+                .insert("typeid".to_string(), Irept::from("1"));
+            let mut t_ireps = Irept::default();
+            t_ireps.subt = targets.into_iter().map(Irept::from).collect();
+            instr.named_subt.insert("targets".to_string(), t_ireps);
+            function.subt.push(instr);
+        }
+
+        let convert = |function: Irept| {
+            esbmc_function_to_cbmc(
+                "foo",
+                function,
+                &HashMap::new(),
+                &HashMap::new(),
+                64,
+                &RuleSet::default().inverted(),
+            )
+        };
+        let converted = convert(function.clone()).unwrap();
+        let numbers: Vec<u32> = converted
+            .instructions
+            .iter()
+            .map(|i| i.target_number)
+            .collect();
+        assert_eq!(numbers, [1, NO_TARGET_NUMBER, NO_TARGET_NUMBER, 2]);
+        assert_eq!(converted.instructions[1].targets[0].id, "2");
+        assert_eq!(converted.instructions[2].targets[0].id, "1");
+
+        // Malformed programs are reported
+        for (key, value, error) in [
+            ("targets", "4", "Invalid target 4"),
+            ("targets", "x", "Invalid target x"),
+            ("typeid", "6", "Unknown instruction kind 6"),
+            ("typeid", "x", "Unknown instruction kind x"),
+        ] {
+            let mut malformed = function.clone();
+            let mut irep = Irept::from(value);
+            if key == "targets" {
+                irep = Irept {
+                    subt: vec![irep],
+                    ..Default::default()
+                };
+            }
+            malformed.subt[0].named_subt.insert(key.to_string(), irep);
+            assert_eq!(convert(malformed).err(), Some(String::from(error)));
+        }
+    }
+
+    #[test]
+    fn instruction_kinds() {
+        for value in 0..20 {
+            let kind = CBMCInstructionKind::from_u32(value).unwrap();
+            if [
+                CBMCInstructionKind::StartThread,
+                CBMCInstructionKind::EndThread,
+            ]
+            .contains(&kind)
+            {
+                continue;
+            }
+            let esbmc = esbmc_instruction_kind(kind).unwrap();
+            let expected = match kind {
+                CBMCInstructionKind::IncompleteGoto => CBMCInstructionKind::Goto,
+                _ => kind,
+            };
+            assert_eq!(cbmc_instruction_kind(esbmc), Some(expected));
+        }
+        assert_eq!(
+            esbmc_instruction_kind(CBMCInstructionKind::SetReturnValue).unwrap() as u32,
+            12
+        );
+        assert_eq!(
+            esbmc_instruction_kind(CBMCInstructionKind::StartThread).err(),
+            Some(String::from(
+                "START_THREAD instructions are not supported by ESBMC"
+            ))
+        );
+        assert_eq!(CBMCInstructionKind::from_u32(20), None);
+        assert_eq!(cbmc_instruction_kind(ESBMCInstructionKind::ThrowDecl), None);
+        assert_eq!(ESBMCInstructionKind::from_u32(6), None);
+
+        // ESBMC exception specifications become a SKIP
+        let mut function = Irept::from("goto-program");
+        for typeid in ["19", "20", "9"] {
+            let mut instr = Irept::default();
+            instr
+                .named_subt
+                .insert("typeid".to_string(), Irept::from(typeid));
+            instr
+                .named_subt
+                .insert("code".to_string(), Irept::from("code"));
+            function.subt.push(instr);
+        }
+        let converted = esbmc_function_to_cbmc(
+            "foo",
+            function,
+            &HashMap::new(),
+            &HashMap::new(),
+            64,
+            &RuleSet::default().inverted(),
+        )
+        .unwrap();
+        let kinds: Vec<u32> = converted
+            .instructions
+            .iter()
+            .map(|i| i.instr_type)
+            .collect();
+        assert_eq!(kinds, [5, 5, 9]);
+        assert_eq!(converted.instructions[0].code.id, "nil");
+    }
+
+    #[test]
+    fn output_statements() {
+        let result = crate::cbmc::process_cbmc_file(&get_test_path("hello_world.rs.goto")).unwrap();
+        let start = result
+            .functions_irep
+            .iter()
+            .find(|f| f.name == "__CPROVER__start")
+            .unwrap()
+            .instructions
+            .len();
+
+        // The output of the returned value is kept as a SKIP
+        let converted = ESBMCParseResult::try_from(result).unwrap();
+        let (_, function) = converted
+            .functions_irep
+            .iter()
+            .find(|(name, _)| name == "__ESBMC_main")
+            .unwrap();
+        // after the initialization of the heap (see heap.rs)
+        assert_eq!(function.subt.len(), start + 3);
+        let typeids: Vec<&str> = function
+            .subt
+            .iter()
+            .skip(3)
+            .map(|i| i.named_subt["typeid"].id.as_str())
+            .collect();
+        assert_eq!(typeids, ["5", "16", "16", "5", "9"]);
+    }
+
+    const CHECKED_UNCHECKED_ADD_I8: &str =
+        "_RNvNtNtCsesPP5EAma4_4core3num6verify24checked_unchecked_add_i8";
+
+    #[test]
+    fn overflow_result() {
+        let input = std::path::Path::new(&crate::resources::get_resource_dir())
+            .join("resources/verify-std/checked_unchecked_add_i8.goto");
+        let mut result = crate::cbmc::process_cbmc_file(input.to_str().unwrap()).unwrap();
+        result.entrypoint = result
+            .find_entrypoint(Some(CHECKED_UNCHECKED_ADD_I8))
+            .unwrap();
+        let converted = ESBMCParseResult::try_from(result).unwrap();
+
+        let mut predicates = 0;
+        for (_, function) in &converted.functions_irep {
+            assert!(!irep_contains(function, "overflow_result-+"));
+            predicates += usize::from(irep_contains(function, "overflow-+"));
+        }
+        assert!(predicates > 0);
+
+        // overflow_result-+(a, b) is {a + b, overflow-+(a, b)}, the type of
+        // a + b is the one of the first component
+        let a = Irept::from("a");
+        let b = a.clone();
+        let component = |name: &str, ctype: &str| {
+            let mut result = Irept::from("component");
+            result
+                .named_subt
+                .insert(String::from("name"), Irept::from(name));
+            result
+                .named_subt
+                .insert(String::from("type"), Irept::from(ctype));
+            result
+        };
+        let mut stype = Irept::from("struct");
+        stype.named_subt.insert(
+            String::from("components"),
+            Irept {
+                subt: vec![
+                    component("result", "signedbv"),
+                    component("overflow", "bool"),
+                ],
+                ..Default::default()
+            },
+        );
+        let mut irep = Irept::from("overflow_result-+");
+        irep.subt = vec![a.clone(), b.clone()];
+        irep.named_subt.insert(String::from("type"), stype);
+        let mut shr = irep.clone();
+        esbmcfixes::lower_overflow_result(&mut irep).unwrap();
+        assert_eq!(irep.id, "struct");
+        assert_eq!(irep.subt[0].id, "+");
+        assert_eq!(irep.subt[0].named_subt["type"].id, "signedbv");
+        assert_eq!(irep.subt[1].id, "overflow-+");
+        assert_eq!(irep.subt[1].named_subt["type"].id, "bool");
+        assert_eq!(irep.subt[1].subt, vec![a, b]);
+
+        shr.id = String::from("overflow_result-shr");
+        assert_eq!(
+            esbmcfixes::lower_overflow_result(&mut shr).err().unwrap(),
+            "overflow_result-shr with 2 operands is not supported by ESBMC"
+        );
+    }
+
+    #[test]
+    fn esbmc_to_cbmc_hello() {
+        let result = crate::esbmc::process_esbmc_file(&get_test_path("hello.goto")).unwrap();
+        let converted = CBMCParseResult::try_from(result).unwrap();
+
+        assert!(converted
+            .functions_irep
+            .iter()
+            .any(|f| f.name == "__CPROVER__start"));
+        assert!(converted
+            .symbols_irep
+            .iter()
+            .any(|s| s.name == "__CPROVER__start"));
+
+        for function in &converted.functions_irep {
+            for instr in &function.instructions {
+                assert!(!instr.code.named_subt.contains_key("operands"));
+                assert!(!irep_contains(&instr.code, "sideeffect"));
+            }
+        }
+    }
+
+    #[test]
+    fn esbmc_to_cbmc_pointer_width() {
+        let width = |result: ESBMCParseResult| {
+            let converted = CBMCParseResult::try_from(result).unwrap();
+            let atexits = converted
+                .symbols_irep
+                .iter()
+                .find(|s| s.name == "c:stdlib.c@__ESBMC_atexits")
+                .unwrap();
+            assert_eq!(atexits.stype.id, "pointer");
+            atexits.stype.named_subt["width"].id.clone()
+        };
+        let result = crate::esbmc::process_esbmc_file(&get_test_path("hello.goto")).unwrap();
+        assert_eq!(width(result.clone()), "64");
+
+        // The architecture of programs that come from CBMC is kept
+        let mut stype = Irept::from("signedbv");
+        stype
+            .named_subt
+            .insert(String::from("width"), Irept::from("32"));
+        let mut value = Irept::from("constant");
+        value.named_subt.insert(String::from("type"), stype.clone());
+        value
+            .named_subt
+            .insert(String::from("value"), Irept::from(format!("{:032b}", 32)));
+        let mut symbol = Irept::default();
+        for (key, irep) in [
+            ("name", Irept::from("__CPROVER_architecture_pointer_width")),
+            ("type", stype),
+            ("symvalue", value),
+        ] {
+            symbol.named_subt.insert(String::from(key), irep);
+        }
+        let mut result = result;
+        result.symbols_irep.push(symbol);
+        assert_eq!(width(result), "32");
+    }
+
+    // Identifiers of the struct_tag and union_tag types within irep
+    fn tags(irep: &Irept, result: &mut Vec<String>) {
+        if ["struct_tag", "union_tag"].contains(&irep.id.as_str()) {
+            result.push(irep.named_subt["identifier"].id.clone());
+        }
+        for sub in irep.subt.iter().chain(irep.named_subt.values()) {
+            tags(sub, result);
+        }
+    }
+
+    #[test]
+    fn esbmc_to_cbmc_round_trip() {
+        let first_steps = "/tmp/round_trip_first_steps.esbmc.goto";
+        cbmc2esbmc(
+            None,
+            &get_test_path("first_steps.rs.goto"),
+            first_steps,
+            &RuleSet::default(),
+        )
+        .unwrap();
+
+        for (index, input) in [get_test_path("hello.goto"), first_steps.to_string()]
+            .iter()
+            .enumerate()
+        {
+            let cbmc = format!("/tmp/round_trip_{}.cbmc.goto", index);
+            let esbmc = format!("/tmp/round_trip_{}.esbmc.goto", index);
+            crate::esbmc2cbmc(input, &cbmc, CBMCLayout::default(), &RuleSet::default()).unwrap();
+
+            // Only type symbols are referred to by tags, called functions
+            // get their type from the symbol table
+            let converted = crate::cbmc::process_cbmc_file(&cbmc).unwrap();
+            let mut referred = Vec::new();
+            for function in &converted.functions_irep {
+                for instr in &function.instructions {
+                    tags(&instr.code, &mut referred);
+                    if instr.instr_type == CBMCInstructionKind::FunctionCall as u32 {
+                        assert!(instr.code.subt[1].named_subt.contains_key("type"));
+                    }
+                }
+            }
+            for identifier in referred {
+                assert!(converted
+                    .symbols_irep
+                    .iter()
+                    .any(|s| s.is_type && s.name == identifier));
+            }
+
+            cbmc2esbmc(None, &cbmc, &esbmc, &RuleSet::default()).unwrap();
+            let original = crate::esbmc::process_esbmc_file(input).unwrap();
+            let result = crate::esbmc::process_esbmc_file(&esbmc).unwrap();
+            std::fs::remove_file(&cbmc).ok();
+            std::fs::remove_file(&esbmc).ok();
+            let names = |program: &ESBMCParseResult| {
+                let mut names: Vec<String> = program
+                    .functions_irep
+                    .iter()
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                names.sort();
+                names
+            };
+            assert_eq!(names(&original), names(&result));
+        }
+        std::fs::remove_file(first_steps).ok();
+    }
+
     #[test]
     #[ignore]
     fn hello_world() {
@@ -839,6 +2711,58 @@ mod tests {
             0,
         );
     }
+    #[test]
+    #[ignore]
+    fn hello_float() {
+        run_test("hello_float.c", &["--goto-functions-only"], 0);
+        run_test("hello_float.c", &["--incremental-bmc"], 0);
+        run_test("hello_float_fail.c", &["--incremental-bmc"], 1);
+    }
+
+    #[test]
+    #[ignore]
+    fn hello_union() {
+        run_test("hello_union.c", &["--goto-functions-only"], 0);
+        run_test("hello_union.c", &["--incremental-bmc"], 0);
+        run_test("hello_union_fail.c", &["--incremental-bmc"], 1);
+    }
+
+    #[test]
+    #[ignore]
+    fn hello_malloc() {
+        run_test("hello_malloc.c", &["--goto-functions-only"], 0);
+        run_test("hello_malloc.c", &["--memory-leak-check"], 0);
+        run_test("hello_malloc_fail.c", &["--incremental-bmc"], 1);
+        run_test("hello_free_fail.c", &["--incremental-bmc"], 1);
+        run_test("hello_leak_fail.c", &["--memory-leak-check"], 1);
+    }
+
+    #[test]
+    #[ignore]
+    fn hello_thread() {
+        run_test("hello_thread.c", &["--goto-functions-only"], 0);
+        run_test("hello_thread.c", &["--context-bound", "2"], 0);
+        run_test("hello_thread_fail.c", &["--context-bound", "2"], 1);
+        run_test("hello_atomic.c", &["--context-bound", "2"], 0);
+        run_test("hello_atomic_fail.c", &["--context-bound", "2"], 1);
+    }
+
+    #[test]
+    #[ignore]
+    fn hello_list() {
+        run_test("hello_list.c", &["--goto-functions-only"], 0);
+        run_test("hello_list.c", &["--incremental-bmc"], 0);
+        run_test("hello_list_fail.c", &["--incremental-bmc"], 1);
+    }
+
+    #[test]
+    #[ignore]
+    fn hello_anon_struct() {
+        run_test("hello_anon_struct.c", &["--goto-functions-only"], 0);
+        run_test("hello_anon_struct.c", &["--incremental-bmc"], 1);
+        run_test("hello_anon_union.c", &["--incremental-bmc"], 0);
+    }
+
     #[test]
     #[ignore]
     fn hello_eq() {
@@ -943,11 +2867,19 @@ mod tests {
     #[test]
     #[ignore]
     fn unchecked_add_contract() {
-        // Disabled because ESBMC does not support: object_size, overflow_result-+
-        // run_goto_test(
-        // "checked_unchecked_add_i8.goto",
-        // &["--goto-functions-only"],
-        // 0,
-        // );
+        let cargo_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let input = std::path::Path::new(&cargo_dir)
+            .join("resources/verify-std/checked_unchecked_add_i8.goto");
+        let esbmc_gbf = "checked_unchecked_add_i8.goto.esbmc.goto";
+        cbmc2esbmc(
+            Some(CHECKED_UNCHECKED_ADD_I8),
+            input.to_str().unwrap(),
+            esbmc_gbf,
+            &RuleSet::default(),
+        )
+        .unwrap();
+        run_esbmc_gbf(esbmc_gbf, &["--goto-functions-only"], 0);
+        run_esbmc_gbf(esbmc_gbf, &["--incremental-bmc"], 0);
+        std::fs::remove_file(esbmc_gbf).ok();
     }
 }
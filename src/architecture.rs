@@ -0,0 +1,226 @@
+// This module reads the target architecture of a CBMC goto program (the
+// __CPROVER_architecture_* symbols) and turns it into the ESBMC flags that
+// verify the program under the same assumptions.
+
+use crate::cbmc::CBMCSymbol;
+use crate::irep::Irept;
+use log::info;
+
+const PREFIX: &str = "__CPROVER_architecture_";
+
+/// Width of the pointers of programs without an architecture
+pub const DEFAULT_POINTER_WIDTH: u64 = 64;
+
+// Widths that ESBMC fixes for a word size. The rest (e.g. long double) is
+// not checked as it depends on the platform for both tools.
+struct DataModel {
+    flag: &'static str,
+    widths: [(&'static str, u64); 9],
+}
+
+const DATA_MODELS: [DataModel; 2] = [
+    DataModel {
+        flag: "--32",
+        widths: [
+            ("bool_width", 8),
+            ("char_width", 8),
+            ("short_int_width", 16),
+            ("int_width", 32),
+            ("long_int_width", 32),
+            ("long_long_int_width", 64),
+            ("pointer_width", 32),
+            ("single_width", 32),
+            ("double_width", 64),
+        ],
+    },
+    DataModel {
+        flag: "--64",
+        widths: [
+            ("bool_width", 8),
+            ("char_width", 8),
+            ("short_int_width", 16),
+            ("int_width", 32),
+            ("long_int_width", 64),
+            ("long_long_int_width", 64),
+            ("pointer_width", 64),
+            ("single_width", 32),
+            ("double_width", 64),
+        ],
+    },
+];
+
+// CBMC endianness: 0 is none, 1 is little-endian and 2 is big-endian
+const ENDIANNESS_FLAGS: [(u64, &str); 2] = [(1, "--little-endian"), (2, "--big-endian")];
+
+// The values are integers, possibly behind a typecast. Constants of
+// integer type are in decimal while bit-vectors are in hexadecimal.
+fn value_of(irep: &Irept) -> Option<u64> {
+    match irep.id.as_str() {
+        "typecast" => irep.subt.first().and_then(value_of),
+        "constant" => {
+            let value = &irep.named_subt.get("value")?.id;
+            match irep.named_subt.get("type").map(|t| t.id.as_str()) {
+                Some("integer") | Some("natural") => value.parse().ok(),
+                _ => u64::from_str_radix(value, 16).ok(),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn setting(symbols: &[CBMCSymbol], name: &str) -> Result<Option<u64>, String> {
+    let symbol = match symbols
+        .iter()
+        .find(|s| s.name == format!("{}{}", PREFIX, name))
+    {
+        Some(symbol) => symbol,
+        None => return Ok(None),
+    };
+    match value_of(&symbol.value) {
+        Some(value) => Ok(Some(value)),
+        None => Err(format!("Could not read the value of {}", symbol.name)),
+    }
+}
+
+/// Width of the pointers of `symbols`, 64 bits if unknown
+pub fn pointer_width(symbols: &[CBMCSymbol]) -> u64 {
+    setting(symbols, "pointer_width")
+        .ok()
+        .flatten()
+        .unwrap_or(DEFAULT_POINTER_WIDTH)
+}
+
+/// Whether `name` is one of the symbols describing the architecture
+pub fn is_setting(name: &str) -> bool {
+    name.starts_with(PREFIX)
+}
+
+/// ESBMC flags for the architecture of `symbols`, fails if ESBMC cannot
+/// verify the program under the same assumptions. Programs without an
+/// architecture (e.g. not linked by goto-cc) do not need any flag.
+pub fn esbmc_flags(symbols: &[CBMCSymbol]) -> Result<Vec<String>, String> {
+    let setting = |name: &str| setting(symbols, name);
+
+    let pointer_width = match setting("pointer_width")? {
+        Some(width) => width,
+        None => return Ok(vec![]),
+    };
+    let model = DATA_MODELS
+        .iter()
+        .find(|m| m.widths.contains(&("pointer_width", pointer_width)))
+        .ok_or(format!(
+            "ESBMC does not support {} bit pointers",
+            pointer_width
+        ))?;
+
+    let mut flags = vec![String::from(model.flag)];
+    for (name, width) in model.widths {
+        if let Some(value) = setting(name)? {
+            if value != width {
+                return Err(format!(
+                    "{}{} is {} but ESBMC uses {} with {}",
+                    PREFIX, name, value, width, model.flag
+                ));
+            }
+        }
+    }
+
+    if let Some(endianness) = setting("endianness")? {
+        if let Some((_, flag)) = ENDIANNESS_FLAGS.iter().find(|(e, _)| *e == endianness) {
+            flags.push(String::from(*flag));
+        }
+    }
+
+    if setting("char_is_unsigned")? == Some(1) {
+        flags.push(String::from("--unsigned-char"));
+    }
+
+    if setting("NULL_is_zero")? == Some(0) {
+        return Err(format!("{}NULL_is_zero is 0 but ESBMC assumes it", PREFIX));
+    }
+
+    Ok(flags)
+}
+
+/// Checks the architecture of `symbols` and prints how to verify `output`
+pub fn print_esbmc_flags(symbols: &[CBMCSymbol], output: &str) -> Result<(), String> {
+    let flags = esbmc_flags(symbols)?;
+    info!("Verify with: esbmc --binary {} {}", output, flags.join(" "));
+    Ok(())
+}
+
+///////////
+// TESTS //
+///////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::get_test_path;
+
+    fn set(symbols: &mut [CBMCSymbol], name: &str, value: u64) {
+        let symbol = symbols
+            .iter_mut()
+            .find(|s| s.name == format!("{}{}", PREFIX, name))
+            .unwrap();
+        let mut constant = Irept::from("constant");
+        constant
+            .named_subt
+            .insert(String::from("type"), Irept::from("integer"));
+        constant
+            .named_subt
+            .insert(String::from("value"), Irept::from(value.to_string()));
+        symbol.value = constant;
+    }
+
+    #[test]
+    fn x86_64() {
+        for file in ["hello-gb.goto", "hello_world.rs.goto"] {
+            let result = crate::cbmc::process_cbmc_file(&get_test_path(file)).unwrap();
+            assert_eq!(
+                esbmc_flags(&result.symbols_irep).unwrap(),
+                vec!["--64", "--little-endian"]
+            );
+        }
+    }
+
+    #[test]
+    fn other_architectures() {
+        let result = crate::cbmc::process_cbmc_file(&get_test_path("hello-gb.goto")).unwrap();
+        let mut symbols = result.symbols_irep;
+
+        // i386 (the long double does not matter)
+        set(&mut symbols, "pointer_width", 32);
+        set(&mut symbols, "long_int_width", 32);
+        set(&mut symbols, "long_double_width", 96);
+        assert_eq!(
+            esbmc_flags(&symbols).unwrap(),
+            vec!["--32", "--little-endian"]
+        );
+
+        // Big-endian with unsigned chars (e.g. PowerPC)
+        set(&mut symbols, "endianness", 2);
+        set(&mut symbols, "char_is_unsigned", 1);
+        assert_eq!(
+            esbmc_flags(&symbols).unwrap(),
+            vec!["--32", "--big-endian", "--unsigned-char"]
+        );
+
+        // 64 bit Windows uses 32 bit longs
+        set(&mut symbols, "pointer_width", 64);
+        assert_eq!(
+            esbmc_flags(&symbols).unwrap_err(),
+            "__CPROVER_architecture_long_int_width is 32 but ESBMC uses 64 with --64"
+        );
+
+        set(&mut symbols, "pointer_width", 16);
+        assert_eq!(
+            esbmc_flags(&symbols).unwrap_err(),
+            "ESBMC does not support 16 bit pointers"
+        );
+
+        // Without an architecture there is nothing to check
+        symbols.retain(|s| !s.name.starts_with(PREFIX));
+        assert!(esbmc_flags(&symbols).unwrap().is_empty());
+    }
+}
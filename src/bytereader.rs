@@ -5,12 +5,147 @@ use std::collections::HashMap;
 use std::fs;
 use std::str;
 
+// Every error carries the byte offset where it happened and the stack of
+// irep ids that were being decoded at that point (outermost first).
+#[derive(Clone, Debug, PartialEq)]
+pub enum GbfError {
+    Io {
+        path: String,
+        message: String,
+    },
+    UnexpectedEof {
+        offset: usize,
+        stack: Vec<String>,
+    },
+    BadTerminator {
+        found: u8,
+        offset: usize,
+        stack: Vec<String>,
+    },
+    VarintOverflow {
+        offset: usize,
+        stack: Vec<String>,
+    },
+    UnknownReference {
+        reference: u32,
+        offset: usize,
+        stack: Vec<String>,
+    },
+    BadHeader {
+        found: Vec<u8>,
+        offset: usize,
+        stack: Vec<String>,
+    },
+    BadVersion {
+        found: u32,
+        supported: Vec<u32>,
+        offset: usize,
+        stack: Vec<String>,
+    },
+}
+
+impl GbfError {
+    pub fn offset(&self) -> usize {
+        match self {
+            GbfError::Io { .. } => 0,
+            GbfError::UnexpectedEof { offset, .. }
+            | GbfError::BadTerminator { offset, .. }
+            | GbfError::VarintOverflow { offset, .. }
+            | GbfError::UnknownReference { offset, .. }
+            | GbfError::BadHeader { offset, .. }
+            | GbfError::BadVersion { offset, .. } => *offset,
+        }
+    }
+
+    pub fn stack(&self) -> &[String] {
+        match self {
+            GbfError::Io { .. } => &[],
+            GbfError::UnexpectedEof { stack, .. }
+            | GbfError::BadTerminator { stack, .. }
+            | GbfError::VarintOverflow { stack, .. }
+            | GbfError::UnknownReference { stack, .. }
+            | GbfError::BadHeader { stack, .. }
+            | GbfError::BadVersion { stack, .. } => stack,
+        }
+    }
+}
+
+impl std::fmt::Display for GbfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GbfError::Io { path, message } => {
+                return write!(f, "Could not read file {}: {}", path, message)
+            }
+            GbfError::UnexpectedEof { .. } => write!(f, "Unexpected end of file"),
+            GbfError::BadTerminator { found, .. } => {
+                write!(f, "Irep not terminated. Got {}", found)
+            }
+            GbfError::VarintOverflow { .. } => write!(f, "Input number is too large"),
+            GbfError::UnknownReference { reference, .. } => {
+                write!(f, "Unknown reference {}", reference)
+            }
+            GbfError::BadHeader { found, .. } => {
+                write!(f, "Invalid header. Found: {:?}", found)
+            }
+            GbfError::BadVersion {
+                found, supported, ..
+            } => write!(
+                f,
+                "Invalid version. Found {}, supported: {:?}",
+                found, supported
+            ),
+        }?;
+        write!(f, " at byte {}", self.offset())?;
+        if !self.stack().is_empty() {
+            write!(f, " while decoding {}", self.stack().join(" > "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GbfError {}
+
+// Formats that a goto program can be stored in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GotoFormat {
+    Cbmc,
+    Esbmc,
+    Json,
+}
+
+impl std::str::FromStr for GotoFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cbmc" => Ok(GotoFormat::Cbmc),
+            "esbmc" => Ok(GotoFormat::Esbmc),
+            "json" => Ok(GotoFormat::Json),
+            _ => Err(format!(
+                "unknown format {}, expected cbmc, esbmc or json",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for GotoFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GotoFormat::Cbmc => write!(f, "cbmc"),
+            GotoFormat::Esbmc => write!(f, "esbmc"),
+            GotoFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ByteReader {
     file: Vec<u8>,
     pointer: usize,
     irep_container: HashMap<u32, Irept>,
     string_ref_container: HashMap<u32, String>,
+    irep_stack: Vec<String>,
 }
 
 impl From<Vec<u8>> for ByteReader {
@@ -20,6 +155,7 @@ impl From<Vec<u8>> for ByteReader {
             pointer: 0,
             irep_container: HashMap::new(),
             string_ref_container: HashMap::new(),
+            irep_stack: Vec::new(),
         }
     }
 }
@@ -30,61 +166,111 @@ impl From<Vec<u8>> for ByteReader {
 impl ByteReader {
     // File manipulation
 
-    pub fn read_file(path: &str) -> Self {
+    pub fn read_file(path: &str) -> Result<Self, GbfError> {
         trace!("Reading goto file: {}", path);
-        let byte_content = fs::read(path).expect(format!("Could not read file {}", path).as_str());
-        ByteReader::from(byte_content)
+        let byte_content = fs::read(path).map_err(|e| GbfError::Io {
+            path: String::from(path),
+            message: e.to_string(),
+        })?;
+        Ok(ByteReader::from(byte_content))
     }
 
-    fn peek(&self) -> u8 {
-        self.file[self.pointer]
+    pub fn offset(&self) -> usize {
+        self.pointer
     }
 
-    fn get(&mut self) -> u8 {
-        let value = self.file[self.pointer];
+    // Error helpers, these capture the current position and irep stack
+
+    pub fn eof_error(&self) -> GbfError {
+        GbfError::UnexpectedEof {
+            offset: self.pointer,
+            stack: self.irep_stack.clone(),
+        }
+    }
+
+    pub fn unknown_reference_error(&self, reference: u32, context: &str) -> GbfError {
+        let mut stack = self.irep_stack.clone();
+        stack.push(String::from(context));
+        GbfError::UnknownReference {
+            reference,
+            offset: self.pointer,
+            stack,
+        }
+    }
+
+    pub fn version_error(&self, found: u32, supported: &[u32]) -> GbfError {
+        GbfError::BadVersion {
+            found,
+            supported: Vec::from(supported),
+            offset: self.pointer,
+            stack: self.irep_stack.clone(),
+        }
+    }
+
+    fn peek(&self) -> Result<u8, GbfError> {
+        self.file
+            .get(self.pointer)
+            .copied()
+            .ok_or_else(|| self.eof_error())
+    }
+
+    fn get(&mut self) -> Result<u8, GbfError> {
+        let value = self.peek()?;
         self.pointer += 1;
-        value
+        Ok(value)
+    }
+
+    fn check_terminator(&mut self) -> Result<(), GbfError> {
+        let offset = self.pointer;
+        let end_value = self.get()?;
+        if end_value != 0 {
+            return Err(GbfError::BadTerminator {
+                found: end_value,
+                offset,
+                stack: self.irep_stack.clone(),
+            });
+        }
+        Ok(())
     }
 
     // Reference parsing. First try the cache, if not available then parse the irep
-    pub fn read_esbmc_reference(&mut self) -> Irept {
-        let id = self.read_esbmc_word();
+    pub fn read_esbmc_reference(&mut self) -> Result<Irept, GbfError> {
+        let id = self.read_esbmc_word()?;
         if self.irep_container.contains_key(&id) {
-            return self.irep_container.get(&id).unwrap().clone();
+            return Ok(self.irep_container.get(&id).unwrap().clone());
         }
 
-        let irep_id = self.read_esbmc_string_ref();
+        let irep_id = self.read_esbmc_string_ref()?;
+        self.irep_stack.push(irep_id.clone());
 
         // Sub-expression
         let mut irep_sub: Vec<Irept> = Vec::new();
-        while self.peek() == b'S' {
+        while self.peek()? == b'S' {
             self.pointer += 1;
-            let sub = self.read_esbmc_reference();
+            let sub = self.read_esbmc_reference()?;
             irep_sub.push(sub);
         }
 
         // Named sub
         let mut named_sub: HashMap<String, Irept> = HashMap::new();
-        while self.peek() == b'N' {
+        while self.peek()? == b'N' {
             self.pointer += 1;
-            let named_id = self.read_esbmc_string_ref();
+            let named_id = self.read_esbmc_string_ref()?;
             // TODO: assert named_id[0] != '#'
-            named_sub.insert(named_id, self.read_esbmc_reference());
+            named_sub.insert(named_id, self.read_esbmc_reference()?);
         }
 
         // Comment?
         let mut comments_sub: HashMap<String, Irept> = HashMap::new();
-        while self.peek() == b'C' {
+        while self.peek()? == b'C' {
             self.pointer += 1;
-            let named_id = self.read_esbmc_string_ref();
+            let named_id = self.read_esbmc_string_ref()?;
             // TODO: assert named_id[0] == '#'
-            comments_sub.insert(named_id, self.read_esbmc_reference());
+            comments_sub.insert(named_id, self.read_esbmc_reference()?);
         }
 
-        let end_value = self.get();
-        if end_value != 0 {
-            panic!("Irep not terminated.");
-        }
+        self.check_terminator()?;
+        self.irep_stack.pop();
 
         let result = Irept {
             id: irep_id,
@@ -94,47 +280,46 @@ impl ByteReader {
         };
 
         self.irep_container.insert(id, result.clone());
-        result
+        Ok(result)
     }
 
-    pub fn read_cbmc_reference(&mut self) -> Irept {
-        let id = self.read_cbmc_word();
+    pub fn read_cbmc_reference(&mut self) -> Result<Irept, GbfError> {
+        let id = self.read_cbmc_word()?;
         if self.irep_container.contains_key(&id) {
-            return self.irep_container.get(&id).unwrap().clone();
+            return Ok(self.irep_container.get(&id).unwrap().clone());
         }
 
-        let irep_id = self.read_cbmc_string_ref();
+        let irep_id = self.read_cbmc_string_ref()?;
+        self.irep_stack.push(irep_id.clone());
 
         // Sub-expression
         let mut irep_sub: Vec<Irept> = Vec::new();
-        while self.peek() == b'S' {
+        while self.peek()? == b'S' {
             self.pointer += 1;
-            let sub = self.read_cbmc_reference();
+            let sub = self.read_cbmc_reference()?;
             irep_sub.push(sub);
         }
 
         // Named sub
         let mut named_sub: HashMap<String, Irept> = HashMap::new();
-        while self.peek() == b'N' {
+        while self.peek()? == b'N' {
             self.pointer += 1;
-            let named_id = self.read_cbmc_string_ref();
+            let named_id = self.read_cbmc_string_ref()?;
             // TODO: assert named_id[0] != '#'
-            named_sub.insert(named_id, self.read_cbmc_reference());
+            named_sub.insert(named_id, self.read_cbmc_reference()?);
         }
 
         // Comment?
         let mut comments_sub: HashMap<String, Irept> = HashMap::new();
-        while self.peek() == b'C' {
+        while self.peek()? == b'C' {
             self.pointer += 1;
-            let named_id = self.read_cbmc_string_ref();
+            let named_id = self.read_cbmc_string_ref()?;
             // TODO: assert named_id[0] == '#'
-            comments_sub.insert(named_id, self.read_cbmc_reference());
+            comments_sub.insert(named_id, self.read_cbmc_reference()?);
         }
 
-        let end_value = self.get();
-        if end_value != 0 {
-            panic!("Irep not terminated. Got {}", end_value);
-        }
+        self.check_terminator()?;
+        self.irep_stack.pop();
 
         let result = Irept {
             id: irep_id,
@@ -144,144 +329,290 @@ impl ByteReader {
         };
 
         self.irep_container.insert(id, result.clone());
-        result
+        Ok(result)
     }
 
     // String parsing.
 
-    pub fn read_esbmc_string(&mut self) -> String {
+    pub fn read_esbmc_string(&mut self) -> Result<String, GbfError> {
         let mut bytes = Vec::<u8>::new();
-        while self.peek() != 0 {
-            let c = self.get();
+        while self.peek()? != 0 {
+            let c = self.get()?;
             if c == b'\\' {
-                bytes.push(self.get());
+                bytes.push(self.get()?);
             } else {
                 bytes.push(c);
             }
         }
         self.pointer += 1;
         let value = String::from_utf8_lossy(&bytes).to_string();
-        value
+        Ok(value)
     }
 
-    pub fn read_gb_string(&mut self) -> String {
+    pub fn read_gb_string(&mut self) -> Result<String, GbfError> {
         self.read_esbmc_string()
     }
 
     // String reference parsing. Similar than the irep one
 
-    pub fn read_esbmc_string_ref(&mut self) -> String {
-        let id = self.read_esbmc_word();
+    pub fn read_esbmc_string_ref(&mut self) -> Result<String, GbfError> {
+        let id = self.read_esbmc_word()?;
 
         if self.string_ref_container.contains_key(&id) {
-            return self.string_ref_container.get(&id).unwrap().clone();
+            return Ok(self.string_ref_container.get(&id).unwrap().clone());
         }
 
-        let value = self.read_esbmc_string();
+        let value = self.read_esbmc_string()?;
 
         self.string_ref_container.insert(id, value.clone());
-        value
+        Ok(value)
     }
 
-    pub fn read_cbmc_string_ref(&mut self) -> String {
-        let id = self.read_cbmc_word();
+    pub fn read_cbmc_string_ref(&mut self) -> Result<String, GbfError> {
+        let id = self.read_cbmc_word()?;
         if self.string_ref_container.contains_key(&id) {
-            return self.string_ref_container.get(&id).unwrap().clone();
+            return Ok(self.string_ref_container.get(&id).unwrap().clone());
         }
-        let value = self.read_gb_string();
+        let value = self.read_gb_string()?;
 
         self.string_ref_container.insert(id, value.clone());
-        value
+        Ok(value)
     }
 
     // Word reading (as u32)
 
-    pub fn read_esbmc_word(&mut self) -> u32 {
-        let raw_bytes: &[u8; 4] = self.file[self.pointer..self.pointer + 4]
-            .try_into()
-            .expect("Slice should be of length 4");
+    pub fn read_esbmc_word(&mut self) -> Result<u32, GbfError> {
+        let raw_bytes: [u8; 4] = match self.file.get(self.pointer..self.pointer + 4) {
+            Some(slice) => slice.try_into().unwrap(),
+            None => return Err(self.eof_error()),
+        };
         self.pointer += 4;
 
         // ESBMC generates this in BE form
-        u32::from_be_bytes(*raw_bytes)
+        Ok(u32::from_be_bytes(raw_bytes))
     }
 
-    pub fn read_cbmc_word(&mut self) -> u32 {
+    pub fn read_cbmc_word(&mut self) -> Result<u32, GbfError> {
+        let start = self.pointer;
         let mut shift_distance: u32 = 0;
         let mut res: u32 = 0;
-        while self.pointer < self.file.len() {
+        loop {
             if shift_distance >= 32 {
-                panic!("input number is too large");
+                return Err(GbfError::VarintOverflow {
+                    offset: start,
+                    stack: self.irep_stack.clone(),
+                });
             }
 
-            let byte: u32 = self.get() as u32;
-            res = res | ((byte & 0x7f) << shift_distance);
-            shift_distance = shift_distance + 7;
+            let byte: u32 = self.get()? as u32;
+            // Only the 4 lowest bits of the 5th byte fit into a u32
+            if shift_distance == 28 && (byte & 0x70) != 0 {
+                return Err(GbfError::VarintOverflow {
+                    offset: start,
+                    stack: self.irep_stack.clone(),
+                });
+            }
+            res |= (byte & 0x7f) << shift_distance;
+            shift_distance += 7;
             if (byte & 0x80) == 0 {
                 break;
             }
         }
 
-        if self.pointer > self.file.len() {
-            panic!("Unexpected end of stream");
-        }
-
-        return res;
+        Ok(res)
     }
 
     // GBF checks
 
-    pub fn check_esbmc_header(&mut self) -> Result<(), String> {
-        trace!("Checking esbmc header");
-        let header: &[u8; 3] = self.file[0..3]
-            .try_into()
-            .expect("GBF does not contain header");
-
-        let gbf: [u8; 3] = [b'G', b'B', b'F'];
-        if *header != gbf {
-            return Err(format!(
-                "Invalid ESBMC header. Found: {}{}{}",
-                header[0], header[1], header[2]
-            ));
+    // Guesses the format from the first bytes, without consuming them
+    pub fn detect_format(&self) -> Result<GotoFormat, GbfError> {
+        if self.file.starts_with(&[0x7f, b'G', b'B', b'F']) {
+            return Ok(GotoFormat::Cbmc);
         }
-        self.pointer = 3;
-        Ok(())
+        if self.file.starts_with(b"GBF") {
+            return Ok(GotoFormat::Esbmc);
+        }
+        if self
+            .file
+            .iter()
+            .find(|c| !c.is_ascii_whitespace())
+            .is_some_and(|c| *c == b'{')
+        {
+            return Ok(GotoFormat::Json);
+        }
+        Err(GbfError::BadHeader {
+            found: self.file.iter().take(4).copied().collect(),
+            offset: 0,
+            stack: Vec::new(),
+        })
     }
 
-    pub fn check_cbmc_header(&mut self) -> Result<(), String> {
-        trace!("Checking header");
-        assert!(self.file.len() >= 4);
-        let header: &[u8; 4] = self.file[0..4]
-            .try_into()
-            .expect("GBF does not contain header");
-
-        let gbf: [u8; 4] = [0x7f, b'G', b'B', b'F'];
-        if *header != gbf {
-            return Err(format!(
-                "Invalid CBMC header. Found: {}{}{}{}",
-                header[0], header[1], header[2], header[3]
-            ));
+    fn check_header(&mut self, expected: &[u8]) -> Result<(), GbfError> {
+        let found = match self.file.get(0..expected.len()) {
+            Some(header) => header,
+            None => {
+                return Err(GbfError::BadHeader {
+                    found: self.file.clone(),
+                    offset: 0,
+                    stack: Vec::new(),
+                })
+            }
+        };
+
+        if found != expected {
+            return Err(GbfError::BadHeader {
+                found: Vec::from(found),
+                offset: 0,
+                stack: Vec::new(),
+            });
         }
-        self.pointer = 4;
+        self.pointer = expected.len();
         Ok(())
     }
 
-    pub fn check_esbmc_version(&mut self) -> Result<(), String> {
-        let version = self.read_esbmc_word();
+    pub fn check_esbmc_header(&mut self) -> Result<(), GbfError> {
+        trace!("Checking esbmc header");
+        self.check_header(b"GBF")
+    }
+
+    pub fn check_cbmc_header(&mut self) -> Result<(), GbfError> {
+        trace!("Checking header");
+        self.check_header(&[0x7f, b'G', b'B', b'F'])
+    }
+
+    pub fn check_esbmc_version(&mut self) -> Result<(), GbfError> {
+        let offset = self.pointer;
+        let version = self.read_esbmc_word()?;
         if version != 1 {
-            return Err(format!("Invalid ESBMC version. Found {}", version));
+            self.pointer = offset;
+            return Err(self.version_error(version, &[1]));
         }
         Ok(())
     }
 
-    pub fn check_cbmc_version(&mut self) -> Result<(), String> {
-        let version = self.read_cbmc_word();
-        if version != 6 {
-            return Err(format!(
-                "Invalid CBMC version. Found {}. Only 6 is supported.",
-                version
-            ));
+    // Returns the version so the caller can dispatch on the record layout
+    pub fn check_cbmc_version(&mut self, supported: &[u32]) -> Result<u32, GbfError> {
+        let offset = self.pointer;
+        let version = self.read_cbmc_word()?;
+        if !supported.contains(&version) {
+            self.pointer = offset;
+            return Err(self.version_error(version, supported));
         }
-        Ok(())
+        Ok(version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::get_test_path;
+
+    #[test]
+    fn truncated_word() {
+        let mut reader = ByteReader::from(vec![0x80, 0x80]);
+        assert_eq!(
+            reader.read_cbmc_word(),
+            Err(GbfError::UnexpectedEof {
+                offset: 2,
+                stack: Vec::new()
+            })
+        );
+
+        let mut reader = ByteReader::from(vec![0, 0, 1]);
+        assert_eq!(reader.read_esbmc_word().unwrap_err().offset(), 0);
+    }
+
+    #[test]
+    fn varint_overflow() {
+        let mut reader = ByteReader::from(vec![0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+        assert!(matches!(
+            reader.read_cbmc_word(),
+            Err(GbfError::VarintOverflow { offset: 0, .. })
+        ));
+
+        // The 5th byte holds bits 28 to 31, more would be cut off
+        let mut reader = ByteReader::from(vec![0xff, 0xff, 0xff, 0xff, 0x0f]);
+        assert_eq!(reader.read_cbmc_word(), Ok(u32::MAX));
+        let mut reader = ByteReader::from(vec![0x80, 0x80, 0x80, 0x80, 0x10]);
+        assert!(matches!(
+            reader.read_cbmc_word(),
+            Err(GbfError::VarintOverflow { offset: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn bad_header() {
+        let mut reader = ByteReader::from(vec![b'G', b'B']);
+        assert!(matches!(
+            reader.check_esbmc_header(),
+            Err(GbfError::BadHeader { .. })
+        ));
+
+        let mut reader = ByteReader::from(vec![b'G', b'B', b'F', 0x06]);
+        assert!(matches!(
+            reader.check_cbmc_header(),
+            Err(GbfError::BadHeader { .. })
+        ));
+    }
+
+    #[test]
+    fn detect_format() {
+        let cbmc = ByteReader::read_file(&get_test_path("hello-gb.goto")).unwrap();
+        assert_eq!(cbmc.detect_format(), Ok(GotoFormat::Cbmc));
+        let esbmc = ByteReader::read_file(&get_test_path("hello.goto")).unwrap();
+        assert_eq!(esbmc.detect_format(), Ok(GotoFormat::Esbmc));
+        let json = ByteReader::from(Vec::from(&b"\n  {\"format\": \"cbmc\"}"[..]));
+        assert_eq!(json.detect_format(), Ok(GotoFormat::Json));
+
+        let elf = ByteReader::from(vec![0x7f, b'E', b'L', b'F', 0x02]);
+        assert_eq!(
+            elf.detect_format(),
+            Err(GbfError::BadHeader {
+                found: vec![0x7f, b'E', b'L', b'F'],
+                offset: 0,
+                stack: Vec::new(),
+            })
+        );
+        assert!(ByteReader::from(Vec::new()).detect_format().is_err());
+    }
+
+    #[test]
+    fn bad_version() {
+        let mut reader = ByteReader::from(vec![0x7f, b'G', b'B', b'F', 0x05]);
+        reader.check_cbmc_header().unwrap();
+        assert_eq!(reader.clone().check_cbmc_version(&[5, 6]), Ok(5));
+        assert!(matches!(
+            reader.check_cbmc_version(&[6]),
+            Err(GbfError::BadVersion {
+                found: 5,
+                offset: 4,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn irep_stack() {
+        // Irep 0 "code" with a sub irep 1 "nil" that is never terminated
+        let mut data = vec![0x00, 0x00];
+        data.extend_from_slice(b"code\0");
+        data.extend_from_slice(&[b'S', 0x01, 0x01]);
+        data.extend_from_slice(b"nil\0");
+        data.push(b'X');
+
+        let mut reader = ByteReader::from(data);
+        let err = reader.read_cbmc_reference().unwrap_err();
+        assert_eq!(
+            err,
+            GbfError::BadTerminator {
+                found: b'X',
+                offset: 14,
+                stack: vec![String::from("code"), String::from("nil")],
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Irep not terminated. Got 88 at byte 14 while decoding code > nil"
+        );
     }
 }
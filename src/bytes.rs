@@ -0,0 +1,132 @@
+// This module prepares the byte operators of CBMC (e.g. transmutes and
+// union accesses in Kani) for ESBMC. Both tools name them after their
+// endianness, which ESBMC turns into the flag of its byte_extract and
+// byte_update while migrating them:
+//
+// byte_extract_little_endian(source, offset)
+// byte_extract_big_endian(source, offset)
+// byte_update_little_endian(source, offset, value)
+// byte_update_big_endian(source, offset, value)
+//
+// CBMC accepts offsets of any integer type (Kani uses usize), ESBMC
+// expects the type of its own pointer offsets: a signed integer as wide
+// as a pointer. CBMC is fine with those, nothing is needed going back.
+
+use crate::cbmc::{CBMCFunction, CBMCSymbol};
+use crate::irep::Irept;
+
+// Byte operators and their number of operands
+const BYTE_OPERATORS: [(&str, usize); 4] = [
+    ("byte_extract_little_endian", 2),
+    ("byte_extract_big_endian", 2),
+    ("byte_update_little_endian", 3),
+    ("byte_update_big_endian", 3),
+];
+
+fn esbmc_expression(irep: &mut Irept, offset_type: &Irept) -> Result<(), String> {
+    for sub in irep.subt.iter_mut().chain(irep.named_subt.values_mut()) {
+        esbmc_expression(sub, offset_type)?;
+    }
+
+    let operands = match BYTE_OPERATORS.iter().find(|(id, _)| *id == irep.id) {
+        Some((_, operands)) => *operands,
+        None => return Ok(()),
+    };
+    if irep.subt.len() != operands {
+        return Err(format!(
+            "{} with {} operands is not supported by ESBMC, it expects {}",
+            irep.id,
+            irep.subt.len(),
+            operands
+        ));
+    }
+
+    let offset = &mut irep.subt[1];
+    if offset.named_subt.get("type") != Some(offset_type) {
+        let mut cast = Irept::typed("typecast", offset_type.clone());
+        cast.subt.push(std::mem::take(offset));
+        *offset = cast;
+    }
+    Ok(())
+}
+
+/// Casts the offsets of the byte operators in `symbols` and `functions`
+/// to the pointer offsets of ESBMC, fails on the operators it cannot read
+pub fn cbmc_bytes_to_esbmc(
+    symbols: &mut [CBMCSymbol],
+    functions: &mut [CBMCFunction],
+    pointer_width: u64,
+) -> Result<(), String> {
+    let offset_type = Irept::bitvector("signedbv", pointer_width);
+    for function in functions.iter_mut() {
+        for instruction in &mut function.instructions {
+            esbmc_expression(&mut instruction.code, &offset_type)
+                .and_then(|_| esbmc_expression(&mut instruction.guard, &offset_type))
+                .map_err(|e| format!("Function {}: {}", function.name, e))?;
+        }
+    }
+    for symbol in symbols.iter_mut() {
+        esbmc_expression(&mut symbol.value, &offset_type)
+            .map_err(|e| format!("Symbol {}: {}", symbol.name, e))?;
+    }
+    Ok(())
+}
+
+///////////
+// TESTS //
+///////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_offsets() {
+        let source = Irept::typed("symbol", Irept::bitvector("unsignedbv", 32));
+        let value = Irept::typed("symbol", Irept::bitvector("unsignedbv", 8));
+        let usize_offset = Irept::typed("constant", Irept::bitvector("unsignedbv", 64));
+        let isize_offset = Irept::typed("constant", Irept::bitvector("signedbv", 64));
+
+        for (id, operands) in BYTE_OPERATORS {
+            for offset in [usize_offset.clone(), isize_offset.clone()] {
+                let mut irep = Irept::typed(id, Irept::bitvector("unsignedbv", 32));
+                irep.subt = vec![source.clone(), offset.clone(), value.clone()];
+                irep.subt.truncate(operands);
+                let mut symbol = CBMCSymbol {
+                    value: irep,
+                    ..Default::default()
+                };
+
+                cbmc_bytes_to_esbmc(std::slice::from_mut(&mut symbol), &mut [], 64).unwrap();
+                let irep = &symbol.value;
+                assert_eq!(irep.id, id);
+                assert_eq!(
+                    irep.subt[1].named_subt["type"],
+                    Irept::bitvector("signedbv", 64)
+                );
+                // Only the offsets of other types are cast
+                match offset.named_subt["type"].id.as_str() {
+                    "signedbv" => assert_eq!(irep.subt[1], offset),
+                    _ => assert_eq!(irep.subt[1].subt[0], offset),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn byte_operands() {
+        let mut irep = Irept::from("byte_update_big_endian");
+        irep.subt = vec![Irept::from("source"), Irept::from("offset")];
+        let mut symbol = CBMCSymbol {
+            name: String::from("x"),
+            value: irep,
+            ..Default::default()
+        };
+        assert_eq!(
+            cbmc_bytes_to_esbmc(std::slice::from_mut(&mut symbol), &mut [], 64)
+                .err()
+                .unwrap(),
+            "Symbol x: byte_update_big_endian with 2 operands is not supported by ESBMC, it expects 3"
+        );
+    }
+}
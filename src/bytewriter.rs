@@ -1,5 +1,5 @@
+use crate::cbmc::{CBMCFunction, CBMCLayout, CBMCSymbol};
 pub use crate::Irept;
-use log::debug;
 use log::trace;
 use std::collections::HashMap;
 use std::io::Write;
@@ -11,10 +11,14 @@ pub struct ByteWriter {
 }
 
 impl ByteWriter {
-    pub fn write_to_file(symbols: Vec<Irept>, functions: Vec<(String, Irept)>, output: &str) {
+    pub fn write_to_file(
+        symbols: Vec<Irept>,
+        functions: Vec<(String, Irept)>,
+        output: &str,
+    ) -> std::io::Result<()> {
         trace!("(ESBMC) Writing goto file: {}", output);
         let mut writer = ByteWriter {
-            file: Vec::with_capacity((functions.len() + symbols.len())*10),
+            file: Vec::with_capacity((functions.len() + symbols.len()) * 10),
             irep_container: HashMap::new(),
             string_ref_container: HashMap::new(),
         };
@@ -38,15 +42,108 @@ impl ByteWriter {
             writer.write_reference(&irep);
         }
 
-        let mut file = std::fs::File::create(output).unwrap();
-        file.write_all(&writer.file).unwrap();
+        let mut file = std::fs::File::create(output)?;
+        file.write_all(&writer.file)
+    }
+
+    pub fn write_cbmc_to_file(
+        symbols: Vec<CBMCSymbol>,
+        functions: Vec<CBMCFunction>,
+        output: &str,
+        layout: CBMCLayout,
+    ) -> std::io::Result<()> {
+        trace!(
+            "(CBMC) Writing goto file: {} (version {})",
+            output,
+            layout.version
+        );
+        let mut writer = ByteWriter {
+            file: Vec::with_capacity((functions.len() + symbols.len()) * 10),
+            irep_container: HashMap::new(),
+            string_ref_container: HashMap::new(),
+        };
+        writer.file.push(0x7f);
+        writer.file.push(b'G');
+        writer.file.push(b'B');
+        writer.file.push(b'F');
+        writer.write_cbmc_word(layout.version);
+
+        // Add symbols
+        trace!("Writing symbols");
+        writer.write_cbmc_word(symbols.len() as u32);
+        for sym in symbols {
+            writer.write_cbmc_reference(&sym.stype);
+            writer.write_cbmc_reference(&sym.value);
+            writer.write_cbmc_reference(&sym.location);
+            writer.write_cbmc_string_reference(&sym.name);
+            writer.write_cbmc_string_reference(&sym.module);
+            writer.write_cbmc_string_reference(&sym.base_name);
+            writer.write_cbmc_string_reference(&sym.mode);
+            writer.write_cbmc_string_reference(&sym.pretty_name);
+            // Ordering is used for historical reasons.
+            writer.write_cbmc_word(0);
+            writer.write_cbmc_word(sym.pack_flags());
+        }
+
+        // Add functions
+        trace!("Writing functions");
+        writer.write_cbmc_word(functions.len() as u32);
+        for function in functions {
+            writer.write_string(&function.name);
+            writer.write_cbmc_word(function.instructions.len() as u32);
+            for instr in function.instructions {
+                writer.write_cbmc_reference(&instr.code);
+                writer.write_cbmc_reference(&instr.source_location);
+                writer.write_cbmc_word(instr.instr_type);
+                writer.write_cbmc_reference(&instr.guard);
+                writer.write_cbmc_word(instr.target_number);
+
+                writer.write_cbmc_word(instr.targets.len() as u32);
+                for target in &instr.targets {
+                    let target = target.id.parse().map_err(|_| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            format!("Function {}: invalid target {}", function.name, target.id),
+                        )
+                    })?;
+                    writer.write_cbmc_word(target);
+                }
+
+                writer.write_cbmc_word(instr.labels.len() as u32);
+                for label in &instr.labels {
+                    writer.write_cbmc_string_reference(label);
+                }
+            }
+        }
+
+        let mut file = std::fs::File::create(output)?;
+        file.write_all(&writer.file)
     }
 
     fn write_string(&mut self, value: &str) {
-        self.file.extend_from_slice(value.as_bytes());
+        for c in value.bytes() {
+            if c == 0 || c == b'\\' {
+                self.file.push(b'\\');
+            }
+            self.file.push(c);
+        }
         self.file.push(0);
     }
 
+    // CBMC words are encoded 7 bits at a time, the MSB flags a continuation
+    fn write_cbmc_word(&mut self, value: u32) {
+        let mut value = value;
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.file.push(byte);
+                break;
+            }
+            self.file.push(byte | 0x80);
+        }
+    }
+
     fn write_u32(&mut self, value: u32) {
         self.file.extend_from_slice(&value.to_be_bytes());
     }
@@ -84,6 +181,48 @@ impl ByteWriter {
         self.write_u32(id);
         self.write_irep(value);
     }
+
+    // CBMC has no comment section, comments are just named subs starting with #
+    fn write_cbmc_irep(&mut self, value: &Irept) {
+        self.write_cbmc_string_reference(&value.id);
+        for irep in &value.subt {
+            self.file.push(b'S');
+            self.write_cbmc_reference(irep);
+        }
+
+        for (name, irep) in value.named_subt.iter().chain(value.comments.iter()) {
+            self.file.push(b'N');
+            self.write_cbmc_string_reference(name);
+            self.write_cbmc_reference(irep);
+        }
+
+        self.file.push(0);
+    }
+
+    fn write_cbmc_reference(&mut self, value: &Irept) {
+        if self.irep_container.contains_key(value) {
+            let id = self.irep_container[value];
+            self.write_cbmc_word(id);
+            return;
+        }
+        let id = self.irep_container.len() as u32;
+        self.irep_container.insert(value.clone(), id);
+        self.write_cbmc_word(id);
+        self.write_cbmc_irep(value);
+    }
+
+    fn write_cbmc_string_reference(&mut self, value: &str) {
+        if self.string_ref_container.contains_key(value) {
+            let id = self.string_ref_container[value];
+            self.write_cbmc_word(id);
+            return;
+        }
+        let id = self.string_ref_container.len() as u32;
+        self.string_ref_container.insert(String::from(value), id);
+        self.write_cbmc_word(id);
+        self.write_string(value);
+    }
+
     fn write_string_reference(&mut self, value: &str) {
         if self.string_ref_container.contains_key(value) {
             let id = self.string_ref_container[value];
@@ -96,3 +235,26 @@ impl ByteWriter {
         self.write_string(value);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::get_test_path;
+
+    #[test]
+    fn invalid_cbmc_target() {
+        let mut result = crate::cbmc::process_cbmc_file(&get_test_path("hello-gb.goto")).unwrap();
+        result.functions_irep[0].instructions[0].targets = vec![Irept::from("end")];
+        let output = "/tmp/test_invalid_cbmc_target.goto";
+        let err = ByteWriter::write_cbmc_to_file(
+            result.symbols_irep,
+            result.functions_irep,
+            output,
+            CBMCLayout::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("invalid target end"));
+        assert!(!std::path::Path::new(output).exists());
+    }
+}
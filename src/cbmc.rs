@@ -1,15 +1,14 @@
-use crate::bytereader::ByteReader;
+use crate::bytereader::{ByteReader, GbfError};
 pub use crate::Irept;
 use log::debug;
-use log::info;
-use std::collections::HashMap;
+use std::collections::HashSet;
 
 ///////////////
 // CBMC DATA //
 ///////////////
 
 // Direct parsing result of a symbol
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct CBMCSymbol {
     pub stype: Irept, // stype => type. Rust reserves some weird words
     pub value: Irept,
@@ -38,39 +37,68 @@ pub struct CBMCSymbol {
     pub is_extern: bool,
     pub is_volatile: bool,
 }
-impl Default for CBMCSymbol {
-    fn default() -> Self {
-        CBMCSymbol {
-            stype: Irept::default(),
-            value: Irept::default(),
-            location: Irept::default(),
-            name: String::default(),
-            module: String::default(),
-            base_name: String::default(),
-            mode: String::default(),
-            pretty_name: String::default(),
-            flags: 0,
-            is_type: false,
-            is_weak: false,
-            is_property: false,
-            is_macro: false,
-            is_exported: false,
-            is_input: false,
-            is_output: false,
-            is_state_var: false,
-            is_parameter: false,
-            is_auxiliary: false,
-            binding: false,
-            is_lvalue: false,
-            is_static_lifetime: false,
-            is_thread_local: false,
-            is_file_local: false,
-            is_extern: false,
-            is_volatile: false,
-        }
+
+impl CBMCSymbol {
+    // Flags in the same order as they are packed in the GBF flags word (MSB first)
+    pub fn flag_list(&self) -> [(&'static str, bool); 17] {
+        [
+            ("is_weak", self.is_weak),
+            ("is_type", self.is_type),
+            ("is_property", self.is_property),
+            ("is_macro", self.is_macro),
+            ("is_exported", self.is_exported),
+            ("is_input", self.is_input),
+            ("is_output", self.is_output),
+            ("is_state_var", self.is_state_var),
+            ("is_parameter", self.is_parameter),
+            ("is_auxiliary", self.is_auxiliary),
+            ("binding", self.binding),
+            ("is_lvalue", self.is_lvalue),
+            ("is_static_lifetime", self.is_static_lifetime),
+            ("is_thread_local", self.is_thread_local),
+            ("is_file_local", self.is_file_local),
+            ("is_extern", self.is_extern),
+            ("is_volatile", self.is_volatile),
+        ]
+    }
+
+    // Sets a flag by the name used in flag_list. Returns false for unknown flags
+    pub fn set_flag(&mut self, name: &str, value: bool) -> bool {
+        let flag = match name {
+            "is_weak" => &mut self.is_weak,
+            "is_type" => &mut self.is_type,
+            "is_property" => &mut self.is_property,
+            "is_macro" => &mut self.is_macro,
+            "is_exported" => &mut self.is_exported,
+            "is_input" => &mut self.is_input,
+            "is_output" => &mut self.is_output,
+            "is_state_var" => &mut self.is_state_var,
+            "is_parameter" => &mut self.is_parameter,
+            "is_auxiliary" => &mut self.is_auxiliary,
+            "binding" => &mut self.binding,
+            "is_lvalue" => &mut self.is_lvalue,
+            "is_static_lifetime" => &mut self.is_static_lifetime,
+            "is_thread_local" => &mut self.is_thread_local,
+            "is_file_local" => &mut self.is_file_local,
+            "is_extern" => &mut self.is_extern,
+            "is_volatile" => &mut self.is_volatile,
+            _ => return false,
+        };
+        *flag = value;
+        true
+    }
+
+    // Inverse of the flag unpacking done in process_cbmc_file
+    pub fn pack_flags(&self) -> u32 {
+        self.flag_list()
+            .iter()
+            .fold(0, |flags, (_, bit)| (flags << 1) | (*bit as u32))
     }
 }
 
+// CBMC uses this target number for instructions that are not the target of any goto
+pub const NO_TARGET_NUMBER: u32 = u32::MAX;
+
 // Direct parsing result of an instruction
 #[derive(Clone, Debug)]
 pub struct CBMCInstruction {
@@ -84,6 +112,72 @@ pub struct CBMCInstruction {
     pub function: Irept,
 }
 
+// Kinds of instruction (goto_program_instruction_typet), instructions keep
+// the number as it is in the goto binary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CBMCInstructionKind {
+    NoInstructionType = 0,
+    Goto = 1,
+    Assume = 2,
+    Assert = 3,
+    Other = 4,
+    Skip = 5,
+    StartThread = 6,
+    EndThread = 7,
+    Location = 8,
+    EndFunction = 9,
+    AtomicBegin = 10,
+    AtomicEnd = 11,
+    SetReturnValue = 12,
+    Assign = 13,
+    Decl = 14,
+    Dead = 15,
+    FunctionCall = 16,
+    Throw = 17,
+    Catch = 18,
+    IncompleteGoto = 19,
+}
+
+impl CBMCInstructionKind {
+    const KINDS: [(CBMCInstructionKind, &'static str); 20] = [
+        (Self::NoInstructionType, "NO_INSTRUCTION_TYPE"),
+        (Self::Goto, "GOTO"),
+        (Self::Assume, "ASSUME"),
+        (Self::Assert, "ASSERT"),
+        (Self::Other, "OTHER"),
+        (Self::Skip, "SKIP"),
+        (Self::StartThread, "START_THREAD"),
+        (Self::EndThread, "END_THREAD"),
+        (Self::Location, "LOCATION"),
+        (Self::EndFunction, "END_FUNCTION"),
+        (Self::AtomicBegin, "ATOMIC_BEGIN"),
+        (Self::AtomicEnd, "ATOMIC_END"),
+        (Self::SetReturnValue, "SET_RETURN_VALUE"),
+        (Self::Assign, "ASSIGN"),
+        (Self::Decl, "DECL"),
+        (Self::Dead, "DEAD"),
+        (Self::FunctionCall, "FUNCTION_CALL"),
+        (Self::Throw, "THROW"),
+        (Self::Catch, "CATCH"),
+        (Self::IncompleteGoto, "INCOMPLETE_GOTO"),
+    ];
+
+    pub fn from_u32(value: u32) -> Option<Self> {
+        Self::KINDS
+            .iter()
+            .find(|(kind, _)| *kind as u32 == value)
+            .map(|(kind, _)| *kind)
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::KINDS
+            .iter()
+            .find(|(kind, _)| *kind == self)
+            .unwrap()
+            .1
+    }
+}
+
 // Direct parsing result of a function
 #[derive(Clone, Debug)]
 pub struct CBMCFunction {
@@ -91,6 +185,36 @@ pub struct CBMCFunction {
     pub instructions: Vec<CBMCInstruction>,
 }
 
+////////////
+// LAYOUT //
+////////////
+
+// Goto binary versions that can be read and written, only the ones checked
+// against the binaries of goto-cc
+pub const SUPPORTED_CBMC_VERSIONS: [u32; 1] = [6];
+pub const DEFAULT_CBMC_VERSION: u32 = 6;
+
+// Differences in the record layout between goto binary versions. Everything
+// else (header, irep sharing, symbol records and flags) is the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CBMCLayout {
+    pub version: u32,
+}
+
+impl CBMCLayout {
+    pub fn from_version(version: u32) -> Option<Self> {
+        SUPPORTED_CBMC_VERSIONS
+            .contains(&version)
+            .then_some(CBMCLayout { version })
+    }
+}
+
+impl Default for CBMCLayout {
+    fn default() -> Self {
+        CBMCLayout::from_version(DEFAULT_CBMC_VERSION).unwrap()
+    }
+}
+
 ////////////
 // PARSER //
 ////////////
@@ -101,46 +225,133 @@ pub struct CBMCParseResult {
     pub symbols_irep: Vec<CBMCSymbol>,
     pub functions_irep: Vec<CBMCFunction>,
     pub entrypoint: String,
+    // Layout of the binary the program was read from
+    pub layout: CBMCLayout,
+}
+
+impl CBMCParseResult {
+    // Returns the requested entrypoint if it exists. Otherwise, it looks for
+    // __CPROVER__start and then for a single Kani harness.
+    pub fn find_entrypoint(&self, requested: Option<&str>) -> Result<String, String> {
+        let exists = |name: &str| self.functions_irep.iter().any(|f| f.name == name);
+        if let Some(name) = requested {
+            return match exists(name) {
+                true => Ok(String::from(name)),
+                false => Err(format!(
+                    "Entrypoint {} not found in the function table",
+                    name
+                )),
+            };
+        }
+
+        if exists(DEFAULT_ENTRYPOINT) {
+            return Ok(String::from(DEFAULT_ENTRYPOINT));
+        }
+
+        let candidates = self.harness_candidates();
+        match candidates.len() {
+            1 => {
+                debug!("Using harness {} as entrypoint", candidates[0]);
+                Ok(candidates[0].clone())
+            }
+            0 => Err(format!(
+                "No {} or harness found, please provide an entrypoint",
+                DEFAULT_ENTRYPOINT
+            )),
+            _ => Err(format!(
+                "Multiple harnesses found, please provide one as entrypoint: {}",
+                candidates.join(", ")
+            )),
+        }
+    }
+
+    // Kani harnesses are Rust functions (with a mangled name) that have a
+    // body and are not referenced by any other function.
+    pub fn harness_candidates(&self) -> Vec<String> {
+        let mut referenced: HashSet<String> = HashSet::new();
+        for function in &self.functions_irep {
+            let mut identifiers = HashSet::new();
+            for instr in &function.instructions {
+                collect_identifiers(&instr.code, &mut identifiers);
+                collect_identifiers(&instr.guard, &mut identifiers);
+            }
+            identifiers.remove(&function.name);
+            referenced.extend(identifiers);
+        }
+        for sym in &self.symbols_irep {
+            collect_identifiers(&sym.value, &mut referenced);
+        }
+
+        self.functions_irep
+            .iter()
+            .filter(|f| f.name.starts_with("_R") || f.name.starts_with("_ZN"))
+            .filter(|f| !f.instructions.is_empty() && !referenced.contains(&f.name))
+            .map(|f| f.name.clone())
+            .collect()
+    }
+}
+
+fn collect_identifiers(irep: &Irept, identifiers: &mut HashSet<String>) {
+    if irep.id == "symbol" {
+        if let Some(identifier) = irep.named_subt.get("identifier") {
+            identifiers.insert(identifier.id.clone());
+        }
+    }
+    for sub in irep.subt.iter().chain(irep.named_subt.values()) {
+        collect_identifiers(sub, identifiers);
+    }
 }
 
-pub fn process_cbmc_file(path: &str, entrypoint: &str) -> CBMCParseResult {
+// Name of the entrypoint generated by CBMC
+pub const DEFAULT_ENTRYPOINT: &str = "__CPROVER__start";
+
+// The entrypoint defaults to __CPROVER__start, use find_entrypoint to pick
+// another one.
+pub fn process_cbmc_file(path: &str) -> Result<CBMCParseResult, GbfError> {
     let mut result = CBMCParseResult {
-        reader: ByteReader::read_file(path),
+        reader: ByteReader::read_file(path)?,
         functions_irep: Vec::new(),
         symbols_irep: Vec::new(),
-        entrypoint: String::from(entrypoint),
+        entrypoint: String::from(DEFAULT_ENTRYPOINT),
+        layout: CBMCLayout::default(),
     };
 
-    result.reader.check_cbmc_header().unwrap();
-    result.reader.check_cbmc_version().unwrap();
+    result.reader.check_cbmc_header()?;
+    let version = result.reader.check_cbmc_version(&SUPPORTED_CBMC_VERSIONS)?;
+    let layout = CBMCLayout::from_version(version).unwrap();
+    result.layout = layout;
+    debug!("Got goto binary version {}", version);
 
     // Symbol table
-    let number_of_symbols = result.reader.read_cbmc_word();
+    let number_of_symbols = result.reader.read_cbmc_word()?;
     debug!("Got {} symbols", number_of_symbols);
     for _ in 0..number_of_symbols {
         let mut sym = CBMCSymbol::default();
         // Type is a... type
-        sym.stype = result.reader.read_cbmc_reference();
+        sym.stype = result.reader.read_cbmc_reference()?;
         // Value is an expr
-        sym.value = result.reader.read_cbmc_reference();
+        sym.value = result.reader.read_cbmc_reference()?;
         // Location is just a string
-        sym.location = result.reader.read_cbmc_reference();
+        sym.location = result.reader.read_cbmc_reference()?;
         // Name is just a string
-        sym.name = result.reader.read_cbmc_string_ref();
+        sym.name = result.reader.read_cbmc_string_ref()?;
         // Module is just a string
-        sym.module = result.reader.read_cbmc_string_ref();
+        sym.module = result.reader.read_cbmc_string_ref()?;
         // Base name is just a string
-        sym.base_name = result.reader.read_cbmc_string_ref();
+        sym.base_name = result.reader.read_cbmc_string_ref()?;
         // Symbol mode conveys the language (C, C++, Rust, etc)
-        sym.mode = result.reader.read_cbmc_string_ref();
+        sym.mode = result.reader.read_cbmc_string_ref()?;
         // String
-        sym.pretty_name = result.reader.read_cbmc_string_ref();
+        sym.pretty_name = result.reader.read_cbmc_string_ref()?;
 
-        // Ordering is used for historical reasons.
-        let ordering = result.reader.read_cbmc_word();
-        assert_eq!(ordering, 0);
+        // Ordering is used for historical reasons. Older versions
+        // wrote the symbol number here, newer ones always write 0.
+        let ordering = result.reader.read_cbmc_word()?;
+        if ordering != 0 {
+            debug!("Ignoring ordering {} of {}", ordering, sym.name);
+        }
 
-        sym.flags = result.reader.read_cbmc_word();
+        sym.flags = result.reader.read_cbmc_word()?;
 
         sym.is_type = sym.flags & (1 << 15) != 0;
         sym.is_weak = sym.flags & (1 << 16) != 0;
@@ -164,11 +375,11 @@ pub fn process_cbmc_file(path: &str, entrypoint: &str) -> CBMCParseResult {
     }
 
     // Functions
-    let number_of_functions = result.reader.read_cbmc_word();
+    let number_of_functions = result.reader.read_cbmc_word()?;
     debug!("Got {} functions", number_of_functions);
     for _ in 0..number_of_functions {
-        let function_name = result.reader.read_gb_string();
-        let num_of_instructions = result.reader.read_cbmc_word();
+        let function_name = result.reader.read_gb_string()?;
+        let num_of_instructions = result.reader.read_cbmc_word()?;
 
         let mut function = CBMCFunction {
             name: function_name,
@@ -177,28 +388,28 @@ pub fn process_cbmc_file(path: &str, entrypoint: &str) -> CBMCParseResult {
 
         for _ in 0..num_of_instructions {
             // # instructions
-            let code = result.reader.read_cbmc_reference();
+            let code = result.reader.read_cbmc_reference()?;
 
-            let source_location = result.reader.read_cbmc_reference();
-            let instr_type = result.reader.read_cbmc_word();
-            let guard = result.reader.read_cbmc_reference();
+            let source_location = result.reader.read_cbmc_reference()?;
+            let instr_type = result.reader.read_cbmc_word()?;
+            let guard = result.reader.read_cbmc_reference()?;
 
             // Label?
-            let target_number = result.reader.read_cbmc_word();
+            let target_number = result.reader.read_cbmc_word()?;
 
             // Add targets
-            let t_count = result.reader.read_cbmc_word();
+            let t_count = result.reader.read_cbmc_word()?;
             let mut targets: Vec<Irept> = Vec::new();
             for _ in 0..t_count {
                 // TODO: These should be stored as numbers.
-                targets.push(Irept::from(result.reader.read_cbmc_word().to_string()));
+                targets.push(Irept::from(result.reader.read_cbmc_word()?.to_string()));
             }
 
             // Add labels
-            let l_count = result.reader.read_cbmc_word();
+            let l_count = result.reader.read_cbmc_word()?;
             let mut labels: Vec<String> = Vec::default();
             for _ in 0..l_count {
-                let label = result.reader.read_cbmc_string_ref();
+                let label = result.reader.read_cbmc_string_ref()?;
                 labels.push(label);
             }
 
@@ -210,13 +421,29 @@ pub fn process_cbmc_file(path: &str, entrypoint: &str) -> CBMCParseResult {
                 target_number,
                 targets,
                 labels,
-                function: Irept::from(&function.name),
+                function: Irept::from(function.name.as_str()),
             })
         }
 
+        // Targets must point to an instruction of the same function
+        for instr in &function.instructions {
+            for t in &instr.targets {
+                let number: u32 = t.id.parse().unwrap();
+                if !function
+                    .instructions
+                    .iter()
+                    .any(|x| x.target_number == number)
+                {
+                    return Err(result
+                        .reader
+                        .unknown_reference_error(number, &function.name));
+                }
+            }
+        }
+
         result.functions_irep.push(function);
     }
-    result
+    Ok(result)
 }
 
 ///////////
@@ -234,6 +461,152 @@ mod tests {
         let test_path = std::path::Path::new(&cargo_dir).join("resources/test/hello-gb.goto");
         assert!(test_path.exists());
 
-        crate::cbmc::process_cbmc_file(test_path.to_str().unwrap(), "__CPROVER__start");
+        crate::cbmc::process_cbmc_file(test_path.to_str().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_cbmc_roundtrip() {
+        let test_path = crate::resources::get_test_path("hello-gb.goto");
+        let result = crate::cbmc::process_cbmc_file(&test_path).unwrap();
+
+        let output = "/tmp/test_cbmc_roundtrip.goto";
+        std::fs::remove_file(output).ok();
+        crate::ByteWriter::write_cbmc_to_file(
+            result.symbols_irep.clone(),
+            result.functions_irep.clone(),
+            output,
+            crate::cbmc::CBMCLayout::default(),
+        )
+        .unwrap();
+        let written = crate::cbmc::process_cbmc_file(output).unwrap();
+        std::fs::remove_file(output).ok();
+
+        assert_eq!(result.symbols_irep.len(), written.symbols_irep.len());
+        for (lhs, rhs) in result.symbols_irep.iter().zip(&written.symbols_irep) {
+            assert_eq!(lhs.name, rhs.name);
+            assert_eq!(lhs.flags, rhs.flags);
+            assert_eq!(lhs.stype, rhs.stype);
+            assert_eq!(lhs.value, rhs.value);
+        }
+
+        assert_eq!(result.functions_irep.len(), written.functions_irep.len());
+        for (lhs, rhs) in result.functions_irep.iter().zip(&written.functions_irep) {
+            assert_eq!(lhs.name, rhs.name);
+            for (l, r) in lhs.instructions.iter().zip(&rhs.instructions) {
+                assert_eq!(l.code, r.code);
+                assert_eq!(l.instr_type, r.instr_type);
+                assert_eq!(l.target_number, r.target_number);
+                assert_eq!(l.targets, r.targets);
+                assert_eq!(l.labels, r.labels);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cbmc_versions() {
+        use crate::cbmc::{CBMCLayout, SUPPORTED_CBMC_VERSIONS};
+        let test_path = crate::resources::get_test_path("hello-gb.goto");
+        let result = crate::cbmc::process_cbmc_file(&test_path).unwrap();
+
+        for version in SUPPORTED_CBMC_VERSIONS {
+            let output = format!("/tmp/test_cbmc_versions_{}.goto", version);
+            crate::ByteWriter::write_cbmc_to_file(
+                result.symbols_irep.clone(),
+                result.functions_irep.clone(),
+                &output,
+                CBMCLayout::from_version(version).unwrap(),
+            )
+            .unwrap();
+            let bytes = std::fs::read(&output).unwrap();
+            assert_eq!(bytes[4] as u32, version);
+
+            let written = crate::cbmc::process_cbmc_file(&output).unwrap();
+            std::fs::remove_file(&output).ok();
+            assert_eq!(result.symbols_irep.len(), written.symbols_irep.len());
+            for (lhs, rhs) in result.functions_irep.iter().zip(&written.functions_irep) {
+                assert_eq!(lhs.name, rhs.name);
+                for (l, r) in lhs.instructions.iter().zip(&rhs.instructions) {
+                    assert_eq!(l.code, r.code);
+                    assert_eq!(l.source_location, r.source_location);
+                    assert_eq!(l.targets, r.targets);
+                    assert_eq!(l.function, r.function);
+                }
+            }
+        }
+        assert!(CBMCLayout::from_version(5).is_none());
+    }
+
+    #[test]
+    fn test_esbmc_to_cbmc_file() {
+        let test_path = crate::resources::get_test_path("hello.goto");
+        let output = "/tmp/test_esbmc_to_cbmc.goto";
+        crate::esbmc2cbmc(
+            &test_path,
+            output,
+            crate::cbmc::CBMCLayout::default(),
+            &crate::rules::RuleSet::default(),
+        )
+        .unwrap();
+
+        let result = crate::cbmc::process_cbmc_file(output).unwrap();
+        std::fs::remove_file(output).ok();
+        assert!(result
+            .functions_irep
+            .iter()
+            .any(|f| f.name == "__CPROVER__start"));
+    }
+
+    #[test]
+    fn test_pack_flags() {
+        let test_path = crate::resources::get_test_path("hello-gb.goto");
+        let result = crate::cbmc::process_cbmc_file(&test_path).unwrap();
+        for sym in result.symbols_irep {
+            assert_eq!(sym.flags, sym.pack_flags());
+        }
+    }
+
+    #[test]
+    fn test_truncated_file() {
+        use crate::bytereader::GbfError;
+        let test_path = crate::resources::get_test_path("hello-gb.goto");
+        let mut bytes = std::fs::read(test_path).unwrap();
+        bytes.truncate(bytes.len() / 2);
+        let output = "/tmp/test_truncated_file.goto";
+        std::fs::write(output, &bytes).unwrap();
+        let result = crate::cbmc::process_cbmc_file(output);
+        std::fs::remove_file(output).ok();
+        match result {
+            Err(GbfError::UnexpectedEof { offset, .. }) => assert_eq!(offset, bytes.len()),
+            Err(err) => panic!("Unexpected error: {}", err),
+            Ok(_) => panic!("Truncated file was accepted"),
+        }
+    }
+
+    #[test]
+    fn test_find_entrypoint() {
+        let test_path = crate::resources::get_test_path("first_steps.rs.goto");
+        let mut result = crate::cbmc::process_cbmc_file(&test_path).unwrap();
+        assert_eq!(result.find_entrypoint(None).unwrap(), "__CPROVER__start");
+
+        let harness = "_RNvCslLLkJv4pJGE_11first_steps19check_estimate_size";
+        assert_eq!(result.find_entrypoint(Some(harness)).unwrap(), harness);
+        assert_eq!(
+            result.find_entrypoint(Some("main")).unwrap_err(),
+            "Entrypoint main not found in the function table"
+        );
+
+        // Without __CPROVER__start the harness is the only uncalled Rust function
+        result
+            .functions_irep
+            .retain(|f| f.name != "__CPROVER__start");
+        assert_eq!(result.harness_candidates(), vec![harness]);
+        assert_eq!(result.find_entrypoint(None).unwrap(), harness);
+
+        let mut other = result.functions_irep[0].clone();
+        other.name = String::from("_RNvCslLLkJv4pJGE_11first_steps5other");
+        result.functions_irep.push(other);
+        let err = result.find_entrypoint(None).unwrap_err();
+        assert!(err.starts_with("Multiple harnesses found"));
+        assert!(err.contains(harness));
     }
 }
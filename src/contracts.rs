@@ -0,0 +1,978 @@
+// This module translates the contracts of CBMC into instructions. CBMC
+// keeps the clauses of a function as lambdas in the type of its contract
+// symbol ("contract::f", or the function itself in older versions):
+//
+//   #spec_requires: [lambda((__CPROVER_return_value, a, b), a < 10)]
+//   #spec_ensures:  [lambda((__CPROVER_return_value, a, b), __CPROVER_return_value == a * b)]
+//
+// and the clauses of a loop in the guard of its back-edge (the last GOTO
+// of the loop) as plain expressions: #spec_loop_invariant, #spec_decreases.
+//
+// ESBMC cannot read contracts from a goto binary, so they are lowered as
+// goto-instrument would (see ContractOption):
+//
+//   - functions with a body check their contract: ASSUME the requires on
+//     entry and ASSERT the ensures before every return. Only the harness
+//     may call them without meeting the requires (--enforce-contract), the
+//     other calls ASSERT them.
+//   - calls to functions without a body use the contract: ASSERT the
+//     requires before the call and ASSUME the ensures after it.
+//   - loop invariants are ASSERTed before the loop and before every
+//     iteration, decreases clauses must decrease at every iteration.
+//
+// Programs already instrumented by goto-instrument are kept as they are.
+// Either way the clauses are removed, ESBMC does not know them.
+
+use std::collections::HashMap;
+
+use log::{info, warn};
+
+use crate::cbmc::{
+    CBMCFunction, CBMCInstruction, CBMCInstructionKind, CBMCSymbol, DEFAULT_ENTRYPOINT,
+    NO_TARGET_NUMBER,
+};
+use crate::irep::Irept;
+
+// Functions of the library that goto-instrument adds with the contracts
+const INSTRUMENTED_PREFIX: &str = "__CPROVER_contracts_";
+
+const FUNCTION_CLAUSES: [&str; 4] = [
+    "#spec_requires",
+    "#spec_ensures",
+    "#spec_assigns",
+    "#spec_frees",
+];
+const LOOP_CLAUSES: [&str; 4] = [
+    "#spec_loop_invariant",
+    "#spec_decreases",
+    "#spec_assigns",
+    "#spec_frees",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContractOption {
+    // goto-instrument did it already
+    Instrumented,
+    // ASSUME the requires, ASSERT the ensures (--enforce-contract). Calls
+    // from outside the harness ASSERT the requires.
+    Checked,
+    // ASSERT the requires, ASSUME the ensures (--replace-call-with-contract)
+    Replaced,
+    // Not used by the program
+    Unused,
+}
+
+impl ContractOption {
+    fn description(&self) -> &'static str {
+        match self {
+            ContractOption::Instrumented => "already instrumented by goto-instrument",
+            ContractOption::Checked => {
+                "checked by the function (ASSUME requires, ASSERT ensures) and its non-harness callers (ASSERT requires)"
+            }
+            ContractOption::Replaced => "replaced at its calls (ASSERT requires, ASSUME ensures)",
+            ContractOption::Unused => "not used",
+        }
+    }
+}
+
+fn boolean(id: &str, operands: Vec<Irept>) -> Irept {
+    let mut result = Irept::typed(id, Irept::from("bool"));
+    result.subt = operands;
+    result
+}
+
+fn identifier(irep: &Irept) -> &str {
+    irep.named_subt
+        .get("identifier")
+        .map(|i| i.id.as_str())
+        .unwrap_or_default()
+}
+
+// `kind` instruction with `condition` as its guard, at the location of `like`
+fn guarded(kind: CBMCInstructionKind, condition: Irept, like: &CBMCInstruction) -> CBMCInstruction {
+    CBMCInstruction {
+        code: Irept::from("nil"),
+        source_location: like.source_location.clone(),
+        instr_type: kind as u32,
+        guard: condition,
+        target_number: NO_TARGET_NUMBER,
+        targets: vec![],
+        labels: vec![],
+        function: like.function.clone(),
+    }
+}
+
+fn code(statement: &str, operands: Vec<Irept>, like: &CBMCInstruction) -> Irept {
+    let mut result = Irept::code(statement, operands);
+    result.named_subt.insert(
+        String::from("#source_location"),
+        like.source_location.clone(),
+    );
+    result
+}
+
+// Local variable of `function`, for the values the contracts remember
+fn local(name: &str, stype: Irept, function: &str, symbols: &mut Vec<CBMCSymbol>) {
+    let like = symbols
+        .iter()
+        .find(|s| s.name == function)
+        .cloned()
+        .unwrap_or_default();
+    let mut result = CBMCSymbol {
+        stype,
+        value: Irept::from("nil"),
+        location: like.location,
+        name: String::from(name),
+        module: like.module,
+        base_name: String::from(name),
+        mode: like.mode,
+        pretty_name: String::from(name),
+        is_lvalue: true,
+        is_thread_local: true,
+        is_file_local: true,
+        ..Default::default()
+    };
+    result.flags = result.pack_flags();
+    symbols.push(result);
+}
+
+// Inserts `instructions` before `index`, jumps into `index` go through them
+fn insert_before(
+    function: &mut CBMCFunction,
+    index: usize,
+    mut instructions: Vec<CBMCInstruction>,
+) -> usize {
+    if instructions.is_empty() {
+        return 0;
+    }
+    let count = instructions.len();
+    let target = &mut function.instructions[index];
+    instructions[0].target_number = target.target_number;
+    instructions[0].labels = std::mem::take(&mut target.labels);
+    target.target_number = NO_TARGET_NUMBER;
+    function.instructions.splice(index..index, instructions);
+    count
+}
+
+fn replace_symbols(irep: &mut Irept, replacements: &HashMap<String, Irept>) {
+    if irep.id == "symbol" {
+        if let Some(replacement) = replacements.get(identifier(irep)) {
+            *irep = replacement.clone();
+            return;
+        }
+    }
+    for sub in irep.subt.iter_mut().chain(irep.named_subt.values_mut()) {
+        replace_symbols(sub, replacements);
+    }
+}
+
+// The clauses of a function contract are lambdas over the return value and
+// the parameters: lambda((ret, p1, ..., pn), condition)
+struct Clause {
+    parameters: Vec<String>,
+    condition: Irept,
+}
+
+impl Clause {
+    fn from_lambda(lambda: &Irept) -> Option<Clause> {
+        if lambda.id != "lambda" || lambda.subt.len() != 2 {
+            return None;
+        }
+        Some(Clause {
+            parameters: lambda.subt[0]
+                .subt
+                .iter()
+                .map(|p| String::from(identifier(p)))
+                .collect(),
+            condition: lambda.subt[1].clone(),
+        })
+    }
+
+    fn mentions_return_value(&self) -> bool {
+        fn mentions(irep: &Irept, name: &str) -> bool {
+            (irep.id == "symbol" && identifier(irep) == name)
+                || irep
+                    .subt
+                    .iter()
+                    .chain(irep.named_subt.values())
+                    .any(|sub| mentions(sub, name))
+        }
+        self.parameters
+            .first()
+            .is_some_and(|ret| mentions(&self.condition, ret))
+    }
+
+    // The condition for the given return value and arguments
+    fn apply(&self, return_value: Option<&Irept>, arguments: &[Irept]) -> Irept {
+        let mut replacements = HashMap::new();
+        let mut parameters = self.parameters.iter();
+        if let (Some(ret), Some(value)) = (parameters.next(), return_value) {
+            replacements.insert(ret.clone(), value.clone());
+        }
+        for (parameter, argument) in parameters.zip(arguments) {
+            replacements.insert(parameter.clone(), argument.clone());
+        }
+        let mut result = self.condition.clone();
+        replace_symbols(&mut result, &replacements);
+        if result.named_subt.get("type").is_none_or(|t| t.id != "bool") {
+            result = boolean("typecast", vec![result]);
+        }
+        result
+    }
+}
+
+struct Contract {
+    requires: Vec<Clause>,
+    ensures: Vec<Clause>,
+}
+
+fn clauses(stype: &Irept, name: &str) -> Vec<Clause> {
+    match stype.named_subt.get(name) {
+        Some(clauses) => clauses
+            .subt
+            .iter()
+            .filter_map(Clause::from_lambda)
+            .collect(),
+        None => vec![],
+    }
+}
+
+// Removes the clauses of `irep` (and of its subexpressions), returns the
+// ones that were not empty
+fn remove_clauses(irep: &mut Irept, names: &[&str]) -> Vec<String> {
+    let mut removed = vec![];
+    for name in names {
+        if let Some(clause) = irep.named_subt.remove(*name) {
+            if !clause.subt.is_empty() {
+                removed.push(String::from(*name));
+            }
+        }
+    }
+    for sub in irep.subt.iter_mut().chain(irep.named_subt.values_mut()) {
+        removed.extend(remove_clauses(sub, names));
+    }
+    removed
+}
+
+// ASSUME the requires on entry and ASSERT the ensures before every return
+fn check(
+    function: &mut CBMCFunction,
+    contract: &Contract,
+    parameters: &[Irept],
+    symbols: &mut Vec<CBMCSymbol>,
+) {
+    let entry = function.instructions[0].clone();
+    let mut prologue: Vec<CBMCInstruction> = contract
+        .requires
+        .iter()
+        .map(|c| {
+            guarded(
+                CBMCInstructionKind::Assume,
+                c.apply(None, parameters),
+                &entry,
+            )
+        })
+        .collect();
+
+    // The ensures are about the values of the parameters on entry
+    let mut old_parameters = vec![];
+    for parameter in parameters {
+        let name = format!("{}::contract::{}", function.name, identifier(parameter));
+        let stype = parameter.named_subt["type"].clone();
+        let old = Irept::symbol(&name, stype.clone());
+        prologue.push(CBMCInstruction {
+            code: code("decl", vec![old.clone()], &entry),
+            ..guarded(CBMCInstructionKind::Decl, boolean("true", vec![]), &entry)
+        });
+        prologue.push(CBMCInstruction {
+            code: code("assign", vec![old.clone(), parameter.clone()], &entry),
+            ..guarded(CBMCInstructionKind::Assign, boolean("true", vec![]), &entry)
+        });
+        local(&name, stype, &function.name, symbols);
+        old_parameters.push(old);
+    }
+    // Jumps to the first instruction do not enter the function again
+    let mut index = prologue.len();
+    function.instructions.splice(0..0, prologue);
+
+    // Functions without a return value are checked at their end
+    let returns = function
+        .instructions
+        .iter()
+        .any(|i| i.instr_type == CBMCInstructionKind::SetReturnValue as u32);
+    while index < function.instructions.len() {
+        let instruction = &function.instructions[index];
+        let return_value = match CBMCInstructionKind::from_u32(instruction.instr_type) {
+            Some(CBMCInstructionKind::SetReturnValue) => instruction.code.subt.first().cloned(),
+            Some(CBMCInstructionKind::EndFunction) if !returns => None,
+            _ => {
+                index += 1;
+                continue;
+            }
+        };
+        let like = instruction.clone();
+        let checks = contract
+            .ensures
+            .iter()
+            .map(|c| {
+                guarded(
+                    CBMCInstructionKind::Assert,
+                    c.apply(return_value.as_ref(), &old_parameters),
+                    &like,
+                )
+            })
+            .collect();
+        index += insert_before(function, index, checks) + 1;
+    }
+}
+
+// Callee of a FUNCTION_CALL instruction
+fn callee(instruction: &CBMCInstruction) -> Option<&str> {
+    match instruction.instr_type == CBMCInstructionKind::FunctionCall as u32
+        && instruction.code.subt.len() == 3
+    {
+        true => Some(identifier(&instruction.code.subt[1])),
+        false => None,
+    }
+}
+
+// Functions that call the ones under verification: the entrypoint, and the
+// functions called by __CPROVER__start (e.g. main) when it is the entrypoint
+fn harness(functions: &[CBMCFunction], entrypoint: &str) -> Vec<String> {
+    let mut result = vec![String::from(entrypoint)];
+    if entrypoint == DEFAULT_ENTRYPOINT {
+        result.extend(
+            functions
+                .iter()
+                .filter(|f| f.name == entrypoint)
+                .flat_map(|f| f.instructions.iter().filter_map(callee))
+                .map(String::from),
+        );
+    }
+    result
+}
+
+// ASSERT the requires before every call of `name` from outside `harness`
+// and, unless the function checks them itself, ASSUME the ensures after
+// it. Returns the number of calls.
+fn replace(
+    functions: &mut [CBMCFunction],
+    name: &str,
+    contract: &Contract,
+    harness: &[String],
+    checked: bool,
+) -> usize {
+    let mut calls = 0;
+    for function in functions.iter_mut() {
+        if harness.contains(&function.name) {
+            continue;
+        }
+        let mut index = 0;
+        while index < function.instructions.len() {
+            let instruction = &function.instructions[index];
+            if callee(instruction) != Some(name) {
+                index += 1;
+                continue;
+            }
+            calls += 1;
+            let like = instruction.clone();
+            let lhs = Some(&like.code.subt[0]).filter(|lhs| lhs.id != "nil");
+            let arguments = &like.code.subt[2].subt;
+
+            let assertions = contract
+                .requires
+                .iter()
+                .map(|c| guarded(CBMCInstructionKind::Assert, c.apply(None, arguments), &like))
+                .collect();
+            index += insert_before(function, index, assertions) + 1;
+            if checked {
+                continue;
+            }
+
+            let assumptions: Vec<CBMCInstruction> = contract
+                .ensures
+                .iter()
+                .filter(|c| lhs.is_some() || !c.mentions_return_value())
+                .map(|c| guarded(CBMCInstructionKind::Assume, c.apply(lhs, arguments), &like))
+                .collect();
+            let count = assumptions.len();
+            function.instructions.splice(index..index, assumptions);
+            index += count;
+        }
+    }
+    calls
+}
+
+// The parameters of a function type, as symbols
+fn parameters(stype: &Irept) -> Vec<Irept> {
+    match stype.named_subt.get("parameters") {
+        Some(parameters) => parameters
+            .subt
+            .iter()
+            .map(|p| {
+                let name = p
+                    .named_subt
+                    .get("#identifier")
+                    .map(|i| i.id.as_str())
+                    .unwrap_or_default();
+                Irept::symbol(name, p.named_subt.get("type").cloned().unwrap_or_default())
+            })
+            .collect(),
+        None => vec![],
+    }
+}
+
+// ASSERT the invariants before the loop and before every iteration. The
+// decreases clause (a single expression) is kept at the start of every
+// iteration and must be smaller at the end of it.
+fn check_loops(function: &mut CBMCFunction, symbols: &mut Vec<CBMCSymbol>) -> usize {
+    let mut loops = 0;
+    let mut index = 0;
+    while index < function.instructions.len() {
+        let back_edge = &function.instructions[index];
+        let has_clauses = back_edge.instr_type == CBMCInstructionKind::Goto as u32
+            && LOOP_CLAUSES[..2]
+                .iter()
+                .any(|c| back_edge.guard.named_subt.contains_key(*c));
+        let head = back_edge
+            .targets
+            .first()
+            .and_then(|t| t.id.parse::<u32>().ok())
+            .and_then(|t| {
+                function.instructions[..index]
+                    .iter()
+                    .position(|i| i.target_number == t)
+            });
+        let head = match head {
+            Some(head) if has_clauses => head,
+            _ => {
+                index += 1;
+                continue;
+            }
+        };
+        loops += 1;
+
+        let mut guard = back_edge.guard.clone();
+        let invariants = guard
+            .named_subt
+            .remove("#spec_loop_invariant")
+            .map(|i| i.subt)
+            .unwrap_or_default();
+        let decreases = guard
+            .named_subt
+            .remove("#spec_decreases")
+            .map(|d| d.subt)
+            .unwrap_or_default();
+        let like = back_edge.clone();
+        // The invariant must hold whenever the loop goes back to its head
+        let iterating =
+            |condition: Irept| boolean("or", vec![boolean("not", vec![guard.clone()]), condition]);
+
+        let mut before_back_edge: Vec<CBMCInstruction> = invariants
+            .iter()
+            .map(|i| guarded(CBMCInstructionKind::Assert, iterating(i.clone()), &like))
+            .collect();
+        let mut before_loop: Vec<CBMCInstruction> = invariants
+            .iter()
+            .map(|i| guarded(CBMCInstructionKind::Assert, i.clone(), &like))
+            .collect();
+        let mut at_head = vec![];
+        match decreases.as_slice() {
+            [] => (),
+            [measure] => {
+                let name = format!("{}::loop{}::decreases", function.name, loops);
+                let stype = measure.named_subt.get("type").cloned().unwrap_or_default();
+                let old = Irept::symbol(&name, stype.clone());
+                before_loop.push(CBMCInstruction {
+                    code: code("decl", vec![old.clone()], &like),
+                    ..guarded(CBMCInstructionKind::Decl, boolean("true", vec![]), &like)
+                });
+                at_head.push(CBMCInstruction {
+                    code: code("assign", vec![old.clone(), measure.clone()], &like),
+                    ..guarded(CBMCInstructionKind::Assign, boolean("true", vec![]), &like)
+                });
+                before_back_edge.push(guarded(
+                    CBMCInstructionKind::Assert,
+                    iterating(boolean("<", vec![measure.clone(), old])),
+                    &like,
+                ));
+                local(&name, stype, &function.name, symbols);
+            }
+            _ => warn!(
+                "{}: lexicographic decreases clauses are not supported, loop {} is only checked for its invariants",
+                function.name, loops
+            ),
+        }
+        function.instructions[index].guard = guard;
+
+        // Jumps to the back-edge (continue) go through the assertions
+        index += insert_before(function, index, before_back_edge) + 1;
+        // Iterations start at the head, but the loop does not
+        index += insert_before(function, head, at_head);
+        let count = before_loop.len();
+        function.instructions.splice(head..head, before_loop);
+        index += count;
+    }
+    loops
+}
+
+/// Lowers the function and loop contracts of `functions` into assertions
+/// and assumptions, returns how the contract of every function was translated.
+/// The functions called by the harness of `entrypoint` are the ones under
+/// verification.
+pub fn lower_contracts(
+    symbols: &mut Vec<CBMCSymbol>,
+    functions: &mut [CBMCFunction],
+    entrypoint: &str,
+) -> Vec<(String, ContractOption)> {
+    let instrumented = functions
+        .iter()
+        .any(|f| f.name.starts_with(INSTRUMENTED_PREFIX) && !f.instructions.is_empty());
+
+    // Contracts by function
+    let mut contracts = vec![];
+    for symbol in symbols.iter() {
+        if symbol.stype.id != "code"
+            || !FUNCTION_CLAUSES
+                .iter()
+                .any(|c| symbol.stype.named_subt.contains_key(*c))
+        {
+            continue;
+        }
+        let name = symbol
+            .name
+            .strip_prefix("contract::")
+            .unwrap_or(&symbol.name);
+        let contract = Contract {
+            requires: clauses(&symbol.stype, "#spec_requires"),
+            ensures: clauses(&symbol.stype, "#spec_ensures"),
+        };
+        if contract.requires.is_empty() && contract.ensures.is_empty() {
+            continue;
+        }
+        contracts.push((String::from(name), contract));
+    }
+
+    let harness = harness(functions, entrypoint);
+    let mut report = vec![];
+    for (name, contract) in &contracts {
+        let option = if instrumented {
+            ContractOption::Instrumented
+        } else {
+            let function_type = symbols
+                .iter()
+                .find(|s| s.name == *name)
+                .map(|s| s.stype.clone())
+                .unwrap_or_default();
+            match functions
+                .iter()
+                .position(|f| f.name == *name && !f.instructions.is_empty())
+            {
+                Some(function) => {
+                    let parameters = parameters(&function_type);
+                    check(&mut functions[function], contract, &parameters, symbols);
+                    replace(functions, name, contract, &harness, true);
+                    ContractOption::Checked
+                }
+                None if replace(functions, name, contract, &[], false) > 0 => {
+                    ContractOption::Replaced
+                }
+                None => ContractOption::Unused,
+            }
+        };
+        info!("Contract of {}: {}", name, option.description());
+        report.push((name.clone(), option));
+    }
+
+    for function in functions.iter_mut() {
+        let loops = if instrumented {
+            0
+        } else {
+            check_loops(function, symbols)
+        };
+        if loops > 0 {
+            info!(
+                "{}: {} loop contract(s) checked by assertions",
+                function.name, loops
+            );
+        }
+    }
+
+    // ESBMC does not know about the clauses, the ones left behind are
+    // the ones that could not be translated
+    let mut untranslated = vec![];
+    for symbol in symbols.iter_mut() {
+        untranslated.extend(
+            remove_clauses(&mut symbol.stype, &FUNCTION_CLAUSES)
+                .into_iter()
+                .filter(|c| !c.ends_with("requires") && !c.ends_with("ensures"))
+                .map(|c| format!("{} of {}", c, symbol.name)),
+        );
+    }
+    for function in functions.iter_mut() {
+        for instruction in &mut function.instructions {
+            untranslated.extend(
+                remove_clauses(&mut instruction.guard, &LOOP_CLAUSES)
+                    .into_iter()
+                    .map(|c| format!("{} in {}", c, function.name)),
+            );
+        }
+    }
+    if !instrumented {
+        for clause in untranslated {
+            warn!("{} is not supported by ESBMC, it was removed", clause);
+        }
+    }
+    report
+}
+
+///////////
+// TESTS //
+///////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int() -> Irept {
+        Irept::bitvector("signedbv", 32)
+    }
+
+    fn constant(value: &str) -> Irept {
+        Irept::constant(value, int())
+    }
+
+    fn list(id: &str, subt: Vec<Irept>) -> Irept {
+        Irept {
+            id: String::from(id),
+            subt,
+            ..Default::default()
+        }
+    }
+
+    fn lambda(parameters: &[&str], condition: Irept) -> Irept {
+        let parameters = parameters.iter().map(|p| Irept::symbol(p, int())).collect();
+        list("lambda", vec![list("tuple", parameters), condition])
+    }
+
+    fn instruction(kind: CBMCInstructionKind, code: Irept, guard: Irept) -> CBMCInstruction {
+        CBMCInstruction {
+            code,
+            source_location: Irept::default(),
+            instr_type: kind as u32,
+            guard,
+            target_number: NO_TARGET_NUMBER,
+            targets: vec![],
+            labels: vec![],
+            function: Irept::default(),
+        }
+    }
+
+    fn kinds(function: &CBMCFunction) -> Vec<CBMCInstructionKind> {
+        function
+            .instructions
+            .iter()
+            .map(|i| CBMCInstructionKind::from_u32(i.instr_type).unwrap())
+            .collect()
+    }
+
+    // int f(int a) with requires(a < 10), ensures(return == a * 2)
+    fn contract(name: &str, requires: Irept, ensures: Irept) -> Vec<CBMCSymbol> {
+        let mut parameter = Irept::typed("parameter", int());
+        parameter
+            .named_subt
+            .insert(String::from("#identifier"), Irept::from("f::a"));
+        let function_type = Irept::code_type(vec![parameter], int());
+        let mut contract_type = function_type.clone();
+        for (clause, lambdas) in [
+            ("#spec_requires", vec![requires]),
+            ("#spec_ensures", vec![ensures]),
+            ("#spec_assigns", vec![]),
+        ] {
+            contract_type
+                .named_subt
+                .insert(String::from(clause), list("", lambdas));
+        }
+        vec![
+            CBMCSymbol {
+                name: String::from(name),
+                stype: function_type,
+                ..Default::default()
+            },
+            CBMCSymbol {
+                name: format!("contract::{}", name),
+                stype: contract_type,
+                is_property: true,
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn clauses() -> (Irept, Irept) {
+        let requires = lambda(
+            &["ret", "l::a"],
+            boolean("<", vec![Irept::symbol("l::a", int()), constant("10")]),
+        );
+        let product = Irept::typed("*", int());
+        let product = Irept {
+            subt: vec![Irept::symbol("l::a", int()), constant("2")],
+            ..product
+        };
+        let ensures = lambda(
+            &["ret", "l::a"],
+            boolean("=", vec![Irept::symbol("ret", int()), product]),
+        );
+        (requires, ensures)
+    }
+
+    #[test]
+    fn check_contract() {
+        let (requires, ensures) = clauses();
+        let mut symbols = contract("f", requires, ensures);
+        let mut ret = instruction(
+            CBMCInstructionKind::SetReturnValue,
+            list("code", vec![constant("4")]),
+            boolean("true", vec![]),
+        );
+        ret.target_number = 1;
+        let end = instruction(
+            CBMCInstructionKind::EndFunction,
+            Irept::from("nil"),
+            boolean("true", vec![]),
+        );
+        let mut functions = vec![CBMCFunction {
+            name: String::from("f"),
+            instructions: vec![ret, end],
+        }];
+
+        let report = lower_contracts(&mut symbols, &mut functions, "main");
+        assert_eq!(report, [(String::from("f"), ContractOption::Checked)]);
+        use CBMCInstructionKind::*;
+        let f = &functions[0];
+        assert_eq!(
+            kinds(f),
+            [Assume, Decl, Assign, Assert, SetReturnValue, EndFunction]
+        );
+        // The requires are about the parameter, the ensures about its old
+        // value and the returned one
+        assert_eq!(identifier(&f.instructions[0].guard.subt[0]), "f::a");
+        let ensures = &f.instructions[3].guard;
+        assert_eq!(ensures.subt[0], constant("4"));
+        assert_eq!(identifier(&ensures.subt[1].subt[0]), "f::contract::f::a");
+        // Returning goes through the check
+        assert_eq!(f.instructions[3].target_number, 1);
+        assert_eq!(f.instructions[4].target_number, NO_TARGET_NUMBER);
+        assert!(symbols.iter().any(|s| s.name == "f::contract::f::a"));
+        // The clauses are gone
+        for symbol in &symbols {
+            assert!(FUNCTION_CLAUSES
+                .iter()
+                .all(|c| !symbol.stype.named_subt.contains_key(*c)));
+        }
+    }
+
+    #[test]
+    fn check_contract_callers() {
+        let (requires, ensures) = clauses();
+        let mut symbols = contract("f", requires, ensures);
+        let call = |caller: &str, argument: &str| {
+            let code = list(
+                "code",
+                vec![
+                    Irept::symbol(&format!("{}::x", caller), int()),
+                    Irept::symbol("f", Irept::from("code")),
+                    list("arguments", vec![constant(argument)]),
+                ],
+            );
+            instruction(
+                CBMCInstructionKind::FunctionCall,
+                code,
+                boolean("true", vec![]),
+            )
+        };
+        let end = instruction(
+            CBMCInstructionKind::EndFunction,
+            Irept::from("nil"),
+            boolean("true", vec![]),
+        );
+        let g = list(
+            "code",
+            vec![
+                Irept::from("nil"),
+                Irept::symbol("g", Irept::from("code")),
+                list("arguments", vec![]),
+            ],
+        );
+        let mut functions = vec![
+            CBMCFunction {
+                name: String::from("f"),
+                instructions: vec![end.clone()],
+            },
+            // g violates the requires of f: it must be caught at the call
+            CBMCFunction {
+                name: String::from("g"),
+                instructions: vec![call("g", "20"), end.clone()],
+            },
+            CBMCFunction {
+                name: String::from("main"),
+                instructions: vec![
+                    call("main", "5"),
+                    instruction(
+                        CBMCInstructionKind::FunctionCall,
+                        g,
+                        boolean("true", vec![]),
+                    ),
+                    end,
+                ],
+            },
+        ];
+
+        let report = lower_contracts(&mut symbols, &mut functions, "main");
+        assert_eq!(report, [(String::from("f"), ContractOption::Checked)]);
+        use CBMCInstructionKind::*;
+        let g = &functions[1];
+        assert_eq!(kinds(g), [Assert, FunctionCall, EndFunction]);
+        assert_eq!(g.instructions[0].guard.subt[0], constant("20"));
+        // The harness is the one calling f without meeting its requires
+        assert_eq!(
+            kinds(&functions[2]),
+            [FunctionCall, FunctionCall, EndFunction]
+        );
+    }
+
+    #[test]
+    fn replace_contract() {
+        let (requires, ensures) = clauses();
+        let mut symbols = contract("f", requires, ensures);
+        let arguments = list("arguments", vec![constant("5")]);
+        let call = list(
+            "code",
+            vec![
+                Irept::symbol("main::x", int()),
+                Irept::symbol("f", Irept::from("code")),
+                arguments,
+            ],
+        );
+        let mut functions = vec![
+            CBMCFunction {
+                name: String::from("f"),
+                instructions: vec![],
+            },
+            CBMCFunction {
+                name: String::from("main"),
+                instructions: vec![instruction(
+                    CBMCInstructionKind::FunctionCall,
+                    call,
+                    boolean("true", vec![]),
+                )],
+            },
+        ];
+
+        let report = lower_contracts(&mut symbols, &mut functions, "main");
+        assert_eq!(report, [(String::from("f"), ContractOption::Replaced)]);
+        use CBMCInstructionKind::*;
+        let main = &functions[1];
+        assert_eq!(kinds(main), [Assert, FunctionCall, Assume]);
+        assert_eq!(main.instructions[0].guard.subt[0], constant("5"));
+        let ensures = &main.instructions[2].guard;
+        assert_eq!(identifier(&ensures.subt[0]), "main::x");
+        assert_eq!(ensures.subt[1].subt[0], constant("5"));
+    }
+
+    #[test]
+    fn loop_contract() {
+        let i = Irept::symbol("main::i", int());
+        let mut guard = boolean("<", vec![i.clone(), constant("10")]);
+        let invariant = boolean("<=", vec![i.clone(), constant("10")]);
+        let measure = Irept::typed("-", int());
+        let measure = Irept {
+            subt: vec![constant("10"), i.clone()],
+            ..measure
+        };
+        guard.named_subt.insert(
+            String::from("#spec_loop_invariant"),
+            list("", vec![invariant.clone()]),
+        );
+        guard.named_subt.insert(
+            String::from("#spec_decreases"),
+            list("", vec![measure.clone()]),
+        );
+        let mut head = instruction(
+            CBMCInstructionKind::Skip,
+            Irept::from("nil"),
+            boolean("true", vec![]),
+        );
+        head.target_number = 1;
+        let mut back_edge = instruction(CBMCInstructionKind::Goto, Irept::from("nil"), guard);
+        back_edge.targets = vec![Irept::from("1")];
+        let end = instruction(
+            CBMCInstructionKind::EndFunction,
+            Irept::from("nil"),
+            boolean("true", vec![]),
+        );
+        let mut symbols = vec![];
+        let mut functions = vec![CBMCFunction {
+            name: String::from("main"),
+            instructions: vec![head, back_edge, end],
+        }];
+
+        assert!(lower_contracts(&mut symbols, &mut functions, "main").is_empty());
+        use CBMCInstructionKind::*;
+        let main = &functions[0];
+        assert_eq!(
+            kinds(main),
+            [
+                Assert,
+                Decl,
+                Assign,
+                Skip,
+                Assert,
+                Assert,
+                Goto,
+                EndFunction
+            ]
+        );
+        // Iterations remember the measure, entering the loop does not
+        assert_eq!(main.instructions[0].guard, invariant);
+        assert_eq!(main.instructions[2].target_number, 1);
+        assert_eq!(main.instructions[3].target_number, NO_TARGET_NUMBER);
+        assert_eq!(main.instructions[2].code.subt[1], measure);
+        // Before going back, the invariant holds and the measure decreased
+        assert_eq!(main.instructions[4].guard.id, "or");
+        assert_eq!(main.instructions[4].guard.subt[1], invariant);
+        assert_eq!(main.instructions[5].guard.subt[1].id, "<");
+        assert!(!main.instructions[6]
+            .guard
+            .named_subt
+            .keys()
+            .any(|k| k.starts_with("#spec_")));
+        assert_eq!(symbols.len(), 1);
+    }
+
+    #[test]
+    fn instrumented_contract() {
+        // goto-instrument --enforce-contract mul
+        let mut result =
+            crate::cbmc::process_cbmc_file(&crate::resources::get_test_path("mul_contract.goto"))
+                .unwrap();
+        let before = result.functions_irep.clone();
+        let report = lower_contracts(
+            &mut result.symbols_irep,
+            &mut result.functions_irep,
+            &result.entrypoint,
+        );
+        assert!(report.iter().any(|(name, _)| name == "mul"));
+        assert!(report
+            .iter()
+            .all(|(_, option)| *option == ContractOption::Instrumented));
+        // Nothing is added
+        for (function, original) in result.functions_irep.iter().zip(&before) {
+            assert_eq!(function.instructions.len(), original.instructions.len());
+        }
+        for symbol in &result.symbols_irep {
+            assert!(!FUNCTION_CLAUSES
+                .iter()
+                .any(|c| symbol.stype.named_subt.contains_key(*c)));
+        }
+    }
+}
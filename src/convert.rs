@@ -0,0 +1,151 @@
+// This module is responsible to convert a goto program into a given format,
+// detecting the format of the input by itself.
+
+use crate::bytereader::{ByteReader, GotoFormat};
+use crate::bytewriter::ByteWriter;
+use crate::cbmc::CBMCLayout;
+use crate::jsongoto::{read_json_file, write_json};
+use crate::rules::RuleSet;
+use crate::{cbmc2esbmc, cbmc2json, esbmc2cbmc, esbmc2json, json2cbmc, json2esbmc};
+use json::JsonValue;
+use log::{debug, trace};
+
+pub fn convert(
+    entrypoint: Option<&str>,
+    input: &str,
+    output: &str,
+    to: GotoFormat,
+    layout: CBMCLayout,
+    rules: &RuleSet,
+) -> Result<(), String> {
+    trace!("convert mode, {} {}", input, output);
+    let from = ByteReader::read_file(input)
+        .and_then(|reader| reader.detect_format())
+        .map_err(|e| format!("Could not detect the format of {}: {}", input, e))?;
+    debug!("Converting {} into {}", from, to);
+
+    match (from, to) {
+        (GotoFormat::Cbmc, GotoFormat::Cbmc) => {
+            // Useful to change the goto binary version
+            let result = crate::cbmc::process_cbmc_file(input).map_err(|e| e.to_string())?;
+            std::fs::remove_file(output).ok();
+            ByteWriter::write_cbmc_to_file(
+                result.symbols_irep,
+                result.functions_irep,
+                output,
+                layout,
+            )
+            .map_err(|e| format!("Could not write file {}: {}", output, e))
+        }
+        (GotoFormat::Cbmc, GotoFormat::Esbmc) => cbmc2esbmc(entrypoint, input, output, rules),
+        (GotoFormat::Cbmc, GotoFormat::Json) => cbmc2json(input, output),
+        (GotoFormat::Esbmc, GotoFormat::Cbmc) => esbmc2cbmc(input, output, layout, rules),
+        (GotoFormat::Esbmc, GotoFormat::Esbmc) => {
+            let result = crate::esbmc::process_esbmc_file(input).map_err(|e| e.to_string())?;
+            std::fs::remove_file(output).ok();
+            ByteWriter::write_to_file(result.symbols_irep, result.functions_irep, output)
+                .map_err(|e| format!("Could not write file {}: {}", output, e))
+        }
+        (GotoFormat::Esbmc, GotoFormat::Json) => esbmc2json(input, output),
+        (GotoFormat::Json, GotoFormat::Cbmc) => json2cbmc(input, output, layout, rules),
+        (GotoFormat::Json, GotoFormat::Esbmc) => json2esbmc(entrypoint, input, output, rules),
+        (GotoFormat::Json, GotoFormat::Json) => {
+            // Validates and normalizes the dump
+            let program = read_json_file(input)?;
+            write_json(&JsonValue::from(&program), output)
+        }
+    }
+}
+
+///////////
+// TESTS //
+///////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::get_test_path;
+
+    fn detect(path: &str) -> GotoFormat {
+        ByteReader::read_file(path)
+            .unwrap()
+            .detect_format()
+            .unwrap()
+    }
+
+    #[test]
+    fn convert_chain() {
+        let layout = CBMCLayout::default();
+        let rules = RuleSet::default();
+        let entrypoint = None;
+        let json_path = "/tmp/test_convert_chain.json";
+        let esbmc_path = "/tmp/test_convert_chain_esbmc.goto";
+        let cbmc_path = "/tmp/test_convert_chain_cbmc.goto";
+
+        let cbmc = get_test_path("hello-gb.goto");
+        convert(
+            entrypoint,
+            &cbmc,
+            json_path,
+            GotoFormat::Json,
+            layout,
+            &rules,
+        )
+        .unwrap();
+        assert_eq!(detect(json_path), GotoFormat::Json);
+
+        convert(
+            entrypoint,
+            json_path,
+            esbmc_path,
+            GotoFormat::Esbmc,
+            layout,
+            &rules,
+        )
+        .unwrap();
+        assert_eq!(detect(esbmc_path), GotoFormat::Esbmc);
+
+        convert(
+            entrypoint,
+            esbmc_path,
+            cbmc_path,
+            GotoFormat::Cbmc,
+            layout,
+            &rules,
+        )
+        .unwrap();
+        assert_eq!(detect(cbmc_path), GotoFormat::Cbmc);
+        let result = crate::cbmc::process_cbmc_file(cbmc_path).unwrap();
+        assert!(result.functions_irep.iter().any(|f| f.name == "main"));
+
+        for path in [json_path, esbmc_path, cbmc_path] {
+            std::fs::remove_file(path).ok();
+        }
+    }
+
+    #[test]
+    fn convert_same_format() {
+        let output = "/tmp/test_convert_same_format.goto";
+        let layout = CBMCLayout::from_version(6).unwrap();
+        let rules = RuleSet::default();
+        let cbmc = get_test_path("hello-gb.goto");
+        convert(None, &cbmc, output, GotoFormat::Cbmc, layout, &rules).unwrap();
+        let bytes = std::fs::read(output).unwrap();
+        std::fs::remove_file(output).ok();
+        assert_eq!(bytes[4], 6);
+    }
+
+    #[test]
+    fn convert_unknown_format() {
+        let err = convert(
+            None,
+            &get_test_path("hello_add.c"),
+            "/tmp/test_convert_unknown_format.goto",
+            GotoFormat::Cbmc,
+            CBMCLayout::default(),
+            &RuleSet::default(),
+        )
+        .unwrap_err();
+        assert!(err.starts_with("Could not detect the format"));
+    }
+}
@@ -1,5 +1,4 @@
-use crate::bytereader::ByteReader;
-use crate::ByteWriter;
+use crate::bytereader::{ByteReader, GbfError};
 pub use crate::Irept;
 
 #[derive(Clone, Debug)]
@@ -9,39 +8,116 @@ pub struct ESBMCParseResult {
     pub functions_irep: Vec<(String, Irept)>,
 }
 
-pub fn process_esbmc_file(path: &str) -> Result<ESBMCParseResult, String> {
+// Kinds of instruction (goto_program_instruction_typet). ESBMC has no
+// thread instructions (6 and 7) and instructions keep it as the "typeid".
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ESBMCInstructionKind {
+    NoInstructionType = 0,
+    Goto = 1,
+    Assume = 2,
+    Assert = 3,
+    Other = 4,
+    Skip = 5,
+    Location = 8,
+    EndFunction = 9,
+    AtomicBegin = 10,
+    AtomicEnd = 11,
+    Return = 12,
+    Assign = 13,
+    Decl = 14,
+    Dead = 15,
+    FunctionCall = 16,
+    Throw = 17,
+    Catch = 18,
+    ThrowDecl = 19,
+    ThrowDeclEnd = 20,
+}
+
+impl ESBMCInstructionKind {
+    const KINDS: [(ESBMCInstructionKind, &'static str); 19] = [
+        (Self::NoInstructionType, "NO_INSTRUCTION_TYPE"),
+        (Self::Goto, "GOTO"),
+        (Self::Assume, "ASSUME"),
+        (Self::Assert, "ASSERT"),
+        (Self::Other, "OTHER"),
+        (Self::Skip, "SKIP"),
+        (Self::Location, "LOCATION"),
+        (Self::EndFunction, "END_FUNCTION"),
+        (Self::AtomicBegin, "ATOMIC_BEGIN"),
+        (Self::AtomicEnd, "ATOMIC_END"),
+        (Self::Return, "RETURN"),
+        (Self::Assign, "ASSIGN"),
+        (Self::Decl, "DECL"),
+        (Self::Dead, "DEAD"),
+        (Self::FunctionCall, "FUNCTION_CALL"),
+        (Self::Throw, "THROW"),
+        (Self::Catch, "CATCH"),
+        (Self::ThrowDecl, "THROW_DECL"),
+        (Self::ThrowDeclEnd, "THROW_DECL_END"),
+    ];
+
+    pub fn from_u32(value: u32) -> Option<Self> {
+        Self::KINDS
+            .iter()
+            .find(|(kind, _)| *kind as u32 == value)
+            .map(|(kind, _)| *kind)
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::KINDS
+            .iter()
+            .find(|(kind, _)| *kind == self)
+            .unwrap()
+            .1
+    }
+}
+
+pub fn process_esbmc_file(path: &str) -> Result<ESBMCParseResult, GbfError> {
     let mut result = ESBMCParseResult {
-        reader: ByteReader::read_file(path),
+        reader: ByteReader::read_file(path)?,
         functions_irep: Vec::new(),
         symbols_irep: Vec::new(),
     };
 
-    result.reader.check_esbmc_header().unwrap();
-    result.reader.check_esbmc_version().unwrap();
+    result.reader.check_esbmc_header()?;
+    result.reader.check_esbmc_version()?;
 
     // Symbol table
-    let number_of_symbols = result.reader.read_esbmc_word();
+    let number_of_symbols = result.reader.read_esbmc_word()?;
     for _ in 0..number_of_symbols {
-        let symbol = result.reader.read_esbmc_reference();
+        let symbol = result.reader.read_esbmc_reference()?;
         result.symbols_irep.push(symbol.clone());
     }
 
     // Functions
-    let number_of_functions = result.reader.read_esbmc_word();
+    let number_of_functions = result.reader.read_esbmc_word()?;
     for _ in 0..number_of_functions {
-        let foo = (
-            result.reader.read_esbmc_string(),
-            result.reader.read_esbmc_reference(),
+        let (name, irep) = (
+            result.reader.read_esbmc_string()?,
+            result.reader.read_esbmc_reference()?,
         );
-        result.functions_irep.push(foo.clone());
+
+        // Targets must point to an instruction of the same function
+        for instr in &irep.subt {
+            if let Some(targets) = instr.named_subt.get("targets") {
+                for t in &targets.subt {
+                    let offset: u32 = t.id.parse().unwrap_or(u32::MAX);
+                    if offset as usize >= irep.subt.len() {
+                        return Err(result.reader.unknown_reference_error(offset, &name));
+                    }
+                }
+            }
+        }
+        result.functions_irep.push((name, irep));
     }
 
-    return Ok(result);
+    Ok(result)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ByteWriter;
 
     #[test]
     fn test_file() {
@@ -55,6 +131,19 @@ mod tests {
         let result = process_esbmc_file(test_path.to_str().unwrap()).unwrap();
 
         std::fs::remove_file("/tmp/test.goto").ok();
-        ByteWriter::write_to_file(result.symbols_irep, result.functions_irep, "/tmp/test.goto");
+        ByteWriter::write_to_file(
+            result.symbols_irep.clone(),
+            result.functions_irep.clone(),
+            "/tmp/test.goto",
+        )
+        .unwrap();
+
+        // Unwritable outputs are reported
+        assert!(ByteWriter::write_to_file(
+            result.symbols_irep,
+            result.functions_irep,
+            "/nonexistent/test.goto"
+        )
+        .is_err());
     }
 }
@@ -0,0 +1,934 @@
+// This module translates the heap model of CBMC into the one of ESBMC and
+// back. Both directions work on the CBMC instructions.
+//
+// CBMC                                 ESBMC
+// side_effect allocate(size, zero)     sideeffect malloc(size)
+// free(p)                              OTHER free(p)
+// is_dynamic_object(p)                 __ESBMC_is_dynamic[pointer_object(p)]
+// object_size(p)                       __ESBMC_get_object_size(p)
+//                                      __ESBMC_alloc_size[pointer_object(p)]
+//
+// pointer_object and pointer_offset are the same for both tools.
+// __ESBMC_alloc_size only has the size of dynamic objects, object_size(p)
+// is read into a local by a call to __ESBMC_get_object_size(p) before its
+// instruction instead. The size of a variable (object_size(&x)) is known
+// upfront, it becomes a constant.
+//
+// CBMC tracks its objects through __CPROVER_deallocated,
+// __CPROVER_dead_object, __CPROVER_memory_leak and __CPROVER_new_object,
+// which ESBMC checks with its own arrays (__ESBMC_alloc, __ESBMC_is_dynamic
+// and __ESBMC_alloc_size, added when needed). The programs that read them
+// outside of the replaced malloc and free are not supported.
+
+use std::collections::HashMap;
+
+use crate::cbmc::{
+    CBMCFunction, CBMCInstruction, CBMCInstructionKind, CBMCSymbol, NO_TARGET_NUMBER,
+};
+use crate::irep::Irept;
+
+const ALLOC: &str = "c:@__ESBMC_alloc";
+const IS_DYNAMIC: &str = "c:@__ESBMC_is_dynamic";
+const ALLOC_SIZE: &str = "c:@__ESBMC_alloc_size";
+const GET_OBJECT_SIZE: &str = "c:@F@__ESBMC_get_object_size";
+
+// Globals of the CBMC model, replaced by the arrays of ESBMC
+const CBMC_OBJECTS: [&str; 4] = [
+    "__CPROVER_deallocated",
+    "__CPROVER_dead_object",
+    "__CPROVER_memory_leak",
+    "__CPROVER_new_object",
+];
+
+const ASSIGN: u32 = CBMCInstructionKind::Assign as u32;
+const DECL: u32 = CBMCInstructionKind::Decl as u32;
+const OTHER: u32 = CBMCInstructionKind::Other as u32;
+const FUNCTION_CALL: u32 = CBMCInstructionKind::FunctionCall as u32;
+
+fn statement(irep: &Irept) -> Option<&str> {
+    irep.named_subt.get("statement").map(|s| s.id.as_str())
+}
+
+fn expression_type(irep: &Irept) -> Result<Irept, String> {
+    irep.named_subt
+        .get("type")
+        .cloned()
+        .ok_or_else(|| format!("{} without type", irep.id))
+}
+
+fn operand(irep: &Irept, index: usize) -> Result<Irept, String> {
+    irep.subt
+        .get(index)
+        .cloned()
+        .ok_or_else(|| format!("{} without operand {}", irep.id, index))
+}
+
+// Identifier of the function called by `instruction`
+fn callee(instruction: &CBMCInstruction) -> Option<&str> {
+    if instruction.instr_type != FUNCTION_CALL || instruction.code.subt.len() != 3 {
+        return None;
+    }
+    let function = &instruction.code.subt[1];
+    match function.named_subt.get("identifier") {
+        Some(identifier) if function.id == "symbol" => Some(identifier.id.as_str()),
+        _ => None,
+    }
+}
+
+// Arguments of a call to `instruction`, when it is a call
+fn arguments(instruction: &CBMCInstruction) -> &[Irept] {
+    match instruction.code.subt.get(2) {
+        Some(arguments) => &arguments.subt,
+        None => &[],
+    }
+}
+
+// The global of the CBMC model read by `irep`, if any
+fn cbmc_object(irep: &Irept) -> Option<&'static str> {
+    if irep.id == "symbol" {
+        if let Some(identifier) = irep.named_subt.get("identifier") {
+            if let Some(name) = CBMC_OBJECTS.iter().find(|n| **n == identifier.id) {
+                return Some(name);
+            }
+        }
+    }
+    irep.subt
+        .iter()
+        .chain(irep.named_subt.values())
+        .find_map(cbmc_object)
+}
+
+// Size in bits of the objects of type `stype`, if it is known before
+// running the program
+fn type_width(stype: &Irept, tags: &HashMap<String, Irept>) -> Option<u64> {
+    let width = |irep: &Irept| irep.named_subt.get("width")?.id.parse().ok();
+    let components = |irep: &Irept| -> Option<Vec<u64>> {
+        irep.named_subt
+            .get("components")?
+            .subt
+            .iter()
+            .map(|c| type_width(c.named_subt.get("type")?, tags))
+            .collect()
+    };
+    match stype.id.as_str() {
+        "signedbv" | "unsignedbv" | "floatbv" | "fixedbv" | "bv" | "c_bool" | "c_enum"
+        | "c_bit_field" | "pointer" => width(stype),
+        "array" => {
+            let size = stype.named_subt.get("size")?;
+            if size.id != "constant" {
+                return None;
+            }
+            let count = u64::from_str_radix(&size.named_subt.get("value")?.id, 16).ok()?;
+            let element = stype.subt.first().or(stype.named_subt.get("subtype"))?;
+            Some(count * type_width(element, tags)?)
+        }
+        "struct" => Some(components(stype)?.iter().sum()),
+        "union" => Some(components(stype)?.into_iter().max().unwrap_or(0)),
+        "struct_tag" | "union_tag" | "c_enum_tag" => {
+            type_width(tags.get(&stype.named_subt.get("identifier")?.id)?, tags)
+        }
+        _ => None,
+    }
+}
+
+// Size of the object pointed by `pointer` when it is the address of a
+// variable (or of a part of it), which is never dynamic
+fn variable_size(pointer: &Irept, tags: &HashMap<String, Irept>) -> Option<u64> {
+    let mut object = pointer;
+    while object.id == "typecast" {
+        object = object.subt.first()?;
+    }
+    if object.id != "address_of" {
+        return None;
+    }
+    object = object.subt.first()?;
+    while ["index", "member"].contains(&object.id.as_str()) {
+        object = object.subt.first()?;
+    }
+    if object.id != "symbol" {
+        return None;
+    }
+    let width = type_width(object.named_subt.get("type")?, tags)?;
+    (width % 8 == 0).then_some(width / 8)
+}
+
+// The ESBMC arrays indexed by object: __ESBMC_alloc_size[pointer_object(p)]
+struct ObjectArrays {
+    size_t: Irept,
+}
+
+impl ObjectArrays {
+    fn array(&self, element: Irept) -> Irept {
+        let mut result = Irept::from("array");
+        result.subt.push(element);
+        result.named_subt.insert(
+            String::from("size"),
+            Irept::typed("infinity", self.size_t.clone()),
+        );
+        result
+    }
+
+    fn element(&self, name: &str) -> Irept {
+        match name {
+            ALLOC_SIZE => self.size_t.clone(),
+            _ => Irept::from("bool"),
+        }
+    }
+
+    fn index(&self, name: &str, pointer: Irept) -> Irept {
+        let element = self.element(name);
+        let mut object = Irept::typed("pointer_object", self.size_t.clone());
+        object.subt.push(pointer);
+        let mut result = Irept::typed("index", element.clone());
+        result.subt = vec![Irept::symbol(name, self.array(element)), object];
+        result
+    }
+
+    // Globals with every object set to false (or 0)
+    fn symbols(&self) -> Vec<CBMCSymbol> {
+        [ALLOC, IS_DYNAMIC, ALLOC_SIZE]
+            .iter()
+            .map(|name| {
+                let element = self.element(name);
+                let zero = match element.id.as_str() {
+                    "bool" => Irept::constant("false", element.clone()),
+                    _ => Irept::constant("0", element.clone()),
+                };
+                let stype = self.array(element);
+                let mut value = Irept::typed("array_of", stype.clone());
+                value.subt.push(zero);
+                let mut result = CBMCSymbol {
+                    stype,
+                    value,
+                    name: String::from(*name),
+                    module: String::from("esbmc_intrinsics"),
+                    base_name: String::from(name.strip_prefix("c:@").unwrap()),
+                    mode: String::from("C"),
+                    pretty_name: String::from(name.strip_prefix("c:@").unwrap()),
+                    is_lvalue: true,
+                    is_static_lifetime: true,
+                    ..Default::default()
+                };
+                result.flags = result.pack_flags();
+                result
+            })
+            .collect()
+    }
+}
+
+// ESBMC allocates `size` chars, as its frontend does for a malloc(size)
+fn malloc(size: Irept, pointer: Irept) -> Irept {
+    let mut result = Irept::typed("side_effect", pointer);
+    result
+        .named_subt
+        .insert(String::from("statement"), Irept::from("malloc"));
+    result.subt.push(size.clone());
+    result.comments.insert(String::from("#size"), size);
+    result
+        .comments
+        .insert(String::from("#type"), Irept::bitvector("unsignedbv", 8));
+    result
+}
+
+// Whether the allocation `irep` is not zero-initialized
+fn is_nondet(allocate: &Irept) -> bool {
+    allocate.subt.get(1).is_some_and(|zero| {
+        zero.id == "constant"
+            && zero
+                .named_subt
+                .get("value")
+                .is_some_and(|v| v.id == "false")
+    })
+}
+
+// Sets the chars allocated by `lhs = malloc(size)` to 0, if `zero` holds:
+// *(char (*)[size])lhs = zero ? array_of(0) : *(char (*)[size])lhs
+fn zeroing(lhs: Irept, size: Irept, zero: Irept, pointer_width: u64) -> Irept {
+    let char_type = Irept::bitvector("unsignedbv", 8);
+    let mut chars = Irept::from("array");
+    chars.subt.push(char_type.clone());
+    chars.named_subt.insert(String::from("size"), size);
+    let mut cast = Irept::typed("typecast", Irept::pointer(chars.clone(), pointer_width));
+    cast.subt.push(lhs);
+    let mut object = Irept::typed("dereference", chars.clone());
+    object.subt.push(cast);
+    let mut value = Irept::typed("array_of", chars.clone());
+    value.subt.push(Irept::constant("0", char_type));
+    if zero.id != "constant" {
+        let mut conditional = Irept::typed("if", chars);
+        conditional.subt = vec![zero, value, object.clone()];
+        value = conditional;
+    }
+
+    Irept::code("assign", vec![object, value])
+}
+
+// The object_size(p) read by the instructions of `function`, each of them
+// is a local set by `local = __ESBMC_get_object_size(p)`
+struct ObjectSizes<'a> {
+    function: &'a str,
+    reads: Vec<(Irept, Irept)>,
+}
+
+impl ObjectSizes<'_> {
+    fn read(&mut self, pointer: Irept, size_t: &Irept) -> Irept {
+        let name = format!("{}::object_size::{}", self.function, self.reads.len());
+        let local = Irept::symbol(&name, size_t.clone());
+        self.reads.push((local.clone(), pointer));
+        local
+    }
+}
+
+// size_t __ESBMC_get_object_size(const void *)
+fn get_object_size_type(size_t: &Irept, pointer_width: u64) -> Irept {
+    let pointer = Irept::pointer(Irept::from("empty"), pointer_width);
+    Irept::code_type(vec![Irept::typed("parameter", pointer)], size_t.clone())
+}
+
+// Rewrites the expressions of CBMC within `irep`, returns whether the
+// arrays of ESBMC are needed. `sizes` is given for the expressions of an
+// instruction, symbol values are never run and read __ESBMC_alloc_size.
+fn esbmc_expression(
+    irep: &mut Irept,
+    arrays: &ObjectArrays,
+    tags: &HashMap<String, Irept>,
+    mut sizes: Option<&mut ObjectSizes>,
+) -> Result<bool, String> {
+    let mut found = false;
+    for sub in irep.subt.iter_mut().chain(irep.named_subt.values_mut()) {
+        found |= esbmc_expression(sub, arrays, tags, sizes.as_deref_mut())?;
+    }
+
+    match irep.id.as_str() {
+        "side_effect" if statement(irep) == Some("allocate") => {
+            // The contents of ESBMC allocations are always nondet, the
+            // zero-initialized ones are set by the next assignment
+            if sizes.is_some() && !is_nondet(irep) {
+                return Err(String::from(
+                    "zero-initialized allocations are only supported as the right-hand side of an assignment",
+                ));
+            }
+            *irep = malloc(operand(irep, 0)?, expression_type(irep)?);
+        }
+        "is_dynamic_object" => *irep = arrays.index(IS_DYNAMIC, operand(irep, 0)?),
+        "object_size" => {
+            let stype = expression_type(irep)?;
+            let pointer = operand(irep, 0)?;
+            if let Some(size) = variable_size(&pointer, tags) {
+                *irep = Irept::constant(&format!("{:X}", size), stype);
+                return Ok(found);
+            }
+            let (mut size, arrays_read) = match sizes {
+                Some(sizes) => (sizes.read(pointer, &arrays.size_t), false),
+                None => (arrays.index(ALLOC_SIZE, pointer), true),
+            };
+            if stype != arrays.size_t {
+                size = {
+                    let mut cast = Irept::typed("typecast", stype);
+                    cast.subt.push(size);
+                    cast
+                };
+            }
+            *irep = size;
+            return Ok(found || arrays_read);
+        }
+        _ => return Ok(found),
+    }
+    Ok(true)
+}
+
+// Rewrites the heap of CBMC within `instructions`, returns whether the
+// arrays of ESBMC are needed
+fn esbmc_instructions(
+    instructions: &mut Vec<CBMCInstruction>,
+    arrays: &ObjectArrays,
+    tags: &HashMap<String, Irept>,
+    sizes: &mut ObjectSizes,
+    pointer_width: u64,
+) -> Result<bool, String> {
+    let mut found = false;
+    let mut index = 0;
+    while index < instructions.len() {
+        let instruction = &mut instructions[index];
+        match callee(instruction) {
+            Some("malloc") if arguments(instruction).len() == 1 => {
+                let lhs = operand(&instruction.code, 0)?;
+                if lhs.id != "nil" {
+                    let pointer = expression_type(&lhs)?;
+                    let size = operand(&instruction.code.subt[2], 0)?;
+                    instruction.instr_type = ASSIGN;
+                    instruction
+                        .code
+                        .named_subt
+                        .insert(String::from("statement"), Irept::from("assign"));
+                    instruction.code.subt = vec![lhs, malloc(size, pointer)];
+                    found = true;
+                }
+            }
+            Some("free") if arguments(instruction).len() == 1 => {
+                let pointer = operand(&instruction.code.subt[2], 0)?;
+                instruction.instr_type = OTHER;
+                instruction
+                    .code
+                    .named_subt
+                    .insert(String::from("statement"), Irept::from("free"));
+                instruction.code.subt = vec![pointer];
+                found = true;
+            }
+            _ => (),
+        }
+
+        // lhs = side_effect allocate(size, zero) is followed by the zeroing
+        let mut zeroed = None;
+        if instruction.instr_type == ASSIGN {
+            if let Some(allocate) = instruction.code.subt.get_mut(1) {
+                if statement(allocate) == Some("allocate") && !is_nondet(allocate) {
+                    let zero = operand(allocate, 1)?;
+                    allocate.subt[1] = Irept::boolean(false);
+                    let size = operand(allocate, 0)?;
+                    let lhs = operand(&instruction.code, 0)?;
+                    zeroed = Some(zeroing(lhs, size, zero, pointer_width));
+                }
+            }
+        }
+
+        let first = sizes.reads.len();
+        found |= esbmc_expression(&mut instruction.code, arrays, tags, Some(sizes))?;
+        found |= esbmc_expression(&mut instruction.guard, arrays, tags, Some(sizes))?;
+
+        let zeroing = zeroed.map(|code| CBMCInstruction {
+            code,
+            target_number: NO_TARGET_NUMBER,
+            labels: vec![],
+            ..instruction.clone()
+        });
+
+        // Jumps into the instruction go through the reads of its sizes
+        let like = CBMCInstruction {
+            code: Irept::from("nil"),
+            instr_type: DECL,
+            guard: Irept::boolean(true),
+            target_number: NO_TARGET_NUMBER,
+            targets: vec![],
+            labels: vec![],
+            ..instruction.clone()
+        };
+        let mut reads = vec![];
+        for (local, pointer) in &sizes.reads[first..] {
+            let arguments = Irept {
+                id: String::from("arguments"),
+                subt: vec![pointer.clone()],
+                ..Default::default()
+            };
+            let function = Irept::symbol(
+                GET_OBJECT_SIZE,
+                get_object_size_type(&arrays.size_t, pointer_width),
+            );
+            reads.push(CBMCInstruction {
+                code: Irept::code("decl", vec![local.clone()]),
+                ..like.clone()
+            });
+            reads.push(CBMCInstruction {
+                code: Irept::code("function_call", vec![local.clone(), function, arguments]),
+                instr_type: FUNCTION_CALL,
+                ..like.clone()
+            });
+        }
+        if let Some(first) = reads.first_mut() {
+            first.target_number = instruction.target_number;
+            first.labels = std::mem::take(&mut instruction.labels);
+            instruction.target_number = NO_TARGET_NUMBER;
+        }
+        let count = reads.len();
+        instructions.splice(index..index, reads);
+        index += count + 1;
+
+        if let Some(zeroing) = zeroing {
+            instructions.insert(index, zeroing);
+            index += 1;
+        }
+    }
+    Ok(found)
+}
+
+/// Moves the heap of CBMC `symbols` and `functions` into the model of
+/// ESBMC. `malloc` and `free` are always replaced, as the ESBMC frontend
+/// does, and the arrays of ESBMC are initialized by `entrypoint`. Fails
+/// on the parts of the CBMC model that ESBMC does not have.
+pub fn cbmc_heap_to_esbmc(
+    symbols: &mut Vec<CBMCSymbol>,
+    functions: &mut [CBMCFunction],
+    entrypoint: &str,
+    pointer_width: u64,
+) -> Result<(), String> {
+    let arrays = ObjectArrays {
+        size_t: Irept::bitvector("unsignedbv", pointer_width),
+    };
+    let tags: HashMap<String, Irept> = symbols
+        .iter()
+        .filter(|s| s.is_type)
+        .map(|s| (s.name.clone(), s.stype.clone()))
+        .collect();
+    let mut found = false;
+    let mut locals = vec![];
+
+    for function in functions.iter_mut() {
+        // Every call to them is replaced, their bodies are left unused
+        if function.name != "malloc" && function.name != "free" {
+            for instruction in &function.instructions {
+                // Updates of the CBMC model (e.g. by the DEAD of Kani) only
+                // flow into the model itself
+                if instruction.instr_type == ASSIGN
+                    && instruction
+                        .code
+                        .subt
+                        .first()
+                        .is_some_and(|lhs| lhs.id == "symbol" && cbmc_object(lhs).is_some())
+                {
+                    continue;
+                }
+                if let Some(name) = instruction
+                    .code
+                    .subt
+                    .iter()
+                    .chain([&instruction.guard])
+                    .find_map(cbmc_object)
+                {
+                    return Err(format!(
+                        "Function {}: {} is not supported by ESBMC",
+                        function.name, name
+                    ));
+                }
+            }
+        }
+        let mut sizes = ObjectSizes {
+            function: &function.name,
+            reads: vec![],
+        };
+        found |= esbmc_instructions(
+            &mut function.instructions,
+            &arrays,
+            &tags,
+            &mut sizes,
+            pointer_width,
+        )
+        .map_err(|e| format!("Function {}: {}", function.name, e))?;
+        locals.extend(sizes.reads.into_iter().map(|(local, _)| local));
+    }
+    for symbol in symbols.iter_mut() {
+        found |= esbmc_expression(&mut symbol.value, &arrays, &tags, None)
+            .map_err(|e| format!("Symbol {}: {}", symbol.name, e))?;
+    }
+
+    if !locals.is_empty() && !symbols.iter().any(|s| s.name == GET_OBJECT_SIZE) {
+        let mut get_object_size = CBMCSymbol {
+            stype: get_object_size_type(&arrays.size_t, pointer_width),
+            value: Irept::from("nil"),
+            name: String::from(GET_OBJECT_SIZE),
+            module: String::from("esbmc_intrinsics"),
+            base_name: String::from("__ESBMC_get_object_size"),
+            mode: String::from("C"),
+            pretty_name: String::from("__ESBMC_get_object_size"),
+            ..Default::default()
+        };
+        get_object_size.flags = get_object_size.pack_flags();
+        symbols.push(get_object_size);
+    }
+    for local in locals {
+        let name = local.named_subt["identifier"].id.clone();
+        let mut result = CBMCSymbol {
+            stype: arrays.size_t.clone(),
+            value: Irept::from("nil"),
+            name: name.clone(),
+            base_name: name.clone(),
+            mode: String::from("C"),
+            pretty_name: name,
+            is_lvalue: true,
+            is_thread_local: true,
+            is_file_local: true,
+            ..Default::default()
+        };
+        result.flags = result.pack_flags();
+        symbols.push(result);
+    }
+
+    if !found || symbols.iter().any(|s| s.name == ALLOC) {
+        return Ok(());
+    }
+
+    // Every object starts as not allocated
+    let entry = match functions.iter_mut().find(|f| f.name == entrypoint) {
+        Some(entry) if !entry.instructions.is_empty() => entry,
+        _ => return Ok(()),
+    };
+    let like = entry.instructions[0].clone();
+    let added = arrays.symbols();
+    let initialization: Vec<CBMCInstruction> = added
+        .iter()
+        .map(|global| {
+            let lhs = Irept::symbol(&global.name, global.stype.clone());
+            CBMCInstruction {
+                code: Irept::code("assign", vec![lhs, global.value.clone()]),
+                instr_type: ASSIGN,
+                guard: Irept::boolean(true),
+                target_number: NO_TARGET_NUMBER,
+                targets: vec![],
+                labels: vec![],
+                ..like.clone()
+            }
+        })
+        .collect();
+    entry.instructions.splice(0..0, initialization);
+    symbols.extend(added);
+    Ok(())
+}
+
+// Rewrites the expressions of ESBMC within `irep`
+fn cbmc_expression(irep: &mut Irept) {
+    for sub in irep.subt.iter_mut().chain(irep.named_subt.values_mut()) {
+        cbmc_expression(sub);
+    }
+
+    if irep.id == "side_effect" && statement(irep) == Some("malloc") {
+        // The size is also kept as a comment, which CBMC reads as a named sub
+        for comment in ["#size", "#type"] {
+            irep.named_subt.remove(comment);
+            irep.comments.remove(comment);
+        }
+        let size = irep.subt.first().cloned().unwrap_or_default();
+        irep.named_subt
+            .insert(String::from("statement"), Irept::from("allocate"));
+        irep.subt = vec![size, Irept::boolean(false)];
+        return;
+    }
+
+    // Reads of __ESBMC_is_dynamic[pointer_object(p)]
+    if irep.id != "index" || irep.subt.len() != 2 || irep.subt[1].id != "pointer_object" {
+        return;
+    }
+    let array = match irep.subt[0].named_subt.get("identifier") {
+        Some(identifier) => identifier.id.clone(),
+        None => return,
+    };
+    let id = match array.as_str() {
+        IS_DYNAMIC => "is_dynamic_object",
+        ALLOC_SIZE => "object_size",
+        _ => return,
+    };
+    let pointer = match irep.subt[1].subt.first() {
+        Some(pointer) => pointer.clone(),
+        None => return,
+    };
+    let stype = irep.named_subt.get("type").cloned().unwrap_or_default();
+    *irep = Irept::typed(id, stype);
+    irep.subt.push(pointer);
+}
+
+/// Moves the heap of ESBMC `functions` into the model of CBMC, `free` is
+/// left to the library of CBMC
+pub fn esbmc_heap_to_cbmc(symbols: &mut Vec<CBMCSymbol>, functions: &mut [CBMCFunction]) {
+    let mut free_type = None;
+    for instruction in functions.iter_mut().flat_map(|f| &mut f.instructions) {
+        if instruction.instr_type == OTHER
+            && statement(&instruction.code) == Some("free")
+            && instruction.code.subt.len() == 1
+        {
+            let pointer = instruction.code.subt[0].clone();
+            let pointer_type = pointer.named_subt.get("type").cloned().unwrap_or_default();
+            let mut parameter = Irept::typed("parameter", pointer_type);
+            parameter
+                .named_subt
+                .insert(String::from("#identifier"), Irept::from("free::ptr"));
+            let mut parameters = Irept::default();
+            parameters.subt.push(parameter);
+            let mut stype = Irept::from("code");
+            stype
+                .named_subt
+                .insert(String::from("parameters"), parameters);
+            stype
+                .named_subt
+                .insert(String::from("return_type"), Irept::from("empty"));
+
+            let arguments = Irept {
+                id: String::from("arguments"),
+                subt: vec![pointer],
+                ..Default::default()
+            };
+            instruction.instr_type = FUNCTION_CALL;
+            instruction
+                .code
+                .named_subt
+                .insert(String::from("statement"), Irept::from("function_call"));
+            instruction.code.subt = vec![
+                Irept::from("nil"),
+                Irept::symbol("free", stype.clone()),
+                arguments,
+            ];
+            free_type = Some(stype);
+        }
+
+        // The size of any object, dynamic or not
+        if callee(instruction) == Some(GET_OBJECT_SIZE) && arguments(instruction).len() == 1 {
+            if let Some(lhs) = instruction.code.subt.first().filter(|l| l.id != "nil") {
+                let stype = lhs.named_subt.get("type").cloned().unwrap_or_default();
+                let mut size = Irept::typed("object_size", stype);
+                size.subt.push(arguments(instruction)[0].clone());
+                instruction.instr_type = ASSIGN;
+                instruction
+                    .code
+                    .named_subt
+                    .insert(String::from("statement"), Irept::from("assign"));
+                instruction.code.subt = vec![lhs.clone(), size];
+            }
+        }
+
+        // Assignments into the arrays are not reads of CBMC expressions
+        let skip =
+            usize::from(instruction.instr_type == ASSIGN && !instruction.code.subt.is_empty());
+        for operand in instruction.code.subt.iter_mut().skip(skip) {
+            cbmc_expression(operand);
+        }
+        cbmc_expression(&mut instruction.guard);
+    }
+
+    if let Some(stype) = free_type {
+        if !symbols.iter().any(|s| s.name == "free") {
+            let mut free = CBMCSymbol {
+                stype,
+                name: String::from("free"),
+                base_name: String::from("free"),
+                pretty_name: String::from("free"),
+                mode: String::from("C"),
+                is_lvalue: true,
+                ..Default::default()
+            };
+            free.flags = free.pack_flags();
+            symbols.push(free);
+        }
+    }
+}
+
+///////////
+// TESTS //
+///////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instruction(instr_type: u32, statement: &str, operands: Vec<Irept>) -> CBMCInstruction {
+        CBMCInstruction {
+            code: Irept::code(statement, operands),
+            source_location: Irept::default(),
+            instr_type,
+            guard: Irept::boolean(true),
+            target_number: NO_TARGET_NUMBER,
+            targets: vec![],
+            labels: vec![],
+            function: Irept::default(),
+        }
+    }
+
+    fn call(lhs: Irept, name: &str, arguments: Vec<Irept>) -> CBMCInstruction {
+        let arguments = Irept {
+            id: String::from("arguments"),
+            subt: arguments,
+            ..Default::default()
+        };
+        instruction(
+            FUNCTION_CALL,
+            "function_call",
+            vec![lhs, Irept::symbol(name, Irept::from("code")), arguments],
+        )
+    }
+
+    fn pointer() -> Irept {
+        Irept::pointer(Irept::from("empty"), 64)
+    }
+
+    fn unary(id: &str, stype: Irept, operand: Irept) -> Irept {
+        let mut result = Irept::typed(id, stype);
+        result.subt.push(operand);
+        result
+    }
+
+    #[test]
+    fn cbmc_heap() {
+        let p = Irept::symbol("main::p", pointer());
+        let size = Irept::constant("10", Irept::bitvector("unsignedbv", 64));
+        let mut allocate = Irept::typed("side_effect", pointer());
+        allocate
+            .named_subt
+            .insert(String::from("statement"), Irept::from("allocate"));
+        allocate.subt = vec![size.clone(), Irept::boolean(false)];
+        let mut calloc = allocate.clone();
+        calloc.subt[1] = Irept::boolean(true);
+        let is_dynamic = unary("is_dynamic_object", Irept::from("bool"), p.clone());
+        let mut chars = Irept::from("array");
+        chars.subt.push(Irept::bitvector("unsignedbv", 8));
+        chars.named_subt.insert(String::from("size"), size.clone());
+        let address = unary("address_of", pointer(), Irept::symbol("main::x", chars));
+        let variable_size = unary("object_size", Irept::bitvector("unsignedbv", 32), address);
+        let object_size = unary("object_size", Irept::bitvector("unsignedbv", 32), p.clone());
+        let mut sizes = instruction(
+            CBMCInstructionKind::Assert as u32,
+            "nil",
+            vec![is_dynamic, variable_size, object_size],
+        );
+        sizes.target_number = 1;
+
+        let mut symbols = vec![];
+        let mut functions = vec![CBMCFunction {
+            name: String::from("main"),
+            instructions: vec![
+                call(p.clone(), "malloc", vec![size.clone()]),
+                instruction(ASSIGN, "assign", vec![p.clone(), allocate]),
+                instruction(ASSIGN, "assign", vec![p.clone(), calloc]),
+                call(Irept::from("nil"), "free", vec![p.clone()]),
+                sizes,
+            ],
+        }];
+        cbmc_heap_to_esbmc(&mut symbols, &mut functions, "main", 64).unwrap();
+
+        let names: Vec<&str> = symbols.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                GET_OBJECT_SIZE,
+                "main::object_size::0",
+                ALLOC,
+                IS_DYNAMIC,
+                ALLOC_SIZE
+            ]
+        );
+        assert_eq!(symbols[4].value.id, "array_of");
+
+        let main = &functions[0].instructions;
+        assert_eq!(main.len(), 11);
+        for init in &main[..3] {
+            assert_eq!(init.instr_type, ASSIGN);
+            assert_eq!(init.code.subt[1].id, "array_of");
+        }
+
+        for assign in [&main[3], &main[4], &main[5]] {
+            assert_eq!(assign.instr_type, ASSIGN);
+            assert_eq!(assign.code.subt[0], p);
+            let rhs = &assign.code.subt[1];
+            assert_eq!(statement(rhs), Some("malloc"));
+            assert_eq!(rhs.subt, vec![size.clone()]);
+            assert_eq!(rhs.comments["#size"], size);
+        }
+
+        // The zero-initialized allocation is followed by its zeroing
+        let zeroing = &main[6].code;
+        assert_eq!(main[6].instr_type, ASSIGN);
+        assert_eq!(zeroing.subt[0].id, "dereference");
+        assert_eq!(zeroing.subt[0].subt[0].subt[0], p);
+        assert_eq!(zeroing.subt[1].id, "array_of");
+        assert_eq!(zeroing.subt[1].named_subt["type"].named_subt["size"], size);
+
+        assert_eq!(main[7].instr_type, OTHER);
+        assert_eq!(statement(&main[7].code), Some("free"));
+        assert_eq!(main[7].code.subt, vec![p.clone()]);
+
+        // The size of any object is read by ESBMC before the instruction,
+        // jumps go through the read
+        let read = Irept::symbol("main::object_size::0", Irept::bitvector("unsignedbv", 64));
+        assert_eq!(main[8].instr_type, CBMCInstructionKind::Decl as u32);
+        assert_eq!(main[8].code.subt, vec![read.clone()]);
+        assert_eq!(main[8].target_number, 1);
+        assert_eq!(main[9].instr_type, FUNCTION_CALL);
+        assert_eq!(main[9].code.subt[0], read);
+        assert_eq!(
+            main[9].code.subt[1].named_subt["identifier"].id,
+            GET_OBJECT_SIZE
+        );
+        assert_eq!(main[9].code.subt[2].subt, vec![p.clone()]);
+        assert_eq!(main[10].target_number, NO_TARGET_NUMBER);
+
+        let is_dynamic = &main[10].code.subt[0];
+        assert_eq!(is_dynamic.id, "index");
+        assert_eq!(is_dynamic.subt[0].named_subt["identifier"].id, IS_DYNAMIC);
+        assert_eq!(is_dynamic.subt[1].subt[0], p);
+        // The size of a variable is known upfront
+        let variable_size = &main[10].code.subt[1];
+        assert_eq!(
+            *variable_size,
+            Irept::constant("10", Irept::bitvector("unsignedbv", 32))
+        );
+        let object_size = &main[10].code.subt[2];
+        assert_eq!(object_size.id, "typecast");
+        assert_eq!(object_size.subt[0], read);
+
+        // And back, apart from the initialization
+        let mut functions = vec![CBMCFunction {
+            name: String::from("main"),
+            instructions: functions[0].instructions.split_off(3),
+        }];
+        let mut symbols = vec![];
+        esbmc_heap_to_cbmc(&mut symbols, &mut functions);
+        let back = &functions[0].instructions;
+        assert_eq!(symbols[0].name, "free");
+        assert_eq!(statement(&back[0].code.subt[1]), Some("allocate"));
+        assert_eq!(back[0].code.subt[1].subt[0], size);
+        assert!(back[0].code.subt[1].comments.is_empty());
+        assert_eq!(back[4].instr_type, FUNCTION_CALL);
+        assert_eq!(back[4].code.subt[1].named_subt["identifier"].id, "free");
+        assert_eq!(back[6].instr_type, ASSIGN);
+        assert_eq!(back[6].code.subt[0], read);
+        assert_eq!(back[6].code.subt[1].id, "object_size");
+        assert_eq!(back[6].code.subt[1].subt[0], p);
+        assert_eq!(back[7].code.subt[0].id, "is_dynamic_object");
+    }
+
+    #[test]
+    fn cbmc_heap_unsupported() {
+        let p = Irept::symbol("main::p", pointer());
+        let lower = |instruction: CBMCInstruction| {
+            let mut functions = vec![CBMCFunction {
+                name: String::from("main"),
+                instructions: vec![instruction],
+            }];
+            cbmc_heap_to_esbmc(&mut vec![], &mut functions, "main", 64)
+                .err()
+                .unwrap()
+        };
+
+        // The zeroing needs the allocated pointer
+        let mut calloc = Irept::typed("side_effect", pointer());
+        calloc
+            .named_subt
+            .insert(String::from("statement"), Irept::from("allocate"));
+        calloc.subt = vec![
+            Irept::constant("10", Irept::bitvector("unsignedbv", 64)),
+            Irept::boolean(true),
+        ];
+        let mut cast = Irept::typed("typecast", pointer());
+        cast.subt.push(calloc);
+        assert_eq!(
+            lower(instruction(ASSIGN, "assign", vec![p.clone(), cast])),
+            "Function main: zero-initialized allocations are only supported as the right-hand side of an assignment"
+        );
+
+        // ESBMC does not know the globals of the CBMC model
+        let deallocated = Irept::symbol("__CPROVER_deallocated", pointer());
+        let mut equal = Irept::typed("=", Irept::from("bool"));
+        equal.subt = vec![p.clone(), deallocated.clone()];
+        let mut check = instruction(CBMCInstructionKind::Assert as u32, "nil", vec![]);
+        check.guard = equal;
+        assert_eq!(
+            lower(check),
+            "Function main: __CPROVER_deallocated is not supported by ESBMC"
+        );
+        // Which might be written
+        let mut functions = vec![CBMCFunction {
+            name: String::from("__CPROVER_initialize"),
+            instructions: vec![instruction(
+                ASSIGN,
+                "assign",
+                vec![deallocated, Irept::constant("NULL", pointer())],
+            )],
+        }];
+        assert!(cbmc_heap_to_esbmc(&mut vec![], &mut functions, "main", 64).is_ok());
+    }
+}
@@ -0,0 +1,407 @@
+// This module is responsible to print goto programs in the style of
+// --show-symbol-table and --show-goto-functions
+
+use crate::bytereader::{ByteReader, GotoFormat};
+use crate::cbmc::{CBMCInstructionKind, CBMCParseResult, NO_TARGET_NUMBER};
+use crate::esbmc::{ESBMCInstructionKind, ESBMCParseResult};
+use crate::irep::Irept;
+use crate::jsongoto::{read_json_file, JsonProgram};
+use crate::pretty;
+use regex::Regex;
+
+fn kind_name(name: Option<&str>, instr_type: u32) -> String {
+    match name {
+        Some(name) => name.to_string(),
+        None => format!("UNKNOWN({})", instr_type),
+    }
+}
+
+// Format independent view of what gets printed
+
+struct SymbolView {
+    name: String,
+    base_name: String,
+    module: String,
+    mode: String,
+    stype: Irept,
+    value: Irept,
+    location: Irept,
+    flags: Vec<String>,
+}
+
+struct InstructionView {
+    kind: String,
+    code: Irept,
+    guard: Irept,
+    location: Irept,
+    target_number: Option<u32>,
+    targets: Vec<u32>,
+    labels: Vec<String>,
+}
+
+struct FunctionView {
+    name: String,
+    instructions: Vec<InstructionView>,
+}
+
+struct ProgramView {
+    symbols: Vec<SymbolView>,
+    functions: Vec<FunctionView>,
+}
+
+impl From<&CBMCParseResult> for ProgramView {
+    fn from(data: &CBMCParseResult) -> Self {
+        let symbols = data
+            .symbols_irep
+            .iter()
+            .map(|sym| SymbolView {
+                name: sym.name.clone(),
+                base_name: sym.base_name.clone(),
+                module: sym.module.clone(),
+                mode: sym.mode.clone(),
+                stype: sym.stype.clone(),
+                value: sym.value.clone(),
+                location: sym.location.clone(),
+                flags: sym
+                    .flag_list()
+                    .iter()
+                    .filter(|(_, set)| *set)
+                    .map(|(flag, _)| flag.to_string())
+                    .collect(),
+            })
+            .collect();
+
+        let functions = data
+            .functions_irep
+            .iter()
+            .map(|function| FunctionView {
+                name: function.name.clone(),
+                instructions: function
+                    .instructions
+                    .iter()
+                    .map(|instr| InstructionView {
+                        kind: kind_name(
+                            CBMCInstructionKind::from_u32(instr.instr_type).map(|k| k.name()),
+                            instr.instr_type,
+                        ),
+                        code: instr.code.clone(),
+                        guard: instr.guard.clone(),
+                        location: instr.source_location.clone(),
+                        target_number: match instr.target_number {
+                            NO_TARGET_NUMBER => None,
+                            number => Some(number),
+                        },
+                        targets: instr
+                            .targets
+                            .iter()
+                            .map(|t| t.id.parse().unwrap())
+                            .collect(),
+                        labels: instr.labels.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        ProgramView { symbols, functions }
+    }
+}
+
+fn named_or_nil(irep: &Irept, name: &str) -> Irept {
+    irep.named_subt
+        .get(name)
+        .cloned()
+        .unwrap_or_else(Irept::get_nil)
+}
+
+impl From<&ESBMCParseResult> for ProgramView {
+    fn from(data: &ESBMCParseResult) -> Self {
+        let symbols = data
+            .symbols_irep
+            .iter()
+            .map(|sym| SymbolView {
+                name: named_or_nil(sym, "name").id,
+                base_name: named_or_nil(sym, "base_name").id,
+                module: named_or_nil(sym, "module").id,
+                mode: named_or_nil(sym, "mode").id,
+                stype: named_or_nil(sym, "type"),
+                value: named_or_nil(sym, "symvalue"),
+                location: named_or_nil(sym, "location"),
+                flags: [
+                    "is_type",
+                    "is_macro",
+                    "is_parameter",
+                    "lvalue",
+                    "static_lifetime",
+                    "file_local",
+                    "is_extern",
+                ]
+                .iter()
+                .filter(|flag| sym.named_subt.get(**flag).is_some_and(|x| x.id == "1"))
+                .map(|flag| flag.to_string())
+                .collect(),
+            })
+            .collect();
+
+        let functions = data
+            .functions_irep
+            .iter()
+            .map(|(name, program)| {
+                // ESBMC targets are instruction offsets
+                let targeted: Vec<u32> = program
+                    .subt
+                    .iter()
+                    .flat_map(|instr| named_or_nil(instr, "targets").subt)
+                    .map(|t| t.id.parse().unwrap())
+                    .collect();
+
+                let instructions = program
+                    .subt
+                    .iter()
+                    .enumerate()
+                    .map(|(index, instr)| InstructionView {
+                        kind: {
+                            let typeid = named_or_nil(instr, "typeid").id.parse().unwrap_or(0);
+                            kind_name(
+                                ESBMCInstructionKind::from_u32(typeid).map(|k| k.name()),
+                                typeid,
+                            )
+                        },
+                        code: named_or_nil(instr, "code"),
+                        guard: named_or_nil(instr, "guard"),
+                        location: named_or_nil(instr, "location"),
+                        target_number: targeted.contains(&(index as u32)).then_some(index as u32),
+                        targets: named_or_nil(instr, "targets")
+                            .subt
+                            .iter()
+                            .map(|t| t.id.parse().unwrap())
+                            .collect(),
+                        labels: named_or_nil(instr, "labels")
+                            .subt
+                            .into_iter()
+                            .map(|l| l.id)
+                            .collect(),
+                    })
+                    .collect();
+                FunctionView {
+                    name: name.clone(),
+                    instructions,
+                }
+            })
+            .collect();
+
+        ProgramView { symbols, functions }
+    }
+}
+
+//////////////
+// PRINTING //
+//////////////
+
+fn location_text(location: &Irept) -> String {
+    ["file", "line", "column", "function"]
+        .iter()
+        .filter_map(|key| {
+            location
+                .named_subt
+                .get(*key)
+                .map(|value| format!("{} {}", key, value.id))
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn is_true(irep: &Irept) -> bool {
+    irep.id == "constant" && irep.named_subt.get("value").is_some_and(|v| v.id == "true")
+}
+
+fn symbol_text(sym: &SymbolView) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("Symbol......: {}\n", sym.name));
+    out.push_str(&format!("Module......: {}\n", sym.module));
+    out.push_str(&format!("Base name...: {}\n", sym.base_name));
+    out.push_str(&format!("Mode........: {}\n", sym.mode));
+    out.push_str(&format!(
+        "Type........: {}\n",
+        pretty::type_text(&sym.stype)
+    ));
+    out.push_str(&format!("Value.......: {}\n", pretty::expr(&sym.value)));
+    out.push_str(&format!("Flags.......: {}\n", sym.flags.join(" ")));
+    out.push_str(&format!("Location....: {}\n", location_text(&sym.location)));
+    out
+}
+
+fn code_text(kind: &str, code: &Irept, guard: &Irept, targets: &[u32]) -> String {
+    let operands = code.operands();
+    let targets = targets
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<String>>()
+        .join(", ");
+    match (kind, operands) {
+        ("GOTO" | "INCOMPLETE_GOTO", _) if is_true(guard) => format!("GOTO {}", targets),
+        ("GOTO" | "INCOMPLETE_GOTO", _) => {
+            format!("IF {} THEN GOTO {}", pretty::expr(guard), targets)
+        }
+        ("ASSUME" | "ASSERT", _) => format!("{} {}", kind, pretty::expr(guard)),
+        ("ASSIGN", [lhs, rhs, ..]) => {
+            format!("ASSIGN {} := {}", pretty::expr(lhs), pretty::expr(rhs))
+        }
+        ("DECL", [symbol, ..]) => format!(
+            "DECL {} : {}",
+            pretty::expr(symbol),
+            pretty::type_text(&named_or_nil(symbol, "type"))
+        ),
+        ("DEAD", [symbol, ..]) => format!("DEAD {}", pretty::expr(symbol)),
+        ("FUNCTION_CALL", [lhs, function, arguments, ..]) => {
+            let arguments = arguments
+                .operands()
+                .iter()
+                .map(pretty::expr)
+                .collect::<Vec<String>>()
+                .join(", ");
+            let call = format!("{}({})", pretty::expr(function), arguments);
+            match lhs.id.as_str() {
+                "nil" => format!("CALL {}", call),
+                _ => format!("CALL {} := {}", pretty::expr(lhs), call),
+            }
+        }
+        ("SET_RETURN_VALUE" | "RETURN", [value, ..]) => {
+            format!("{} {}", kind, pretty::expr(value))
+        }
+        ("OTHER", _) => format!("OTHER {}", pretty::code_text(code)),
+        _ => String::from(kind),
+    }
+}
+
+fn function_text(function: &FunctionView) -> String {
+    let mut out = format!("{} /* {} */\n", function.name, function.name);
+    for (index, instr) in function.instructions.iter().enumerate() {
+        out.push_str(&format!(
+            "        // {} {}\n",
+            index,
+            location_text(&instr.location)
+        ));
+        if !instr.labels.is_empty() {
+            out.push_str(&format!("        // Labels: {}\n", instr.labels.join(", ")));
+        }
+        let prefix = match instr.target_number {
+            Some(number) => format!("{:>6}: ", number),
+            None => String::from("        "),
+        };
+        out.push_str(&format!(
+            "{}{}\n",
+            prefix,
+            code_text(&instr.kind, &instr.code, &instr.guard, &instr.targets)
+        ));
+    }
+    out
+}
+
+// Nothing is filtered out when no filter is given. Otherwise only the
+// matching symbols and/or the given function are printed.
+fn program_text(
+    program: &ProgramView,
+    function: Option<&str>,
+    symbol: Option<&Regex>,
+) -> Result<String, String> {
+    let mut out = String::new();
+    let everything = function.is_none() && symbol.is_none();
+
+    if everything || symbol.is_some() {
+        out.push_str("Symbols:\n\n");
+        for sym in &program.symbols {
+            if symbol.is_none_or(|regex| regex.is_match(&sym.name)) {
+                out.push_str(&symbol_text(sym));
+                out.push('\n');
+            }
+        }
+    }
+
+    if everything || function.is_some() {
+        let functions: Vec<&FunctionView> = program
+            .functions
+            .iter()
+            .filter(|f| function.is_none_or(|name| f.name == name))
+            .collect();
+        if let (Some(name), true) = (function, functions.is_empty()) {
+            return Err(format!("Function {} not found", name));
+        }
+        out.push_str("Functions:\n\n");
+        for f in functions {
+            out.push_str(&function_text(f));
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+pub fn inspect(input: &str, function: Option<&str>, symbol: Option<&str>) -> Result<(), String> {
+    let symbol = match symbol {
+        Some(pattern) => Some(Regex::new(pattern).map_err(|e| e.to_string())?),
+        None => None,
+    };
+
+    let format = ByteReader::read_file(input)
+        .and_then(|reader| reader.detect_format())
+        .map_err(|e| format!("Could not detect the format of {}: {}", input, e))?;
+    let program = match format {
+        GotoFormat::Cbmc => {
+            ProgramView::from(&crate::cbmc::process_cbmc_file(input).map_err(|e| e.to_string())?)
+        }
+        GotoFormat::Esbmc => {
+            ProgramView::from(&crate::esbmc::process_esbmc_file(input).map_err(|e| e.to_string())?)
+        }
+        GotoFormat::Json => match read_json_file(input)? {
+            JsonProgram::Cbmc(result) => ProgramView::from(&result),
+            JsonProgram::Esbmc(result) => ProgramView::from(&result),
+        },
+    };
+
+    print!("{}", program_text(&program, function, symbol.as_ref())?);
+    Ok(())
+}
+
+///////////
+// TESTS //
+///////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::get_test_path;
+
+    #[test]
+    fn inspect_cbmc() {
+        let result = crate::cbmc::process_cbmc_file(&get_test_path("hello-gb.goto")).unwrap();
+        let program = ProgramView::from(&result);
+
+        let text = program_text(&program, Some("main"), None).unwrap();
+        assert!(text.starts_with("Functions:\n\nmain /* main */\n"));
+        assert!(!text.contains("Symbols:"));
+        assert!(text.contains("GOTO 1\n"));
+        assert!(text.contains("     1: "));
+        assert!(text.contains("END_FUNCTION"));
+
+        let regex = Regex::new("^main$").unwrap();
+        let text = program_text(&program, None, Some(&regex)).unwrap();
+        assert_eq!(text.matches("Symbol......:").count(), 1);
+        assert!(text.contains("Symbol......: main\n"));
+        assert!(!text.contains("Functions:"));
+
+        assert_eq!(
+            program_text(&program, Some("nope"), None).unwrap_err(),
+            "Function nope not found"
+        );
+    }
+
+    #[test]
+    fn inspect_esbmc() {
+        let result = crate::esbmc::process_esbmc_file(&get_test_path("hello.goto")).unwrap();
+        let program = ProgramView::from(&result);
+        let text = program_text(&program, None, None).unwrap();
+        assert!(text.contains("Symbol......: c:@F@free\n"));
+        assert!(text.contains("__ESBMC_main /* __ESBMC_main */\n"));
+        assert!(text.contains("ATOMIC_BEGIN"));
+        assert!(text.contains("CALL c:@F@__ESBMC_yield()"));
+    }
+}
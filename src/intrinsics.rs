@@ -0,0 +1,803 @@
+// This module maps the intrinsics of CBMC (__CPROVER_*) into the ones of
+// ESBMC (__ESBMC_*) and back. The intrinsics of both tools are listed at
+// docs/Development.md
+
+use std::collections::{BTreeSet, HashSet};
+
+use crate::cbmc::{
+    CBMCFunction, CBMCInstruction, CBMCInstructionKind, CBMCSymbol, NO_TARGET_NUMBER,
+};
+use crate::irep::Irept;
+
+// Intrinsics are lowered once both tools use the CBMC instructions
+const ASSUME: u32 = CBMCInstructionKind::Assume as u32;
+const ASSERT: u32 = CBMCInstructionKind::Assert as u32;
+const FUNCTION_CALL: u32 = CBMCInstructionKind::FunctionCall as u32;
+const DECL: u32 = CBMCInstructionKind::Decl as u32;
+const ASSIGN: u32 = CBMCInstructionKind::Assign as u32;
+const GOTO: u32 = CBMCInstructionKind::Goto as u32;
+const SKIP: u32 = CBMCInstructionKind::Skip as u32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Intrinsic {
+    // Global known by both tools under another name
+    Renamed(&'static str),
+    // Calls become an ASSUME of the first argument
+    Assume,
+    // Calls become an ASSERT of the first argument, the second one (if any)
+    // is the message
+    Assert,
+    // Calls become an instruction without operands, e.g. a SKIP for the
+    // ones without effect on the verification
+    Instruction(CBMCInstructionKind),
+    // Calls become the loop of an operational model, see model_intrinsics
+    Model,
+    // No counterpart in the other tool, converting from CBMC fails and
+    // converting from ESBMC keeps the calls as is
+    Unsupported,
+}
+
+const CBMC_INTRINSICS: [(&str, Intrinsic); 17] = [
+    (
+        "__CPROVER_rounding_mode",
+        Intrinsic::Renamed("__ESBMC_rounding_mode"),
+    ),
+    ("__CPROVER_assume", Intrinsic::Assume),
+    ("__CPROVER_assert", Intrinsic::Assert),
+    ("__CPROVER_precondition", Intrinsic::Assert),
+    ("__CPROVER_postcondition", Intrinsic::Assert),
+    (
+        "__CPROVER_atomic_begin",
+        Intrinsic::Instruction(CBMCInstructionKind::AtomicBegin),
+    ),
+    (
+        "__CPROVER_atomic_end",
+        Intrinsic::Instruction(CBMCInstructionKind::AtomicEnd),
+    ),
+    (
+        "__CPROVER_input",
+        Intrinsic::Instruction(CBMCInstructionKind::Skip),
+    ),
+    (
+        "__CPROVER_output",
+        Intrinsic::Instruction(CBMCInstructionKind::Skip),
+    ),
+    (
+        "__CPROVER_printf",
+        Intrinsic::Instruction(CBMCInstructionKind::Skip),
+    ),
+    (
+        "__CPROVER_fence",
+        Intrinsic::Instruction(CBMCInstructionKind::Skip),
+    ),
+    ("__CPROVER_cover", Intrinsic::Unsupported),
+    ("__CPROVER_havoc_object", Intrinsic::Model),
+    ("__CPROVER_havoc_slice", Intrinsic::Model),
+    ("__CPROVER_array_set", Intrinsic::Model),
+    ("__CPROVER_array_copy", Intrinsic::Model),
+    ("__CPROVER_array_replace", Intrinsic::Model),
+];
+
+// Renamed globals are derived from CBMC_INTRINSICS. CBMC considers every
+// interleaving, so there is no need to yield.
+const ESBMC_INTRINSICS: [(&str, Intrinsic); 5] = [
+    ("__ESBMC_assume", Intrinsic::Assume),
+    ("__ESBMC_assert", Intrinsic::Assert),
+    (
+        "__ESBMC_atomic_begin",
+        Intrinsic::Instruction(CBMCInstructionKind::AtomicBegin),
+    ),
+    (
+        "__ESBMC_atomic_end",
+        Intrinsic::Instruction(CBMCInstructionKind::AtomicEnd),
+    ),
+    (
+        "__ESBMC_yield",
+        Intrinsic::Instruction(CBMCInstructionKind::Skip),
+    ),
+];
+
+/// ESBMC name of a renamed CBMC global
+pub fn esbmc_name(cbmc: &str) -> Option<&'static str> {
+    CBMC_INTRINSICS
+        .iter()
+        .find_map(|(name, intrinsic)| match intrinsic {
+            Intrinsic::Renamed(esbmc) if *name == cbmc => Some(*esbmc),
+            _ => None,
+        })
+}
+
+/// CBMC name of a renamed ESBMC global, given by its base name
+pub fn cbmc_name(esbmc: &str) -> Option<&'static str> {
+    CBMC_INTRINSICS
+        .iter()
+        .find_map(|(name, intrinsic)| match intrinsic {
+            Intrinsic::Renamed(renamed) if *renamed == esbmc => Some(*name),
+            _ => None,
+        })
+}
+
+/// Every other __CPROVER_ function is unsupported
+pub fn cbmc_intrinsic(name: &str) -> Option<Intrinsic> {
+    match CBMC_INTRINSICS.iter().find(|(n, _)| *n == name) {
+        Some((_, intrinsic)) => Some(*intrinsic),
+        None if name.starts_with("__CPROVER_") => Some(Intrinsic::Unsupported),
+        None => None,
+    }
+}
+
+/// Every other __ESBMC_ function is unsupported. ESBMC functions are
+/// named as "c:@F@<base_name>".
+pub fn esbmc_intrinsic(name: &str) -> Option<Intrinsic> {
+    let base_name = name.strip_prefix("c:@F@").unwrap_or(name);
+    match ESBMC_INTRINSICS.iter().find(|(n, _)| *n == base_name) {
+        Some((_, intrinsic)) => Some(*intrinsic),
+        None if base_name.starts_with("__ESBMC_") => Some(Intrinsic::Unsupported),
+        None => None,
+    }
+}
+
+// The value of the string literal within `irep`, e.g. for the message of
+// an assertion: &"message"[0]
+fn string_literal(irep: &Irept) -> Option<String> {
+    if ["string_constant", "string-constant"].contains(&irep.id.as_str()) {
+        return irep.named_subt.get("value").map(|v| v.id.clone());
+    }
+    irep.subt.iter().find_map(string_literal)
+}
+
+// Conditions of ASSUME and ASSERT must be of type bool
+fn condition(argument: Irept) -> Irept {
+    if argument
+        .named_subt
+        .get("type")
+        .is_some_and(|t| t.id == "bool")
+    {
+        return argument;
+    }
+    let mut result = Irept::from("typecast");
+    result.subt.push(argument);
+    result
+        .named_subt
+        .insert(String::from("type"), Irept::from("bool"));
+    result
+}
+
+/// Replaces the calls to intrinsics (that are not defined in `functions`)
+/// by the instructions that model them. Returns the intrinsics that could
+/// not be translated.
+pub fn lower_intrinsics(
+    functions: &mut [CBMCFunction],
+    lookup: fn(&str) -> Option<Intrinsic>,
+) -> BTreeSet<String> {
+    let defined: HashSet<String> = functions
+        .iter()
+        .filter(|f| !f.instructions.is_empty())
+        .map(|f| f.name.clone())
+        .collect();
+
+    let mut unsupported = BTreeSet::new();
+    for instruction in functions.iter_mut().flat_map(|f| &mut f.instructions) {
+        if instruction.instr_type != FUNCTION_CALL || instruction.code.subt.len() != 3 {
+            continue;
+        }
+        let function = &instruction.code.subt[1];
+        let name = match function.named_subt.get("identifier") {
+            Some(identifier) if function.id == "symbol" => identifier.id.clone(),
+            _ => continue,
+        };
+        if defined.contains(&name) {
+            continue;
+        }
+
+        // Models are left by model_intrinsics when it does not run
+        let intrinsic = match lookup(&name) {
+            Some(Intrinsic::Unsupported | Intrinsic::Model) => {
+                unsupported.insert(name);
+                continue;
+            }
+            Some(Intrinsic::Renamed(_)) | None => continue,
+            Some(intrinsic) => intrinsic,
+        };
+
+        let mut arguments = std::mem::take(&mut instruction.code.subt[2].subt).into_iter();
+        match intrinsic {
+            Intrinsic::Assume => {
+                instruction.instr_type = ASSUME;
+                instruction.guard = condition(arguments.next().unwrap_or_default());
+            }
+            Intrinsic::Assert => {
+                instruction.instr_type = ASSERT;
+                instruction.guard = condition(arguments.next().unwrap_or_default());
+                if instruction.source_location.id == "nil" {
+                    instruction.source_location = Irept::default();
+                }
+                if let Some(message) = arguments.next().as_ref().and_then(string_literal) {
+                    instruction
+                        .source_location
+                        .named_subt
+                        .insert(String::from("comment"), Irept::from(message));
+                }
+                instruction
+                    .source_location
+                    .named_subt
+                    .insert(String::from("property_class"), Irept::from("assertion"));
+            }
+            Intrinsic::Instruction(kind) => instruction.instr_type = kind as u32,
+            Intrinsic::Renamed(_) | Intrinsic::Model | Intrinsic::Unsupported => unreachable!(),
+        }
+        instruction.code = Irept::from("nil");
+    }
+    unsupported
+}
+
+// The operational models of the CBMC intrinsics without ESBMC counterpart
+// are loops over the objects they write, q and r being locals:
+//
+// __CPROVER_havoc_object(p)        q = (char *)p - pointer_offset(p)
+//                                  while pointer_offset(q) < object_size(q)
+//                                    *q++ = nondet
+// __CPROVER_havoc_slice(p, size)   q = (char *)p, end = q + size
+//                                  while q != end
+//                                    *q++ = nondet
+// __CPROVER_array_set(dest, v)     q = dest
+//                                  while pointer_offset(q + 1) <= object_size(q)
+//                                    *q++ = v
+// __CPROVER_array_copy(dest, src)  q = dest, r = src
+//                                  while pointer_offset(r + 1) <= object_size(r)
+//                                    *q++ = *r++
+// __CPROVER_array_replace          as array_copy, while q is in bounds too
+//
+// As for CBMC, the arrays start at the given element. array_copy writes
+// every element of src, ESBMC checks that they fit into dest. object_size
+// is left to heap.rs. __CPROVER_cover has no model: ESBMC only checks
+// properties that must hold, not goals that must be reachable.
+
+// The loop of a model: its locals are declared and set in order, then
+// the assignments of `body` run while `condition` holds
+struct Model {
+    locals: Vec<(Irept, Irept)>,
+    condition: Irept,
+    body: Vec<(Irept, Irept)>,
+}
+
+fn expression(id: &str, stype: Irept, operands: Vec<Irept>) -> Irept {
+    let mut result = Irept::typed(id, stype);
+    result.subt = operands;
+    result
+}
+
+fn expression_type(irep: &Irept) -> Irept {
+    irep.named_subt.get("type").cloned().unwrap_or_default()
+}
+
+// Identifier of the function called by `instruction`
+fn callee(instruction: &CBMCInstruction) -> Option<&str> {
+    if instruction.instr_type != FUNCTION_CALL || instruction.code.subt.len() != 3 {
+        return None;
+    }
+    let function = &instruction.code.subt[1];
+    match function.named_subt.get("identifier") {
+        Some(identifier) if function.id == "symbol" => Some(identifier.id.as_str()),
+        _ => None,
+    }
+}
+
+// The array `irep` points into, without its casts into `void *` (CBMC
+// declares the arrays of the intrinsics as such)
+fn array(irep: &Irept, name: &str) -> Result<Irept, String> {
+    let mut result = irep;
+    while result.id == "typecast"
+        && expression_type(result)
+            .subt
+            .first()
+            .is_some_and(|t| t.id == "empty")
+    {
+        match result.subt.first() {
+            Some(operand) => result = operand,
+            None => break,
+        }
+    }
+    let stype = expression_type(result);
+    match stype.subt.first() {
+        Some(element) if stype.id == "pointer" && element.id != "empty" => Ok(result.clone()),
+        _ => Err(format!("{} expects a pointer into an array", name)),
+    }
+}
+
+impl Model {
+    // Model of the call to `name` on `arguments`, its locals are named
+    // after `prefix`
+    fn new(
+        name: &str,
+        arguments: &[Irept],
+        prefix: &str,
+        pointer_width: u64,
+    ) -> Result<Self, String> {
+        let expected = match name {
+            "__CPROVER_havoc_object" => 1,
+            _ => 2,
+        };
+        if arguments.len() != expected {
+            return Err(format!("{} expects {} arguments", name, expected));
+        }
+
+        let size_t = Irept::bitvector("unsignedbv", pointer_width);
+        let bool_type = Irept::from("bool");
+        let local =
+            |name: &str, stype: Irept| Irept::symbol(&format!("{}::{}", prefix, name), stype);
+        let cast = |irep: &Irept, stype: &Irept| match expression_type(irep) == *stype {
+            true => irep.clone(),
+            false => expression("typecast", stype.clone(), vec![irep.clone()]),
+        };
+        let offset = |pointer: Irept| expression("pointer_offset", size_t.clone(), vec![pointer]);
+        let size = |pointer: Irept| expression("object_size", size_t.clone(), vec![pointer]);
+        let plus = |pointer: &Irept, count: Irept| {
+            expression("+", expression_type(pointer), vec![pointer.clone(), count])
+        };
+        let next = |pointer: &Irept| {
+            (
+                pointer.clone(),
+                plus(pointer, Irept::constant("1", size_t.clone())),
+            )
+        };
+        let dereference = |pointer: &Irept| {
+            let element = expression_type(pointer)
+                .subt
+                .first()
+                .cloned()
+                .unwrap_or_default();
+            expression("dereference", element, vec![pointer.clone()])
+        };
+        // The element `pointer` points to is within its object
+        let in_bounds = |pointer: &Irept| {
+            let end = offset(plus(pointer, Irept::constant("1", size_t.clone())));
+            expression("<=", bool_type.clone(), vec![end, size(pointer.clone())])
+        };
+        let char_type = Irept::bitvector("unsignedbv", 8);
+        let chars = Irept::pointer(char_type.clone(), pointer_width);
+        let mut nondet = Irept::typed("side_effect", char_type);
+        nondet
+            .named_subt
+            .insert(String::from("statement"), Irept::from("nondet"));
+
+        Ok(match name {
+            "__CPROVER_havoc_object" => {
+                let q = local("q", chars.clone());
+                let p = cast(&arguments[0], &chars);
+                let start = expression("-", chars, vec![p.clone(), offset(p)]);
+                Model {
+                    locals: vec![(q.clone(), start)],
+                    condition: expression("<", bool_type, vec![offset(q.clone()), size(q.clone())]),
+                    body: vec![(dereference(&q), nondet), next(&q)],
+                }
+            }
+            "__CPROVER_havoc_slice" => {
+                let q = local("q", chars.clone());
+                let end = local("end", chars.clone());
+                let last = plus(&q, cast(&arguments[1], &size_t));
+                Model {
+                    locals: vec![
+                        (q.clone(), cast(&arguments[0], &chars)),
+                        (end.clone(), last),
+                    ],
+                    condition: expression("notequal", bool_type, vec![q.clone(), end]),
+                    body: vec![(dereference(&q), nondet), next(&q)],
+                }
+            }
+            "__CPROVER_array_set" => {
+                let dest = array(&arguments[0], name)?;
+                let q = local("q", expression_type(&dest));
+                let element = expression_type(&dest).subt[0].clone();
+                let value = local("value", element.clone());
+                Model {
+                    locals: vec![
+                        (q.clone(), dest),
+                        (value.clone(), cast(&arguments[1], &element)),
+                    ],
+                    condition: in_bounds(&q),
+                    body: vec![(dereference(&q), value), next(&q)],
+                }
+            }
+            _ => {
+                let dest = array(&arguments[0], name)?;
+                let src = array(&arguments[1], name)?;
+                let q = local("q", expression_type(&dest));
+                let r = local("r", expression_type(&src));
+                let element = expression_type(&dest).subt[0].clone();
+                // Copies stop at the end of src, replacements also at the
+                // end of dest
+                let condition = match name {
+                    "__CPROVER_array_copy" => in_bounds(&r),
+                    _ => expression("and", bool_type.clone(), vec![in_bounds(&q), in_bounds(&r)]),
+                };
+                Model {
+                    locals: vec![(q.clone(), dest), (r.clone(), src)],
+                    condition,
+                    body: vec![
+                        (dereference(&q), cast(&dereference(&r), &element)),
+                        next(&q),
+                        next(&r),
+                    ],
+                }
+            }
+        })
+    }
+
+    // The instructions of the loop at the location of `like`, `head` and
+    // `exit` are the target numbers of its condition and of its end
+    fn instructions(self, like: &CBMCInstruction, head: u32, exit: u32) -> Vec<CBMCInstruction> {
+        let instruction = |instr_type: u32, code: Irept| CBMCInstruction {
+            code,
+            instr_type,
+            guard: Irept::boolean(true),
+            target_number: NO_TARGET_NUMBER,
+            targets: vec![],
+            labels: vec![],
+            ..like.clone()
+        };
+        let assign =
+            |(lhs, rhs): (Irept, Irept)| instruction(ASSIGN, Irept::code("assign", vec![lhs, rhs]));
+
+        let mut result = vec![];
+        for (local, value) in self.locals {
+            result.push(instruction(DECL, Irept::code("decl", vec![local.clone()])));
+            result.push(assign((local, value)));
+        }
+        let mut condition = instruction(GOTO, Irept::from("nil"));
+        condition.guard = expression("not", Irept::from("bool"), vec![self.condition]);
+        condition.target_number = head;
+        condition.targets = vec![Irept::from(exit.to_string())];
+        result.push(condition);
+        result.extend(self.body.into_iter().map(assign));
+        let mut back_edge = instruction(GOTO, Irept::from("nil"));
+        back_edge.targets = vec![Irept::from(head.to_string())];
+        result.push(back_edge);
+        let mut end = instruction(SKIP, Irept::from("nil"));
+        end.target_number = exit;
+        result.push(end);
+        result
+    }
+}
+
+/// Replaces the calls to the CBMC intrinsics with an operational model
+/// (that are not defined in `functions`) by the loop of the model, whose
+/// locals are added to `symbols`. Fails on the calls a model cannot run.
+pub fn model_intrinsics(
+    symbols: &mut Vec<CBMCSymbol>,
+    functions: &mut [CBMCFunction],
+    pointer_width: u64,
+) -> Result<(), String> {
+    let defined: HashSet<String> = functions
+        .iter()
+        .filter(|f| !f.instructions.is_empty())
+        .map(|f| f.name.clone())
+        .collect();
+
+    for function in functions.iter_mut() {
+        // The loops jump to new target numbers
+        let mut target = function
+            .instructions
+            .iter()
+            .map(|i| i.target_number)
+            .filter(|t| *t != NO_TARGET_NUMBER)
+            .max()
+            .unwrap_or(0);
+        let mut count = 0;
+        let mut index = 0;
+        while index < function.instructions.len() {
+            let instruction = &function.instructions[index];
+            let name = match callee(instruction) {
+                Some(name)
+                    if cbmc_intrinsic(name) == Some(Intrinsic::Model)
+                        && !defined.contains(name) =>
+                {
+                    String::from(name)
+                }
+                _ => {
+                    index += 1;
+                    continue;
+                }
+            };
+            let base_name = name.strip_prefix("__CPROVER_").unwrap_or(&name);
+            let prefix = format!("{}::{}::{}", function.name, base_name, count);
+            let model = Model::new(
+                &name,
+                &instruction.code.subt[2].subt,
+                &prefix,
+                pointer_width,
+            )
+            .map_err(|e| format!("Function {}: {}", function.name, e))?;
+            count += 1;
+
+            for (local, _) in &model.locals {
+                let name = local.named_subt["identifier"].id.clone();
+                let mut symbol = CBMCSymbol {
+                    stype: expression_type(local),
+                    value: Irept::from("nil"),
+                    name: name.clone(),
+                    base_name: name.clone(),
+                    mode: String::from("C"),
+                    pretty_name: name,
+                    is_lvalue: true,
+                    is_thread_local: true,
+                    is_file_local: true,
+                    ..Default::default()
+                };
+                symbol.flags = symbol.pack_flags();
+                symbols.push(symbol);
+            }
+
+            // Jumps to the call run the whole loop
+            let mut instructions = model.instructions(instruction, target + 1, target + 2);
+            target += 2;
+            instructions[0].target_number = instruction.target_number;
+            instructions[0].labels = instruction.labels.clone();
+            let length = instructions.len();
+            function.instructions.splice(index..=index, instructions);
+            index += length;
+        }
+    }
+    Ok(())
+}
+
+///////////
+// TESTS //
+///////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instruction(instr_type: u32, code: Irept) -> CBMCInstruction {
+        CBMCInstruction {
+            code,
+            source_location: Irept::from("nil"),
+            instr_type,
+            guard: Irept::from("true"),
+            target_number: NO_TARGET_NUMBER,
+            targets: vec![],
+            labels: vec![],
+            function: Irept::default(),
+        }
+    }
+
+    fn call(name: &str, arguments: Vec<Irept>) -> CBMCInstruction {
+        let mut function = Irept::from("symbol");
+        function
+            .named_subt
+            .insert(String::from("identifier"), Irept::from(name));
+        let mut code = Irept::from("code");
+        code.named_subt
+            .insert(String::from("statement"), Irept::from("function_call"));
+        code.subt = vec![
+            Irept::from("nil"),
+            function,
+            Irept {
+                id: String::from("arguments"),
+                subt: arguments,
+                ..Default::default()
+            },
+        ];
+        instruction(FUNCTION_CALL, code)
+    }
+
+    fn message(text: &str) -> Irept {
+        let mut literal = Irept::from("string_constant");
+        literal
+            .named_subt
+            .insert(String::from("value"), Irept::from(text));
+        let mut index = Irept::from("index");
+        index.subt = vec![literal, Irept::from("0")];
+        let mut result = Irept::from("address_of");
+        result.subt = vec![index];
+        result
+    }
+
+    #[test]
+    fn renamed_globals() {
+        assert_eq!(
+            esbmc_name("__CPROVER_rounding_mode"),
+            Some("__ESBMC_rounding_mode")
+        );
+        assert_eq!(
+            cbmc_name("__ESBMC_rounding_mode"),
+            Some("__CPROVER_rounding_mode")
+        );
+        assert_eq!(esbmc_name("__CPROVER_assume"), None);
+        assert_eq!(cbmc_name("main"), None);
+    }
+
+    #[test]
+    fn cbmc_intrinsics() {
+        let condition = Irept::typed("x", Irept::from("bool"));
+        let mut functions = vec![
+            CBMCFunction {
+                name: String::from("main"),
+                instructions: vec![
+                    call("__CPROVER_assume", vec![condition.clone()]),
+                    call(
+                        "__CPROVER_assert",
+                        vec![
+                            Irept::typed("y", Irept::from("signedbv")),
+                            message("y is set"),
+                        ],
+                    ),
+                    call("__CPROVER_printf", vec![message("%d")]),
+                    call("__CPROVER_cover", vec![condition.clone()]),
+                    call("__CPROVER_foo", vec![]),
+                    call("__CPROVER_initialize", vec![]),
+                    call("bar", vec![]),
+                ],
+            },
+            CBMCFunction {
+                name: String::from("__CPROVER_initialize"),
+                instructions: vec![instruction(SKIP, Irept::from("nil"))],
+            },
+        ];
+
+        let unsupported = lower_intrinsics(&mut functions, cbmc_intrinsic);
+        assert_eq!(
+            unsupported.into_iter().collect::<Vec<String>>(),
+            vec!["__CPROVER_cover", "__CPROVER_foo"]
+        );
+
+        let main = &functions[0].instructions;
+        assert_eq!(main[0].instr_type, ASSUME);
+        assert_eq!(main[0].guard, condition);
+        assert_eq!(main[0].code.id, "nil");
+
+        assert_eq!(main[1].instr_type, ASSERT);
+        assert_eq!(main[1].guard.id, "typecast");
+        assert_eq!(main[1].guard.subt[0].id, "y");
+        assert_eq!(main[1].source_location.named_subt["comment"].id, "y is set");
+
+        assert_eq!(main[2].instr_type, SKIP);
+
+        // Everything else is kept as is
+        for call in &main[3..] {
+            assert_eq!(call.instr_type, FUNCTION_CALL);
+            assert_eq!(call.code.subt.len(), 3);
+        }
+        assert_eq!(main[3].code.subt[2].subt, vec![condition]);
+    }
+
+    #[test]
+    fn modelled_intrinsics() {
+        let int = Irept::bitvector("signedbv", 32);
+        let mut array = Irept::from("array");
+        array.subt.push(int.clone());
+        let a = Irept::symbol("main::a", array);
+        let mut element = Irept::typed("index", int.clone());
+        element.subt = vec![a, Irept::constant("0", int.clone())];
+        let mut dest = Irept::typed("address_of", Irept::pointer(int.clone(), 64));
+        dest.subt.push(element);
+        let void_pointer = Irept::pointer(Irept::from("empty"), 64);
+        let mut cast = Irept::typed("typecast", void_pointer.clone());
+        cast.subt.push(dest.clone());
+
+        let mut set = call("__CPROVER_array_set", vec![cast, Irept::constant("0", int)]);
+        set.target_number = 1;
+        let mut jump = instruction(CBMCInstructionKind::Goto as u32, Irept::from("nil"));
+        jump.targets = vec![Irept::from("1")];
+        let p = Irept::symbol("main::p", void_pointer.clone());
+        let mut symbols = vec![];
+        let mut functions = vec![CBMCFunction {
+            name: String::from("main"),
+            instructions: vec![set, call("__CPROVER_havoc_object", vec![p.clone()]), jump],
+        }];
+        model_intrinsics(&mut symbols, &mut functions, 64).unwrap();
+
+        use CBMCInstructionKind::*;
+        let main = &functions[0].instructions;
+        let kinds: Vec<CBMCInstructionKind> = main
+            .iter()
+            .map(|i| CBMCInstructionKind::from_u32(i.instr_type).unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                Decl, Assign, Decl, Assign, Goto, Assign, Assign, Goto, Skip, // array_set
+                Decl, Assign, Goto, Assign, Assign, Goto, Skip, // havoc_object
+                Goto
+            ]
+        );
+
+        // The array starts at dest, not at the void pointer it is cast to
+        assert_eq!(main[1].code.subt[1], dest);
+        let names: Vec<&str> = symbols.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "main::array_set::0::q",
+                "main::array_set::0::value",
+                "main::havoc_object::1::q"
+            ]
+        );
+        // Jumps to the call run the whole loop, which jumps to new targets
+        assert_eq!(main[0].target_number, 1);
+        assert_eq!(main[4].target_number, 2);
+        assert_eq!(main[4].targets, vec![Irept::from("3")]);
+        assert_eq!(main[4].guard.subt[0].id, "<=");
+        assert_eq!(main[7].targets, vec![Irept::from("2")]);
+        assert_eq!(main[8].target_number, 3);
+        assert_eq!(main[11].targets, vec![Irept::from("5")]);
+        assert_eq!(main[14].targets, vec![Irept::from("4")]);
+        assert_eq!(main[16].targets, vec![Irept::from("1")]);
+        // Every byte of the object is havocked
+        assert_eq!(main[12].code.subt[0].id, "dereference");
+        assert_eq!(
+            main[12].code.subt[1].named_subt["statement"],
+            Irept::from("nondet")
+        );
+
+        // Models need the elements of an array
+        let model = |arguments: Vec<Irept>| {
+            let mut functions = vec![CBMCFunction {
+                name: String::from("main"),
+                instructions: vec![call("__CPROVER_array_copy", arguments)],
+            }];
+            model_intrinsics(&mut vec![], &mut functions, 64)
+                .err()
+                .unwrap()
+        };
+        assert_eq!(
+            model(vec![p.clone(), p.clone()]),
+            "Function main: __CPROVER_array_copy expects a pointer into an array"
+        );
+        assert_eq!(
+            model(vec![p]),
+            "Function main: __CPROVER_array_copy expects 2 arguments"
+        );
+    }
+
+    #[test]
+    fn esbmc_intrinsics() {
+        let mut functions = vec![CBMCFunction {
+            name: String::from("__CPROVER__start"),
+            instructions: vec![
+                call(
+                    "c:@F@__ESBMC_assume",
+                    vec![Irept::typed("x", Irept::from("bool"))],
+                ),
+                call(
+                    "c:@F@__ESBMC_assert",
+                    vec![Irept::typed("x", Irept::from("bool"))],
+                ),
+                call("c:@F@__ESBMC_atomic_begin", vec![]),
+                call("c:@F@__ESBMC_yield", vec![]),
+                call("c:@F@__ESBMC_spawn_thread", vec![]),
+                call(
+                    "__CPROVER_assume",
+                    vec![Irept::typed("x", Irept::from("bool"))],
+                ),
+            ],
+        }];
+
+        let unsupported = lower_intrinsics(&mut functions, esbmc_intrinsic);
+        assert_eq!(
+            unsupported.into_iter().collect::<Vec<String>>(),
+            vec!["c:@F@__ESBMC_spawn_thread"]
+        );
+        let kinds: Vec<u32> = functions[0]
+            .instructions
+            .iter()
+            .map(|i| i.instr_type)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ASSUME,
+                ASSERT,
+                CBMCInstructionKind::AtomicBegin as u32,
+                SKIP,
+                FUNCTION_CALL,
+                FUNCTION_CALL
+            ]
+        );
+        assert!(!functions[0].instructions[1]
+            .source_location
+            .named_subt
+            .contains_key("comment"));
+    }
+}
@@ -1,8 +1,7 @@
 use json::object;
 use json::JsonValue;
 use std::collections::HashMap;
-use std::collections::HashSet;
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Irept {
     // TODO: This should be references and not copies!
     pub id: String,
@@ -15,31 +14,177 @@ impl Irept {
     pub fn get_nil() -> Self {
         Irept::from("nil")
     }
+
+    // Expression `id` of type `stype`
+    pub fn typed(id: &str, stype: Irept) -> Self {
+        let mut result = Irept::from(id);
+        result.named_subt.insert(String::from("type"), stype);
+        result
+    }
+
+    pub fn symbol(identifier: &str, stype: Irept) -> Self {
+        let mut result = Irept::typed("symbol", stype);
+        result
+            .named_subt
+            .insert(String::from("identifier"), Irept::from(identifier));
+        result
+    }
+
+    pub fn constant(value: &str, stype: Irept) -> Self {
+        let mut result = Irept::typed("constant", stype);
+        result
+            .named_subt
+            .insert(String::from("value"), Irept::from(value));
+        result
+    }
+
+    pub fn boolean(value: bool) -> Self {
+        Irept::constant(&value.to_string(), Irept::from("bool"))
+    }
+
+    // Bit-vector type `id` (e.g. signedbv or unsignedbv) of `width` bits
+    pub fn bitvector(id: &str, width: u64) -> Self {
+        let mut result = Irept::from(id);
+        result
+            .named_subt
+            .insert(String::from("width"), Irept::from(width.to_string()));
+        result
+    }
+
+    pub fn pointer(subtype: Irept, width: u64) -> Self {
+        let mut result = Irept::bitvector("pointer", width);
+        result.subt.push(subtype);
+        result
+    }
+
+    pub fn code_type(parameters: Vec<Irept>, return_type: Irept) -> Self {
+        let mut result = Irept::from("code");
+        result.named_subt.insert(
+            String::from("parameters"),
+            Irept {
+                subt: parameters,
+                ..Default::default()
+            },
+        );
+        result
+            .named_subt
+            .insert(String::from("return_type"), return_type);
+        result
+    }
+
+    // Instruction code `statement` on `operands`
+    pub fn code(statement: &str, operands: Vec<Irept>) -> Self {
+        let mut result = Irept::typed("code", Irept::from("empty"));
+        result
+            .named_subt
+            .insert(String::from("statement"), Irept::from(statement));
+        result.subt = operands;
+        result
+    }
+
+    // CBMC keeps operands in the sub list while ESBMC keeps them in the
+    // "operands" named sub.
+    pub fn operands(&self) -> &[Irept] {
+        match self.named_subt.get("operands") {
+            Some(operands) => &operands.subt,
+            None => &self.subt,
+        }
+    }
+}
+
+// Keys are sorted so the output is stable and can be diffed
+fn sorted_object(map: &HashMap<String, Irept>) -> JsonValue {
+    let mut keys: Vec<&String> = map.keys().collect();
+    keys.sort();
+
+    let mut obj = JsonValue::new_object();
+    for k in keys {
+        obj[k.as_str()] = JsonValue::from(&map[k]);
+    }
+    obj
 }
 
 impl From<&Irept> for JsonValue {
     fn from(data: &Irept) -> Self {
         let mut obj = object! {id: data.id.clone()};
 
-        let mut sub_vec: Vec<JsonValue> = Vec::new();
-        for sub in &data.subt {
-            sub_vec.push(JsonValue::from(sub));
-        }
-        if sub_vec.len() > 0 {
-            obj["subt"] = JsonValue::from(sub_vec);
+        if !data.subt.is_empty() {
+            let sub_vec: Vec<JsonValue> = data.subt.iter().map(JsonValue::from).collect();
+            obj["sub"] = JsonValue::from(sub_vec);
         }
 
-        for (k, v) in &data.named_subt {
-            obj[k] = JsonValue::from(v);
+        if !data.named_subt.is_empty() {
+            obj["named_sub"] = sorted_object(&data.named_subt);
         }
 
-        for (k, v) in &data.comments {
-            obj[k] = JsonValue::from(v);
+        if !data.comments.is_empty() {
+            obj["comment"] = sorted_object(&data.comments);
         }
         obj
     }
 }
 
+fn named_from_json(value: &JsonValue, path: &str) -> Result<HashMap<String, Irept>, String> {
+    if !value.is_object() {
+        return Err(format!("{}: expected an object", path));
+    }
+    let mut result = HashMap::new();
+    for (k, v) in value.entries() {
+        result.insert(
+            k.to_string(),
+            Irept::from_json(v, &format!("{}.{}", path, k))?,
+        );
+    }
+    Ok(result)
+}
+
+impl Irept {
+    // Inverse of the JSON conversion. The path is used to point at the
+    // offending JSON value on errors (e.g. $.symbols[3].type.sub[0])
+    pub fn from_json(value: &JsonValue, path: &str) -> Result<Self, String> {
+        if !value.is_object() {
+            return Err(format!("{}: expected an irep object", path));
+        }
+
+        let mut result = Irept::default();
+        for (k, v) in value.entries() {
+            let field_path = format!("{}.{}", path, k);
+            match k {
+                "id" => {
+                    result.id = v
+                        .as_str()
+                        .ok_or(format!("{}: expected a string", field_path))?
+                        .to_string();
+                }
+                "sub" => {
+                    if !v.is_array() {
+                        return Err(format!("{}: expected an array", field_path));
+                    }
+                    for (i, sub) in v.members().enumerate() {
+                        let sub_path = format!("{}[{}]", field_path, i);
+                        result.subt.push(Irept::from_json(sub, &sub_path)?);
+                    }
+                }
+                "named_sub" => result.named_subt = named_from_json(v, &field_path)?,
+                "comment" => result.comments = named_from_json(v, &field_path)?,
+                _ => return Err(format!("{}: unknown irep field", field_path)),
+            }
+        }
+
+        if !value.has_key("id") {
+            return Err(format!("{}: missing id", path));
+        }
+        Ok(result)
+    }
+}
+
+impl TryFrom<&JsonValue> for Irept {
+    type Error = String;
+    fn try_from(value: &JsonValue) -> Result<Self, Self::Error> {
+        Irept::from_json(value, "$")
+    }
+}
+
 impl std::hash::Hash for Irept {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.id.hash(state);
@@ -67,20 +212,12 @@ impl PartialEq for Irept {
 }
 impl Eq for Irept {}
 
+// C-like text, use the JSON conversion to see every field
 impl std::fmt::Display for Irept {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let as_json = JsonValue::from(self);
-        write!(f, "{}", json::stringify_pretty(as_json, 4))
-    }
-}
-
-impl Default for Irept {
-    fn default() -> Self {
-        Irept {
-            id: String::from(""),
-            subt: Vec::new(),
-            named_subt: HashMap::new(),
-            comments: HashMap::new(),
+        match crate::pretty::is_type(self) {
+            true => write!(f, "{}", crate::pretty::type_text(self)),
+            false => write!(f, "{}", crate::pretty::expr(self)),
         }
     }
 }
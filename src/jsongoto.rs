@@ -0,0 +1,715 @@
+// This module is responsible to convert goto programs from/into JSON. The
+// schema is described at docs/Architecture.md
+
+use crate::adapter::{cbmc_to_esbmc, esbmc_to_cbmc};
+use crate::bytereader::ByteReader;
+use crate::bytewriter::ByteWriter;
+use crate::cbmc::{
+    CBMCFunction, CBMCInstruction, CBMCLayout, CBMCParseResult, CBMCSymbol, DEFAULT_ENTRYPOINT,
+    NO_TARGET_NUMBER,
+};
+use crate::esbmc::ESBMCParseResult;
+use crate::irep::Irept;
+use crate::rules::RuleSet;
+use json::object;
+use json::JsonValue;
+use log::trace;
+
+pub fn cbmc2json(input: &str, output: &str) -> Result<(), String> {
+    trace!("cbmc2json mode, {} {}", input, output);
+    let result = crate::cbmc::process_cbmc_file(input).map_err(|e| e.to_string())?;
+    write_json(&JsonValue::from(&result), output)
+}
+
+pub fn esbmc2json(input: &str, output: &str) -> Result<(), String> {
+    trace!("esbmc2json mode, {} {}", input, output);
+    let result = crate::esbmc::process_esbmc_file(input).map_err(|e| e.to_string())?;
+    write_json(&JsonValue::from(&result), output)
+}
+
+pub fn json2cbmc(
+    input: &str,
+    output: &str,
+    layout: CBMCLayout,
+    rules: &RuleSet,
+) -> Result<(), String> {
+    trace!("json2cbmc mode, {} {}", input, output);
+    let converted = match read_json_file(input)? {
+        JsonProgram::Cbmc(result) => result,
+        JsonProgram::Esbmc(result) => esbmc_to_cbmc(result, rules)?,
+    };
+    std::fs::remove_file(output).ok();
+    ByteWriter::write_cbmc_to_file(
+        converted.symbols_irep,
+        converted.functions_irep,
+        output,
+        layout,
+    )
+    .map_err(|e| format!("Could not write file {}: {}", output, e))
+}
+
+pub fn json2esbmc(
+    entrypoint: Option<&str>,
+    input: &str,
+    output: &str,
+    rules: &RuleSet,
+) -> Result<(), String> {
+    trace!("json2esbmc mode, {} {}", input, output);
+    let converted = match read_json_file(input)? {
+        JsonProgram::Cbmc(mut result) => {
+            result.entrypoint = result.find_entrypoint(entrypoint)?;
+            crate::architecture::print_esbmc_flags(&result.symbols_irep, output)?;
+            cbmc_to_esbmc(result, rules)?
+        }
+        JsonProgram::Esbmc(result) => result,
+    };
+    std::fs::remove_file(output).ok();
+    ByteWriter::write_to_file(converted.symbols_irep, converted.functions_irep, output)
+        .map_err(|e| format!("Could not write file {}: {}", output, e))
+}
+
+pub fn write_json(value: &JsonValue, output: &str) -> Result<(), String> {
+    std::fs::remove_file(output).ok();
+    std::fs::write(output, json::stringify_pretty(value.clone(), 4))
+        .map_err(|e| format!("Could not write file {}: {}", output, e))
+}
+
+//////////
+// CBMC //
+//////////
+
+impl From<&CBMCSymbol> for JsonValue {
+    fn from(data: &CBMCSymbol) -> Self {
+        let flags: Vec<&str> = data
+            .flag_list()
+            .iter()
+            .filter(|(_, set)| *set)
+            .map(|(name, _)| *name)
+            .collect();
+
+        object! {
+            name: data.name.clone(),
+            base_name: data.base_name.clone(),
+            pretty_name: data.pretty_name.clone(),
+            module: data.module.clone(),
+            mode: data.mode.clone(),
+            flags: flags,
+            type: JsonValue::from(&data.stype),
+            value: JsonValue::from(&data.value),
+            location: JsonValue::from(&data.location),
+        }
+    }
+}
+
+impl From<&CBMCInstruction> for JsonValue {
+    fn from(data: &CBMCInstruction) -> Self {
+        let target_number = match data.target_number {
+            NO_TARGET_NUMBER => JsonValue::Null,
+            n => JsonValue::from(n),
+        };
+        let targets: Vec<u32> = data.targets.iter().map(|t| t.id.parse().unwrap()).collect();
+
+        object! {
+            instr_type: data.instr_type,
+            target_number: target_number,
+            targets: targets,
+            labels: data.labels.clone(),
+            guard: JsonValue::from(&data.guard),
+            code: JsonValue::from(&data.code),
+            source_location: JsonValue::from(&data.source_location),
+            function: data.function.id.clone(),
+        }
+    }
+}
+
+impl From<&CBMCFunction> for JsonValue {
+    fn from(data: &CBMCFunction) -> Self {
+        let instructions: Vec<JsonValue> = data.instructions.iter().map(JsonValue::from).collect();
+        object! {
+            name: data.name.clone(),
+            instructions: instructions,
+        }
+    }
+}
+
+impl From<&CBMCParseResult> for JsonValue {
+    fn from(data: &CBMCParseResult) -> Self {
+        let symbols: Vec<JsonValue> = data.symbols_irep.iter().map(JsonValue::from).collect();
+        let functions: Vec<JsonValue> = data.functions_irep.iter().map(JsonValue::from).collect();
+        object! {
+            format: "cbmc",
+            version: data.layout.version,
+            symbols: symbols,
+            functions: functions,
+        }
+    }
+}
+
+///////////
+// ESBMC //
+///////////
+
+// ESBMC keeps symbols and instructions as plain ireps. The known fields are
+// moved into first-class JSON fields and whatever is left goes into "extra"
+// so nothing is lost.
+
+const ESBMC_SYMBOL_FLAGS: [&str; 7] = [
+    "is_type",
+    "is_macro",
+    "is_parameter",
+    "lvalue",
+    "static_lifetime",
+    "file_local",
+    "is_extern",
+];
+
+fn is_leaf(irep: &Irept) -> bool {
+    irep.subt.is_empty() && irep.named_subt.is_empty() && irep.comments.is_empty()
+}
+
+fn take_string(irep: &mut Irept, key: &str) -> Option<String> {
+    if !irep.named_subt.get(key).is_some_and(is_leaf) {
+        return None;
+    }
+    irep.named_subt.remove(key).map(|x| x.id)
+}
+
+fn take_irep(irep: &mut Irept, key: &str, obj: &mut JsonValue, field: &str) {
+    if let Some(value) = irep.named_subt.remove(key) {
+        obj[field] = JsonValue::from(&value);
+    }
+}
+
+fn add_extra(residual: &Irept, expected: &Irept, obj: &mut JsonValue) {
+    if residual != expected {
+        obj["extra"] = JsonValue::from(residual);
+    }
+}
+
+fn esbmc_symbol_to_json(symbol: &Irept) -> JsonValue {
+    let mut residual = symbol.clone();
+    let mut obj = JsonValue::new_object();
+
+    for key in ["name", "base_name", "module", "mode"] {
+        if let Some(value) = take_string(&mut residual, key) {
+            obj[key] = JsonValue::from(value);
+        }
+    }
+
+    let mut flags: Vec<&str> = Vec::new();
+    for flag in ESBMC_SYMBOL_FLAGS {
+        if residual
+            .named_subt
+            .get(flag)
+            .is_some_and(|x| x.id == "1" && is_leaf(x))
+        {
+            residual.named_subt.remove(flag);
+            flags.push(flag);
+        }
+    }
+    obj["flags"] = JsonValue::from(flags);
+
+    take_irep(&mut residual, "type", &mut obj, "type");
+    take_irep(&mut residual, "symvalue", &mut obj, "value");
+    take_irep(&mut residual, "location", &mut obj, "location");
+    add_extra(&residual, &Irept::default(), &mut obj);
+    obj
+}
+
+fn esbmc_instruction_to_json(instruction: &Irept) -> JsonValue {
+    let mut residual = instruction.clone();
+    let mut obj = JsonValue::new_object();
+
+    if let Some(typeid) = residual.named_subt.get("typeid") {
+        if let (true, Ok(instr_type)) = (is_leaf(typeid), typeid.id.parse::<u32>()) {
+            obj["instr_type"] = JsonValue::from(instr_type);
+            residual.named_subt.remove("typeid");
+        }
+    }
+
+    if let Some(targets) = residual.named_subt.remove("targets") {
+        let numbers: Vec<u32> = targets.subt.iter().map(|t| t.id.parse().unwrap()).collect();
+        obj["targets"] = JsonValue::from(numbers);
+    }
+
+    if let Some(labels) = residual.named_subt.remove("labels") {
+        let names: Vec<String> = labels.subt.into_iter().map(|l| l.id).collect();
+        obj["labels"] = JsonValue::from(names);
+    }
+
+    if let Some(function) = take_string(&mut residual, "function") {
+        obj["function"] = JsonValue::from(function);
+    }
+
+    take_irep(&mut residual, "guard", &mut obj, "guard");
+    take_irep(&mut residual, "code", &mut obj, "code");
+    take_irep(&mut residual, "location", &mut obj, "source_location");
+    add_extra(&residual, &Irept::default(), &mut obj);
+    obj
+}
+
+fn esbmc_function_to_json(name: &str, function: &Irept) -> JsonValue {
+    let instructions: Vec<JsonValue> = function
+        .subt
+        .iter()
+        .map(esbmc_instruction_to_json)
+        .collect();
+    let mut obj = object! {
+        name: name,
+        instructions: instructions,
+    };
+
+    let mut residual = function.clone();
+    residual.subt.clear();
+    add_extra(&residual, &Irept::from("goto-program"), &mut obj);
+    obj
+}
+
+impl From<&ESBMCParseResult> for JsonValue {
+    fn from(data: &ESBMCParseResult) -> Self {
+        let symbols: Vec<JsonValue> = data.symbols_irep.iter().map(esbmc_symbol_to_json).collect();
+        let functions: Vec<JsonValue> = data
+            .functions_irep
+            .iter()
+            .map(|(name, irep)| esbmc_function_to_json(name, irep))
+            .collect();
+        object! {
+            format: "esbmc",
+            version: 1,
+            symbols: symbols,
+            functions: functions,
+        }
+    }
+}
+
+////////////
+// PARSER //
+////////////
+
+pub enum JsonProgram {
+    Cbmc(CBMCParseResult),
+    Esbmc(ESBMCParseResult),
+}
+
+impl From<&JsonProgram> for JsonValue {
+    fn from(data: &JsonProgram) -> Self {
+        match data {
+            JsonProgram::Cbmc(result) => JsonValue::from(result),
+            JsonProgram::Esbmc(result) => JsonValue::from(result),
+        }
+    }
+}
+
+pub fn read_json_file(path: &str) -> Result<JsonProgram, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read file {}: {}", path, e))?;
+    let value = json::parse(&content).map_err(|e| format!("{}: {}", path, e))?;
+    parse_program(&value)
+}
+
+pub fn parse_program(value: &JsonValue) -> Result<JsonProgram, String> {
+    let format = get_string(value, "format", "$")?;
+    let version = get_u32(value, "version", "$")?;
+    let symbols = get_array(value, "symbols", "$")?;
+    let functions = get_array(value, "functions", "$")?;
+
+    match (format.as_str(), CBMCLayout::from_version(version)) {
+        ("cbmc", Some(layout)) => {
+            let mut result = CBMCParseResult {
+                reader: ByteReader::from(Vec::new()),
+                symbols_irep: Vec::with_capacity(symbols.len()),
+                functions_irep: Vec::with_capacity(functions.len()),
+                entrypoint: String::from(DEFAULT_ENTRYPOINT),
+                layout,
+            };
+            for (i, symbol) in symbols.iter().enumerate() {
+                let path = format!("$.symbols[{}]", i);
+                result
+                    .symbols_irep
+                    .push(cbmc_symbol_from_json(symbol, &path)?);
+            }
+            for (i, function) in functions.iter().enumerate() {
+                let path = format!("$.functions[{}]", i);
+                result
+                    .functions_irep
+                    .push(cbmc_function_from_json(function, &path)?);
+            }
+            Ok(JsonProgram::Cbmc(result))
+        }
+        ("esbmc", _) if version == 1 => {
+            let mut result = ESBMCParseResult {
+                reader: ByteReader::from(Vec::new()),
+                symbols_irep: Vec::with_capacity(symbols.len()),
+                functions_irep: Vec::with_capacity(functions.len()),
+            };
+            for (i, symbol) in symbols.iter().enumerate() {
+                let path = format!("$.symbols[{}]", i);
+                result
+                    .symbols_irep
+                    .push(esbmc_symbol_from_json(symbol, &path)?);
+            }
+            for (i, function) in functions.iter().enumerate() {
+                let path = format!("$.functions[{}]", i);
+                result
+                    .functions_irep
+                    .push(esbmc_function_from_json(function, &path)?);
+            }
+            Ok(JsonProgram::Esbmc(result))
+        }
+        _ => Err(format!(
+            "$: unsupported format {} version {}",
+            format, version
+        )),
+    }
+}
+
+fn get_field<'a>(value: &'a JsonValue, key: &str, path: &str) -> Result<&'a JsonValue, String> {
+    if !value.is_object() {
+        return Err(format!("{}: expected an object", path));
+    }
+    if !value.has_key(key) {
+        return Err(format!("{}: missing field {}", path, key));
+    }
+    Ok(&value[key])
+}
+
+fn get_string(value: &JsonValue, key: &str, path: &str) -> Result<String, String> {
+    get_field(value, key, path)?
+        .as_str()
+        .map(String::from)
+        .ok_or(format!("{}.{}: expected a string", path, key))
+}
+
+fn get_u32(value: &JsonValue, key: &str, path: &str) -> Result<u32, String> {
+    get_field(value, key, path)?
+        .as_u32()
+        .ok_or(format!("{}.{}: expected an unsigned number", path, key))
+}
+
+fn get_array<'a>(
+    value: &'a JsonValue,
+    key: &str,
+    path: &str,
+) -> Result<Vec<&'a JsonValue>, String> {
+    let field = get_field(value, key, path)?;
+    if !field.is_array() {
+        return Err(format!("{}.{}: expected an array", path, key));
+    }
+    Ok(field.members().collect())
+}
+
+fn get_strings(value: &JsonValue, key: &str, path: &str) -> Result<Vec<String>, String> {
+    let mut result = Vec::new();
+    for (i, v) in get_array(value, key, path)?.iter().enumerate() {
+        let s = v
+            .as_str()
+            .ok_or(format!("{}.{}[{}]: expected a string", path, key, i))?;
+        result.push(String::from(s));
+    }
+    Ok(result)
+}
+
+fn get_u32s(value: &JsonValue, key: &str, path: &str) -> Result<Vec<u32>, String> {
+    let mut result = Vec::new();
+    for (i, v) in get_array(value, key, path)?.iter().enumerate() {
+        let n = v.as_u32().ok_or(format!(
+            "{}.{}[{}]: expected an unsigned number",
+            path, key, i
+        ))?;
+        result.push(n);
+    }
+    Ok(result)
+}
+
+fn get_irep(value: &JsonValue, key: &str, path: &str) -> Result<Irept, String> {
+    Irept::from_json(get_field(value, key, path)?, &format!("{}.{}", path, key))
+}
+
+fn cbmc_symbol_from_json(value: &JsonValue, path: &str) -> Result<CBMCSymbol, String> {
+    let mut result = CBMCSymbol::default();
+    result.name = get_string(value, "name", path)?;
+    result.base_name = get_string(value, "base_name", path)?;
+    result.pretty_name = get_string(value, "pretty_name", path)?;
+    result.module = get_string(value, "module", path)?;
+    result.mode = get_string(value, "mode", path)?;
+    result.stype = get_irep(value, "type", path)?;
+    result.value = get_irep(value, "value", path)?;
+    result.location = get_irep(value, "location", path)?;
+
+    for (i, flag) in get_strings(value, "flags", path)?.iter().enumerate() {
+        if !result.set_flag(flag, true) {
+            return Err(format!("{}.flags[{}]: unknown flag {}", path, i, flag));
+        }
+    }
+    result.flags = result.pack_flags();
+    Ok(result)
+}
+
+fn cbmc_function_from_json(value: &JsonValue, path: &str) -> Result<CBMCFunction, String> {
+    let name = get_string(value, "name", path)?;
+    let mut instructions = Vec::new();
+    for (i, instr) in get_array(value, "instructions", path)?.iter().enumerate() {
+        let instr_path = format!("{}.instructions[{}]", path, i);
+        // Older dumps do not have it, instructions belong to their function
+        let function = match instr.has_key("function") {
+            true => Irept::from(get_string(instr, "function", &instr_path)?),
+            false => Irept::from(&name),
+        };
+        let target_number = match get_field(instr, "target_number", &instr_path)? {
+            JsonValue::Null => NO_TARGET_NUMBER,
+            _ => get_u32(instr, "target_number", &instr_path)?,
+        };
+
+        instructions.push(CBMCInstruction {
+            code: get_irep(instr, "code", &instr_path)?,
+            source_location: get_irep(instr, "source_location", &instr_path)?,
+            instr_type: get_u32(instr, "instr_type", &instr_path)?,
+            guard: get_irep(instr, "guard", &instr_path)?,
+            target_number,
+            targets: get_u32s(instr, "targets", &instr_path)?
+                .iter()
+                .map(|t| Irept::from(t.to_string()))
+                .collect(),
+            labels: get_strings(instr, "labels", &instr_path)?,
+            function,
+        });
+    }
+
+    // Hand-edited programs might jump into nowhere
+    for (i, instr) in instructions.iter().enumerate() {
+        for (j, target) in instr.targets.iter().enumerate() {
+            let number: u32 = target.id.parse().unwrap();
+            if !instructions.iter().any(|x| x.target_number == number) {
+                return Err(format!(
+                    "{}.instructions[{}].targets[{}]: no instruction has target_number {}",
+                    path, i, j, number
+                ));
+            }
+        }
+    }
+
+    Ok(CBMCFunction { name, instructions })
+}
+
+fn get_extra(value: &JsonValue, path: &str, default: Irept) -> Result<Irept, String> {
+    match value.has_key("extra") {
+        true => get_irep(value, "extra", path),
+        false => Ok(default),
+    }
+}
+
+fn esbmc_symbol_from_json(value: &JsonValue, path: &str) -> Result<Irept, String> {
+    let mut result = get_extra(value, path, Irept::default())?;
+    for key in ["name", "base_name", "module", "mode"] {
+        if value.has_key(key) {
+            result
+                .named_subt
+                .insert(key.to_string(), Irept::from(get_string(value, key, path)?));
+        }
+    }
+
+    for (i, flag) in get_strings(value, "flags", path)?.iter().enumerate() {
+        if !ESBMC_SYMBOL_FLAGS.contains(&flag.as_str()) {
+            return Err(format!("{}.flags[{}]: unknown flag {}", path, i, flag));
+        }
+        result.named_subt.insert(flag.clone(), Irept::from("1"));
+    }
+
+    for (field, key) in [
+        ("type", "type"),
+        ("value", "symvalue"),
+        ("location", "location"),
+    ] {
+        if value.has_key(field) {
+            result
+                .named_subt
+                .insert(key.to_string(), get_irep(value, field, path)?);
+        }
+    }
+    Ok(result)
+}
+
+fn esbmc_function_from_json(value: &JsonValue, path: &str) -> Result<(String, Irept), String> {
+    let name = get_string(value, "name", path)?;
+    let mut result = get_extra(value, path, Irept::from("goto-program"))?;
+    let instructions = get_array(value, "instructions", path)?;
+
+    for (i, instr) in instructions.iter().enumerate() {
+        let instr_path = format!("{}.instructions[{}]", path, i);
+        let mut irep = get_extra(instr, &instr_path, Irept::default())?;
+
+        if instr.has_key("instr_type") {
+            let instr_type = get_u32(instr, "instr_type", &instr_path)?;
+            irep.named_subt
+                .insert("typeid".to_string(), Irept::from(instr_type.to_string()));
+        }
+
+        if instr.has_key("targets") {
+            let mut targets = Irept::default();
+            for (j, t) in get_u32s(instr, "targets", &instr_path)?.iter().enumerate() {
+                if *t as usize >= instructions.len() {
+                    return Err(format!(
+                        "{}.targets[{}]: offset {} is out of the function",
+                        instr_path, j, t
+                    ));
+                }
+                targets.subt.push(Irept::from(t.to_string()));
+            }
+            irep.named_subt.insert("targets".to_string(), targets);
+        }
+
+        if instr.has_key("labels") {
+            let mut labels = Irept::default();
+            for label in get_strings(instr, "labels", &instr_path)? {
+                labels.subt.push(Irept::from(label));
+            }
+            irep.named_subt.insert("labels".to_string(), labels);
+        }
+
+        if instr.has_key("function") {
+            let function = get_string(instr, "function", &instr_path)?;
+            irep.named_subt
+                .insert("function".to_string(), Irept::from(function));
+        }
+
+        for (field, key) in [
+            ("guard", "guard"),
+            ("code", "code"),
+            ("source_location", "location"),
+        ] {
+            if instr.has_key(field) {
+                irep.named_subt
+                    .insert(key.to_string(), get_irep(instr, field, &instr_path)?);
+            }
+        }
+        result.subt.push(irep);
+    }
+    Ok((name, result))
+}
+
+///////////
+// TESTS //
+///////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::get_test_path;
+
+    #[test]
+    fn irep_schema() {
+        let mut irep = Irept::from("constant");
+        irep.subt.push(Irept::from("a"));
+        irep.named_subt
+            .insert("value".to_string(), Irept::from("2A"));
+        irep.named_subt
+            .insert("#location".to_string(), Irept::from("l"));
+        irep.comments
+            .insert("#location".to_string(), Irept::from("c"));
+
+        let obj = JsonValue::from(&irep);
+        assert_eq!(obj["id"], "constant");
+        assert_eq!(obj["sub"][0]["id"], "a");
+        assert_eq!(obj["named_sub"]["value"]["id"], "2A");
+        assert_eq!(obj["named_sub"]["#location"]["id"], "l");
+        assert_eq!(obj["comment"]["#location"]["id"], "c");
+        assert!(!JsonValue::from(&Irept::from("a")).has_key("sub"));
+    }
+
+    #[test]
+    fn cbmc_json() {
+        let result = crate::cbmc::process_cbmc_file(&get_test_path("hello-gb.goto")).unwrap();
+        let obj = JsonValue::from(&result);
+        assert_eq!(obj["format"], "cbmc");
+
+        let main = obj["functions"]
+            .members()
+            .find(|f| f["name"] == "main")
+            .unwrap();
+        assert_eq!(main["instructions"][1]["instr_type"], 1);
+        assert_eq!(main["instructions"][1]["targets"][0], 1);
+        assert_eq!(main["instructions"][2]["target_number"], 1);
+        assert!(main["instructions"][0]["target_number"].is_null());
+    }
+
+    #[test]
+    fn cbmc_json_roundtrip() {
+        let result = crate::cbmc::process_cbmc_file(&get_test_path("hello-gb.goto")).unwrap();
+        let obj = json::parse(&json::stringify(JsonValue::from(&result))).unwrap();
+        let parsed = match parse_program(&obj).unwrap() {
+            JsonProgram::Cbmc(parsed) => parsed,
+            JsonProgram::Esbmc(_) => panic!("Expected a CBMC program"),
+        };
+
+        for (lhs, rhs) in result.symbols_irep.iter().zip(&parsed.symbols_irep) {
+            assert_eq!(lhs.name, rhs.name);
+            assert_eq!(lhs.flags, rhs.flags);
+            assert_eq!(lhs.stype, rhs.stype);
+            assert_eq!(lhs.value, rhs.value);
+            assert_eq!(lhs.location, rhs.location);
+        }
+        for (lhs, rhs) in result.functions_irep.iter().zip(&parsed.functions_irep) {
+            for (l, r) in lhs.instructions.iter().zip(&rhs.instructions) {
+                assert_eq!(l.code, r.code);
+                assert_eq!(l.guard, r.guard);
+                assert_eq!(l.target_number, r.target_number);
+                assert_eq!(l.targets, r.targets);
+                assert_eq!(l.labels, r.labels);
+            }
+        }
+    }
+
+    #[test]
+    fn esbmc_json_roundtrip() {
+        let result = crate::esbmc::process_esbmc_file(&get_test_path("hello.goto")).unwrap();
+        let obj = JsonValue::from(&result);
+        let parsed = match parse_program(&obj).unwrap() {
+            JsonProgram::Esbmc(parsed) => parsed,
+            JsonProgram::Cbmc(_) => panic!("Expected an ESBMC program"),
+        };
+        assert_eq!(result.symbols_irep, parsed.symbols_irep);
+        assert_eq!(result.functions_irep, parsed.functions_irep);
+    }
+
+    #[test]
+    fn json_errors() {
+        let result = crate::esbmc::process_esbmc_file(&get_test_path("hello.goto")).unwrap();
+        let mut obj = JsonValue::from(&result);
+        obj["functions"][3]["instructions"][0]["code"]["named_sub"]["type"] = JsonValue::from(42);
+        let err = parse_program(&obj).err().unwrap();
+        assert_eq!(
+            err,
+            "$.functions[3].instructions[0].code.named_sub.type: expected an irep object"
+        );
+
+        obj["functions"][3]["instructions"][0]["targets"] = json::array![100];
+        let err = parse_program(&obj).err().unwrap();
+        assert!(err.starts_with("$.functions[3].instructions[0].targets[0]"));
+
+        obj["symbols"][0]["flags"] = json::array!["is_weird"];
+        let err = parse_program(&obj).err().unwrap();
+        assert_eq!(err, "$.symbols[0].flags[0]: unknown flag is_weird");
+    }
+
+    #[test]
+    fn esbmc_json() {
+        let result = crate::esbmc::process_esbmc_file(&get_test_path("hello.goto")).unwrap();
+        let obj = JsonValue::from(&result);
+        assert_eq!(obj["format"], "esbmc");
+
+        let main = obj["symbols"]
+            .members()
+            .find(|s| s["name"] == "c:@F@main")
+            .unwrap();
+        assert_eq!(main["base_name"], "main");
+        assert!(main["flags"].contains("lvalue"));
+        assert!(!main.has_key("extra"));
+
+        for function in obj["functions"].members() {
+            for instruction in function["instructions"].members() {
+                assert!(instruction.has_key("instr_type"));
+                assert!(!instruction.has_key("extra"));
+            }
+        }
+    }
+}
@@ -1,18 +1,35 @@
+#![allow(clippy::field_reassign_with_default)]
+
 mod adapter;
+mod architecture;
 mod bytereader;
+mod bytes;
 mod bytewriter;
 mod cbmc;
+mod contracts;
+mod convert;
 mod esbmc;
+mod heap;
+mod inspect;
+mod intrinsics;
 mod irep;
+mod jsongoto;
+mod pretty;
+#[cfg(test)]
 mod resources;
+mod rules;
+mod threads;
 
 pub use adapter::cbmc2esbmc;
+pub use adapter::esbmc2cbmc;
 pub use bytereader::ByteReader;
 pub use bytewriter::ByteWriter;
-use esbmc::ESBMCParseResult;
+pub use convert::convert;
+pub use inspect::inspect;
 pub use irep::Irept;
+pub use jsongoto::{cbmc2json, esbmc2json, json2cbmc, json2esbmc};
 
-use log::trace;
+use log::{error, trace};
 
 use clap::{Args, Parser, Subcommand};
 
@@ -31,46 +48,163 @@ fn init() {
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// JSON file with extra rewrite rules, which take precedence over the
+    /// built-in ones (see docs/Development.md)
+    #[arg(long, global = true)]
+    rules: Option<std::path::PathBuf>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Converts CBMC <INPUT> into ESBMC <OUTPUT>
-    CBMC2ESBMC(CmdArgs),
+    CBMC2ESBMC(EntrypointArgs),
     /// Converts CBMC <INPUT> into JSON <OUTPUT>
     CBMC2JSON(CmdArgs),
     /// Converts ESBMC <INPUT> into CBMC <OUTPUT>
-    ESBMC2CBMC(CmdArgs),
+    ESBMC2CBMC(CBMCOutputArgs),
     /// Converts ESBMC <INPUT> into JSON <OUTPUT>
     ESBMC2JSON(CmdArgs),
     /// Converts JSON <INPUT> into CBMC <OUTPUT>
-    JSON2CBMC(CmdArgs),
+    JSON2CBMC(CBMCOutputArgs),
     /// Converts JSON <INPUT> into ESBMC <OUTPUT>
-    JSON2ESBMC(CmdArgs),
+    JSON2ESBMC(EntrypointArgs),
+    /// Converts <INPUT> into <OUTPUT>, detecting the format of <INPUT>
+    Convert(ConvertArgs),
+    /// Prints the symbols and functions of <INPUT>
+    Inspect(InspectArgs),
 }
 
 #[derive(Args)]
 struct CmdArgs {
-    entrypoint: String,
     input: std::path::PathBuf,
     output: std::path::PathBuf,
 }
 
+#[derive(Args)]
+struct EntrypointArgs {
+    #[command(flatten)]
+    args: CmdArgs,
+    /// Function of a CBMC <INPUT> to use as entrypoint. Defaults to
+    /// __CPROVER__start or to the only Kani harness of <INPUT>
+    #[arg(long)]
+    entrypoint: Option<String>,
+}
+
+#[derive(Args)]
+struct CBMCOutputArgs {
+    #[command(flatten)]
+    args: CmdArgs,
+    /// Goto binary version of the CBMC <OUTPUT>
+    #[arg(long, default_value_t = cbmc::DEFAULT_CBMC_VERSION, value_parser = cbmc_layout)]
+    target_version: u32,
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    #[command(flatten)]
+    args: CmdArgs,
+    /// Format of <OUTPUT>: cbmc, esbmc or json
+    #[arg(long)]
+    to: bytereader::GotoFormat,
+    /// Function of a CBMC <INPUT> to use as entrypoint. Defaults to
+    /// __CPROVER__start or to the only Kani harness of <INPUT>
+    #[arg(long)]
+    entrypoint: Option<String>,
+    /// Goto binary version of a CBMC <OUTPUT>
+    #[arg(long, default_value_t = cbmc::DEFAULT_CBMC_VERSION, value_parser = cbmc_layout)]
+    target_version: u32,
+}
+
+#[derive(Args)]
+struct InspectArgs {
+    input: std::path::PathBuf,
+    /// Only print the function with this name
+    #[arg(long)]
+    function: Option<String>,
+    /// Only print the symbols whose name matches this regex
+    #[arg(long)]
+    symbol: Option<String>,
+}
+
+fn cbmc_layout(version: &str) -> Result<u32, String> {
+    version
+        .parse()
+        .ok()
+        .filter(|v| cbmc::CBMCLayout::from_version(*v).is_some())
+        .ok_or(format!(
+            "supported versions are {:?}",
+            cbmc::SUPPORTED_CBMC_VERSIONS
+        ))
+}
+
 fn main() {
     init();
     trace!("Starting goto-transcoder");
     let cli = Cli::parse();
 
-    match cli.command {
-        Commands::CBMC2ESBMC(args) => {
-            cbmc2esbmc(
-                &args.entrypoint,
-                &args.input.to_str().unwrap(),
-                args.output.to_str().unwrap(),
-            );
+    let input = |args: &CmdArgs| String::from(args.input.to_str().unwrap());
+    let output = |args: &CmdArgs| String::from(args.output.to_str().unwrap());
+    let layout = |version: u32| cbmc::CBMCLayout::from_version(version).unwrap();
+    let rules = match &cli.rules {
+        Some(path) => rules::RuleSet::load(path.to_str().unwrap()),
+        None => Ok(rules::RuleSet::default()),
+    };
+    let rules = rules.unwrap_or_else(|err| {
+        error!("{}", err);
+        std::process::exit(1);
+    });
+
+    let result = match cli.command {
+        Commands::CBMC2ESBMC(EntrypointArgs { args, entrypoint }) => {
+            cbmc2esbmc(entrypoint.as_deref(), &input(&args), &output(&args), &rules)
         }
-        _ => panic!("Command not implemented yet"),
+        Commands::ESBMC2CBMC(CBMCOutputArgs {
+            args,
+            target_version,
+        }) => esbmc2cbmc(
+            &input(&args),
+            &output(&args),
+            layout(target_version),
+            &rules,
+        ),
+        Commands::CBMC2JSON(args) => cbmc2json(&input(&args), &output(&args)),
+        Commands::ESBMC2JSON(args) => esbmc2json(&input(&args), &output(&args)),
+        Commands::JSON2CBMC(CBMCOutputArgs {
+            args,
+            target_version,
+        }) => json2cbmc(
+            &input(&args),
+            &output(&args),
+            layout(target_version),
+            &rules,
+        ),
+        Commands::JSON2ESBMC(EntrypointArgs { args, entrypoint }) => {
+            json2esbmc(entrypoint.as_deref(), &input(&args), &output(&args), &rules)
+        }
+        Commands::Convert(ConvertArgs {
+            args,
+            to,
+            entrypoint,
+            target_version,
+        }) => convert(
+            entrypoint.as_deref(),
+            &input(&args),
+            &output(&args),
+            to,
+            layout(target_version),
+            &rules,
+        ),
+        Commands::Inspect(args) => inspect(
+            args.input.to_str().unwrap(),
+            args.function.as_deref(),
+            args.symbol.as_deref(),
+        ),
     };
 
+    if let Err(err) = result {
+        error!("{}", err);
+        std::process::exit(1);
+    }
+
     trace!("Done");
 }
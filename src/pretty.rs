@@ -0,0 +1,554 @@
+// This module is responsible to render ireps as C-like text, for humans.
+// Both CBMC (operands in sub, subtypes in sub[0], hex constants) and ESBMC
+// (operands and subtype as named subs, binary constants) ireps are accepted.
+
+use crate::irep::Irept;
+
+// Compact s-expression: (id operands... :name value ...). Types and comments
+// are skipped as they are mostly noise when reading a program.
+pub fn sexpr(irep: &Irept) -> String {
+    let operands = irep.operands();
+    let mut named: Vec<(&String, &Irept)> = irep
+        .named_subt
+        .iter()
+        .filter(|(k, _)| !k.starts_with('#') && !["operands", "type"].contains(&k.as_str()))
+        .collect();
+    if operands.is_empty() && named.is_empty() {
+        return irep.id.clone();
+    }
+    named.sort_by_key(|(k, _)| *k);
+
+    let mut parts = vec![irep.id.clone()];
+    parts.extend(operands.iter().map(sexpr));
+    for (k, v) in named {
+        parts.push(format!(":{} {}", k, sexpr(v)));
+    }
+    format!("({})", parts.join(" "))
+}
+
+fn named<'a>(irep: &'a Irept, name: &str) -> Option<&'a Irept> {
+    irep.named_subt.get(name)
+}
+
+fn named_id<'a>(irep: &'a Irept, name: &str) -> &'a str {
+    named(irep, name).map_or("", |x| x.id.as_str())
+}
+
+pub fn width(irep: &Irept) -> Option<usize> {
+    named_id(irep, "width").parse().ok()
+}
+
+// Pointers and arrays keep their subtype in sub[0] in CBMC
+fn subtype(irep: &Irept) -> Option<&Irept> {
+    named(irep, "subtype").or(irep.subt.first())
+}
+
+///////////
+// TYPES //
+///////////
+
+const TYPE_IDS: [&str; 17] = [
+    "signedbv",
+    "unsignedbv",
+    "bool",
+    "empty",
+    "pointer",
+    "array",
+    "struct",
+    "struct_tag",
+    "union",
+    "union_tag",
+    "floatbv",
+    "fixedbv",
+    "c_bool",
+    "c_enum",
+    "c_enum_tag",
+    "code",
+    "incomplete_array",
+];
+
+pub fn is_type(irep: &Irept) -> bool {
+    TYPE_IDS.contains(&irep.id.as_str()) && named(irep, "statement").is_none()
+}
+
+fn components_text(irep: &Irept) -> String {
+    let components = named(irep, "components").map_or(&[][..], |c| &c.subt[..]);
+    let fields: Vec<String> = components
+        .iter()
+        .map(|c| {
+            format!(
+                "{} {};",
+                type_text(named(c, "type").unwrap_or(c)),
+                named_id(c, "name")
+            )
+        })
+        .collect();
+    format!("{{ {} }}", fields.join(" "))
+}
+
+// Renders a type, following the names used by --show-goto-functions
+pub fn type_text(irep: &Irept) -> String {
+    match irep.id.as_str() {
+        "signedbv" | "unsignedbv" | "floatbv" | "fixedbv" | "c_bool" => match width(irep) {
+            Some(w) => format!("{}[{}]", irep.id, w),
+            None => irep.id.clone(),
+        },
+        "bool" | "empty" => irep.id.clone(),
+        "pointer" => match subtype(irep) {
+            Some(sub) => format!("{} *", type_text(sub)),
+            None => String::from("pointer"),
+        },
+        "array" | "incomplete_array" => {
+            let element = subtype(irep).map_or(String::from("?"), type_text);
+            match named(irep, "size") {
+                Some(size) => format!("{}[{}]", element, expr(size)),
+                None => format!("{}[]", element),
+            }
+        }
+        "struct" | "union" => match named(irep, "tag") {
+            Some(tag) if !tag.id.is_empty() => format!("{} {}", irep.id, tag.id),
+            _ => format!("{} {}", irep.id, components_text(irep)),
+        },
+        "struct_tag" | "union_tag" | "c_enum_tag" => format!(
+            "{} {}",
+            irep.id.trim_end_matches("_tag"),
+            named_id(irep, "identifier")
+        ),
+        "c_enum" => match subtype(irep) {
+            Some(sub) => format!("c_enum({})", type_text(sub)),
+            None => String::from("c_enum"),
+        },
+        "code" => {
+            // CBMC calls them parameters and ESBMC arguments
+            let parameters = named(irep, "parameters")
+                .or(named(irep, "arguments"))
+                .map_or(&[][..], |p| &p.subt[..]);
+            let parameters: Vec<String> = parameters
+                .iter()
+                .map(|p| type_text(named(p, "type").unwrap_or(p)))
+                .collect();
+            let return_type = named(irep, "return_type").map_or(String::from("?"), type_text);
+            format!("{} ({})", return_type, parameters.join(", "))
+        }
+        // ESBMC refers to tags with symbol types
+        "symbol" if named(irep, "identifier").is_some() => named_id(irep, "identifier").to_string(),
+        _ => sexpr(irep),
+    }
+}
+
+///////////////
+// CONSTANTS //
+///////////////
+
+// ESBMC keeps every bit while CBMC keeps hexadecimal digits without leading
+// zeros. A value with as many digits as the width can only be binary.
+fn constant_bits(value: &str, width: usize) -> Option<Vec<bool>> {
+    if value.len() == width && value.chars().all(|c| c == '0' || c == '1') {
+        return Some(value.chars().map(|c| c == '1').collect());
+    }
+    let mut bits = Vec::with_capacity(value.len() * 4);
+    for c in value.chars() {
+        let digit = c.to_digit(16)?;
+        bits.extend((0..4).rev().map(|i| digit & (1 << i) != 0));
+    }
+    // Keep the lowest bits
+    let excess = bits.len().saturating_sub(width);
+    if bits[..excess].iter().any(|b| *b) {
+        return None;
+    }
+    let mut bits = bits.split_off(excess);
+    let mut padded = vec![false; width - bits.len()];
+    padded.append(&mut bits);
+    Some(padded)
+}
+
+fn bits_to_u128(bits: &[bool]) -> u128 {
+    bits.iter().fold(0, |acc, b| (acc << 1) | (*b as u128))
+}
+
+fn bits_to_hex(bits: &[bool]) -> String {
+    let padding = vec![false; (4 - bits.len() % 4) % 4];
+    let padded: Vec<bool> = padding.into_iter().chain(bits.iter().copied()).collect();
+    let hex: String = padded
+        .chunks(4)
+        .map(|nibble| char::from_digit(bits_to_u128(nibble) as u32, 16).unwrap())
+        .collect();
+    format!("0x{}", hex)
+}
+
+fn integer_text(bits: &[bool], signed: bool) -> String {
+    if bits.len() > 128 {
+        return bits_to_hex(bits);
+    }
+    let value = bits_to_u128(bits);
+    if signed && bits.first() == Some(&true) {
+        // Two's complement, computed on the magnitude to support 128 bits
+        let magnitude = (!value).wrapping_add(1) & (u128::MAX >> (128 - bits.len()));
+        return format!("-{}", magnitude);
+    }
+    value.to_string()
+}
+
+pub fn constant_text(irep: &Irept) -> String {
+    let value = named_id(irep, "value");
+    let ctype = named(irep, "type").cloned().unwrap_or_default();
+    let bits = width(&ctype).and_then(|w| constant_bits(value, w));
+
+    match (ctype.id.as_str(), bits) {
+        ("bool", _) => value.to_string(),
+        ("pointer", _) if value == "NULL" => String::from("NULL"),
+        ("pointer", Some(bits)) if bits.iter().all(|b| !b) => String::from("NULL"),
+        ("signedbv", Some(bits)) => integer_text(&bits, true),
+        ("unsignedbv" | "c_bool" | "c_enum" | "c_enum_tag" | "pointer", Some(bits)) => {
+            integer_text(&bits, false)
+        }
+        ("floatbv", Some(bits)) if bits.len() == 32 => {
+            f32::from_bits(bits_to_u128(&bits) as u32).to_string()
+        }
+        ("floatbv", Some(bits)) if bits.len() == 64 => {
+            f64::from_bits(bits_to_u128(&bits) as u64).to_string()
+        }
+        _ => value.to_string(),
+    }
+}
+
+/////////////////
+// EXPRESSIONS //
+/////////////////
+
+// C operator and its precedence, higher binds tighter
+fn binary_operator(id: &str) -> Option<(&'static str, u8)> {
+    Some(match id {
+        "*" => ("*", 13),
+        "/" => ("/", 13),
+        "mod" => ("%", 13),
+        "+" => ("+", 12),
+        "-" => ("-", 12),
+        "shl" => ("<<", 11),
+        "ashr" | "lshr" => (">>", 11),
+        "<" => ("<", 10),
+        "<=" => ("<=", 10),
+        ">" => (">", 10),
+        ">=" => (">=", 10),
+        "=" => ("==", 9),
+        "notequal" => ("!=", 9),
+        "bitand" => ("&", 8),
+        "bitxor" => ("^", 7),
+        "bitor" => ("|", 6),
+        "and" => ("&&", 5),
+        "or" => ("||", 4),
+        "implies" => ("==>", 3),
+        _ => return None,
+    })
+}
+
+fn unary_operator(id: &str) -> Option<&'static str> {
+    Some(match id {
+        "not" => "!",
+        "unary-" => "-",
+        "bitnot" => "~",
+        "dereference" => "*",
+        "address_of" => "&",
+        _ => return None,
+    })
+}
+
+const PRIMARY: u8 = 16;
+const POSTFIX: u8 = 15;
+const UNARY: u8 = 14;
+const TERNARY: u8 = 2;
+
+fn precedence(irep: &Irept) -> u8 {
+    let id = irep.id.as_str();
+    if let Some((_, p)) = binary_operator(id) {
+        return p;
+    }
+    match id {
+        "member" | "index" => POSTFIX,
+        "typecast" => UNARY,
+        "if" => TERNARY,
+        _ if unary_operator(id).is_some() => UNARY,
+        _ => PRIMARY,
+    }
+}
+
+// Adds parenthesis when the operand binds looser than its parent
+fn operand_text(irep: &Irept, parent: u8) -> String {
+    match precedence(irep) < parent {
+        true => format!("({})", expr(irep)),
+        false => expr(irep),
+    }
+}
+
+fn list_text(ireps: &[Irept]) -> String {
+    ireps.iter().map(expr).collect::<Vec<String>>().join(", ")
+}
+
+// Renders an expression
+pub fn expr(irep: &Irept) -> String {
+    let id = irep.id.as_str();
+    let ops = irep.operands();
+
+    if let Some((op, p)) = binary_operator(id) {
+        if ops.len() >= 2 {
+            // Left associative: the right operand needs parenthesis on ties
+            let mut text = operand_text(&ops[0], p);
+            for rhs in &ops[1..] {
+                text = format!("{} {} {}", text, op, operand_text(rhs, p + 1));
+            }
+            return text;
+        }
+    }
+
+    if let (Some(op), [operand]) = (unary_operator(id), ops) {
+        return format!("{}{}", op, operand_text(operand, UNARY));
+    }
+
+    match (id, ops) {
+        ("symbol", _) | ("nondet_symbol", _) if named(irep, "identifier").is_some() => {
+            named_id(irep, "identifier").to_string()
+        }
+        ("constant", _) if named(irep, "value").is_some() => constant_text(irep),
+        ("string_constant", _) => format!("\"{}\"", named_id(irep, "value")),
+        ("typecast", [operand]) => format!(
+            "({}){}",
+            type_text(named(irep, "type").unwrap_or(&Irept::default())),
+            operand_text(operand, UNARY)
+        ),
+        ("member", [operand]) => format!(
+            "{}.{}",
+            operand_text(operand, POSTFIX),
+            named_id(irep, "component_name")
+        ),
+        ("index", [array, index]) => {
+            format!("{}[{}]", operand_text(array, POSTFIX), expr(index))
+        }
+        ("if", [cond, then, other]) => format!(
+            "{} ? {} : {}",
+            operand_text(cond, TERNARY + 1),
+            operand_text(then, TERNARY + 1),
+            operand_text(other, TERNARY)
+        ),
+        ("struct" | "union" | "array", _) if !is_type(irep) => {
+            format!("{{ {} }}", list_text(ops))
+        }
+        ("side_effect" | "sideeffect", _) => {
+            let statement = named_id(irep, "statement");
+            match statement {
+                "nondet" => String::from("NONDET"),
+                _ => format!("{}({})", statement, list_text(ops)),
+            }
+        }
+        ("code", _) => code_text(irep),
+        _ if is_type(irep) => type_text(irep),
+        _ => sexpr(irep),
+    }
+}
+
+// Renders a statement (code irep)
+pub fn code_text(irep: &Irept) -> String {
+    let statement = named_id(irep, "statement");
+    match (statement, irep.operands()) {
+        ("assign", [lhs, rhs]) => format!("{} = {}", expr(lhs), expr(rhs)),
+        ("decl", [symbol, ..]) => format!(
+            "{} {}",
+            type_text(named(symbol, "type").unwrap_or(&Irept::default())),
+            expr(symbol)
+        ),
+        ("function_call", [lhs, function, arguments]) => {
+            let call = format!("{}({})", expr(function), list_text(arguments.operands()));
+            match lhs.id.as_str() {
+                "nil" => call,
+                _ => format!("{} = {}", expr(lhs), call),
+            }
+        }
+        ("expression", [operand]) => expr(operand),
+        ("skip", _) => String::from("skip"),
+        _ => format!("{}({})", statement, list_text(irep.operands())),
+    }
+}
+
+///////////
+// TESTS //
+///////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(id: &str) -> Irept {
+        Irept::from(id)
+    }
+
+    fn with(mut irep: Irept, name: &str, value: Irept) -> Irept {
+        irep.named_subt.insert(String::from(name), value);
+        irep
+    }
+
+    fn bv(id: &str, width: usize) -> Irept {
+        with(leaf(id), "width", Irept::from(width.to_string()))
+    }
+
+    fn symbol(name: &str, stype: Irept) -> Irept {
+        with(
+            with(leaf("symbol"), "identifier", leaf(name)),
+            "type",
+            stype,
+        )
+    }
+
+    fn constant(value: &str, stype: Irept) -> Irept {
+        with(with(leaf("constant"), "value", leaf(value)), "type", stype)
+    }
+
+    // CBMC convention
+    fn cbmc(id: &str, operands: Vec<Irept>) -> Irept {
+        let mut irep = leaf(id);
+        irep.subt = operands;
+        irep
+    }
+
+    // ESBMC convention
+    fn esbmc(id: &str, operands: Vec<Irept>) -> Irept {
+        let mut irep = leaf(id);
+        let mut ops = Irept::default();
+        ops.subt = operands;
+        irep.named_subt.insert(String::from("operands"), ops);
+        irep
+    }
+
+    #[test]
+    fn sexpr_both_conventions() {
+        let int = bv("signedbv", 32);
+        let x = symbol("x", int.clone());
+        let y = symbol("y", int.clone());
+        let lhs = cbmc("=", vec![x.clone(), y.clone()]);
+        assert_eq!(
+            sexpr(&lhs),
+            "(= (symbol :identifier x) (symbol :identifier y))"
+        );
+        assert_eq!(sexpr(&esbmc("=", vec![x, y])), sexpr(&lhs));
+    }
+
+    #[test]
+    fn binary_operators() {
+        let int = bv("signedbv", 32);
+        let a = symbol("a", int.clone());
+        let b = symbol("b", int.clone());
+        let c = symbol("c", int.clone());
+        for make in [cbmc, esbmc] {
+            let sum = make("+", vec![a.clone(), b.clone()]);
+            let product = make("*", vec![sum.clone(), c.clone()]);
+            assert_eq!(expr(&product), "(a + b) * c");
+            let diff = make("-", vec![a.clone(), make("-", vec![b.clone(), c.clone()])]);
+            assert_eq!(expr(&diff), "a - (b - c)");
+            let cond = make(
+                "and",
+                vec![
+                    make("<", vec![a.clone(), b.clone()]),
+                    make("notequal", vec![b.clone(), c.clone()]),
+                ],
+            );
+            assert_eq!(expr(&cond), "a < b && b != c");
+        }
+    }
+
+    #[test]
+    fn unary_and_postfix() {
+        let int = bv("signedbv", 32);
+        let p = symbol("p", with(leaf("pointer"), "subtype", int.clone()));
+        let s = symbol("s", with(leaf("struct_tag"), "identifier", leaf("tag-foo")));
+        let arr = symbol("arr", int.clone());
+
+        let deref = cbmc("dereference", vec![p.clone()]);
+        let member = with(cbmc("member", vec![deref]), "component_name", leaf("next"));
+        assert_eq!(expr(&member), "(*p).next");
+
+        let address = esbmc(
+            "address_of",
+            vec![with(esbmc("member", vec![s]), "component_name", leaf("a"))],
+        );
+        assert_eq!(expr(&address), "&s.a");
+
+        let index = esbmc(
+            "index",
+            vec![
+                arr,
+                constant("00000000000000000000000000000011", int.clone()),
+            ],
+        );
+        assert_eq!(expr(&index), "arr[3]");
+
+        let cast = with(
+            cbmc("typecast", vec![symbol("c", bv("signedbv", 8))]),
+            "type",
+            bv("unsignedbv", 32),
+        );
+        assert_eq!(expr(&cast), "(unsignedbv[32])c");
+
+        let neg = cbmc("unary-", vec![cbmc("+", vec![p.clone(), p])]);
+        assert_eq!(expr(&neg), "-(p + p)");
+    }
+
+    #[test]
+    fn constants() {
+        // CBMC: hexadecimal without leading zeros
+        assert_eq!(expr(&constant("FFFFFFFF", bv("signedbv", 32))), "-1");
+        assert_eq!(expr(&constant("2A", bv("unsignedbv", 32))), "42");
+        assert_eq!(expr(&constant("80", bv("signedbv", 8))), "-128");
+        // ESBMC: every bit
+        assert_eq!(expr(&constant("11111110", bv("signedbv", 8))), "-2");
+        assert_eq!(expr(&constant("00000101", bv("unsignedbv", 8))), "5");
+        // Wide and special values
+        assert_eq!(
+            expr(&constant(
+                "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
+                bv("signedbv", 128)
+            )),
+            "-1"
+        );
+        assert_eq!(
+            expr(&constant("1", bv("unsignedbv", 256))),
+            format!("0x{}1", "0".repeat(63))
+        );
+        assert_eq!(expr(&constant("NULL", bv("pointer", 64))), "NULL");
+        assert_eq!(expr(&constant("true", leaf("bool"))), "true");
+        assert_eq!(expr(&constant("3F800000", bv("floatbv", 32))), "1");
+    }
+
+    #[test]
+    fn types() {
+        let int = bv("signedbv", 32);
+        // CBMC keeps the subtype in sub[0]
+        let mut cbmc_ptr = bv("pointer", 64);
+        cbmc_ptr.subt.push(int.clone());
+        assert_eq!(type_text(&cbmc_ptr), "signedbv[32] *");
+        let esbmc_ptr = with(leaf("pointer"), "subtype", int.clone());
+        assert_eq!(type_text(&esbmc_ptr), "signedbv[32] *");
+
+        let size = constant("A", bv("unsignedbv", 64));
+        let array = with(
+            with(leaf("array"), "subtype", bv("unsignedbv", 8)),
+            "size",
+            size,
+        );
+        assert_eq!(type_text(&array), "unsignedbv[8][10]");
+
+        let component = with(with(leaf("component"), "name", leaf("a")), "type", int);
+        let mut components = Irept::default();
+        components.subt.push(component);
+        let st = with(leaf("struct"), "components", components);
+        assert_eq!(type_text(&st), "struct { signedbv[32] a; }");
+        assert_eq!(
+            type_text(&with(leaf("struct_tag"), "identifier", leaf("tag-foo"))),
+            "struct tag-foo"
+        );
+        assert_eq!(format!("{}", st), "struct { signedbv[32] a; }");
+    }
+
+    #[test]
+    fn fallback() {
+        let weird = cbmc("overflow-+", vec![leaf("a"), leaf("b")]);
+        assert_eq!(expr(&weird), "(overflow-+ a b)");
+    }
+}
@@ -1,4 +1,4 @@
-/// This crate serves to handle all the data available from the resources dir
+//! This crate serves to handle all the data available from the resources dir
 
 pub fn get_resource_dir() -> String {
     match std::env::var("CARGO_MANIFEST_DIR") {
@@ -0,0 +1,454 @@
+// This module is responsible for the rewrite rules that turn CBMC ireps
+// into ESBMC ireps (and back). The built-in rules are at resources/rules.json
+// and the format is described at docs/Development.md
+
+use crate::irep::Irept;
+use json::JsonValue;
+use log::trace;
+
+const BUILTIN_RULES: &str = include_str!("../resources/rules.json");
+
+const RULE_FIELDS: [&str; 12] = [
+    "id",
+    "type",
+    "operands",
+    "fields",
+    "move_operand",
+    "default",
+    "rename_fields",
+    "drop_fields",
+    "add_fields",
+    "rename",
+    "wrap_operands",
+    "oneway",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Action {
+    // Changes the id of the irep
+    Rename(String),
+    // Moves the unnamed subs into the "operands" named sub
+    WrapOperands,
+    // Moves the "operands" named sub back into the unnamed subs
+    UnwrapOperands,
+    // Pops the last operand into a named sub
+    MoveOperand(String),
+    // Pushes a named sub as the last operand, using the default if missing
+    PushOperand(String, Option<Irept>),
+    RenameField(String, String),
+    DropField(String),
+    AddField(String, Irept),
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    ids: Vec<String>,
+    // Id of the "type" named sub
+    type_id: Option<String>,
+    // Only match ireps with (or without) operands
+    operands: Option<bool>,
+    // Only match ireps with all these named subs
+    fields: Vec<String>,
+    actions: Vec<Action>,
+    // Operand to push back when the moved one is missing
+    default: Option<Irept>,
+    // Rules that cannot be undone are not part of the inverted set
+    oneway: bool,
+}
+
+impl Rule {
+    fn matches(&self, irep: &Irept) -> bool {
+        if !self.ids.contains(&irep.id) {
+            return false;
+        }
+        if let Some(type_id) = &self.type_id {
+            if irep.named_subt.get("type").is_none_or(|t| &t.id != type_id) {
+                return false;
+            }
+        }
+        if let Some(operands) = self.operands {
+            if irep.operands().is_empty() == operands {
+                return false;
+            }
+        }
+        self.fields.iter().all(|f| irep.named_subt.contains_key(f))
+    }
+
+    fn apply(&self, irep: &mut Irept) {
+        for action in &self.actions {
+            match action {
+                Action::Rename(id) => irep.id = id.clone(),
+                Action::WrapOperands => {
+                    let mut operands = Irept::default();
+                    operands.subt = std::mem::take(&mut irep.subt);
+                    irep.named_subt.insert("operands".to_string(), operands);
+                }
+                Action::UnwrapOperands => {
+                    if let Some(operands) = irep.named_subt.remove("operands") {
+                        irep.subt = operands.subt;
+                    }
+                }
+                Action::MoveOperand(field) => {
+                    if let Some(operand) = irep.subt.pop() {
+                        irep.named_subt.insert(field.clone(), operand);
+                    }
+                }
+                Action::PushOperand(field, default) => {
+                    if let Some(operand) = irep.named_subt.remove(field).or(default.clone()) {
+                        irep.subt.push(operand);
+                    }
+                }
+                Action::RenameField(from, to) => {
+                    if let Some(value) = irep.named_subt.remove(from) {
+                        irep.named_subt.insert(to.clone(), value);
+                    }
+                }
+                Action::DropField(field) => {
+                    irep.named_subt.remove(field);
+                }
+                Action::AddField(field, value) => {
+                    irep.named_subt
+                        .entry(field.clone())
+                        .or_insert(value.clone());
+                }
+            }
+        }
+    }
+
+    // Dropped fields cannot be restored, everything else is undone in the
+    // reverse order.
+    fn inverted(&self) -> Rule {
+        let mut ids = self.ids.clone();
+        let mut fields = self.fields.clone();
+        let mut actions = Vec::with_capacity(self.actions.len());
+        for action in self.actions.iter().rev() {
+            let inverse = match action {
+                Action::Rename(id) => {
+                    let original = std::mem::replace(&mut ids, vec![id.clone()]);
+                    Action::Rename(original[0].clone())
+                }
+                Action::WrapOperands => Action::UnwrapOperands,
+                Action::UnwrapOperands => Action::WrapOperands,
+                Action::MoveOperand(field) => {
+                    // Without a default the moved operand identifies the rule
+                    if self.default.is_none() {
+                        fields.push(field.clone());
+                    }
+                    Action::PushOperand(field.clone(), self.default.clone())
+                }
+                Action::PushOperand(field, _) => Action::MoveOperand(field.clone()),
+                Action::RenameField(from, to) => Action::RenameField(to.clone(), from.clone()),
+                Action::DropField(_) => continue,
+                Action::AddField(field, _) => Action::DropField(field.clone()),
+            };
+            actions.push(inverse);
+        }
+
+        Rule {
+            ids,
+            type_id: self.type_id.clone(),
+            operands: self.operands,
+            fields,
+            actions,
+            default: None,
+            oneway: false,
+        }
+    }
+
+    fn from_json(value: &JsonValue, path: &str) -> Result<Rule, String> {
+        if !value.is_object() {
+            return Err(format!("{}: expected a rule object", path));
+        }
+        for (k, _) in value.entries() {
+            if !RULE_FIELDS.contains(&k) {
+                return Err(format!("{}.{}: unknown rule field", path, k));
+            }
+        }
+
+        let ids = match &value["id"] {
+            JsonValue::Null => return Err(format!("{}: missing id", path)),
+            JsonValue::Array(_) => get_strings(value, "id", path)?,
+            _ => vec![get_string(value, "id", path)?.unwrap()],
+        };
+
+        // Actions are always done in this order
+        let mut actions = vec![];
+        if let Some(field) = get_string(value, "move_operand", path)? {
+            actions.push(Action::MoveOperand(field));
+        }
+        for (from, to) in get_object(value, "rename_fields", path)? {
+            let to = to.as_str().ok_or(format!(
+                "{}.rename_fields.{}: expected a string",
+                path, from
+            ))?;
+            actions.push(Action::RenameField(from, to.to_string()));
+        }
+        for field in get_strings(value, "drop_fields", path)? {
+            actions.push(Action::DropField(field));
+        }
+        for (field, v) in get_object(value, "add_fields", path)? {
+            let irep = Irept::from_json(&v, &format!("{}.add_fields.{}", path, field))?;
+            actions.push(Action::AddField(field, irep));
+        }
+        if let Some(id) = get_string(value, "rename", path)? {
+            if ids.len() != 1 {
+                return Err(format!(
+                    "{}.rename: only rules with one id can rename",
+                    path
+                ));
+            }
+            actions.push(Action::Rename(id));
+        }
+        if get_bool(value, "wrap_operands", path)?.unwrap_or(false) {
+            actions.push(Action::WrapOperands);
+        }
+
+        let default = match &value["default"] {
+            JsonValue::Null => None,
+            v if value.has_key("move_operand") => {
+                Some(Irept::from_json(v, &format!("{}.default", path))?)
+            }
+            _ => return Err(format!("{}.default: requires move_operand", path)),
+        };
+
+        Ok(Rule {
+            ids,
+            type_id: get_string(value, "type", path)?,
+            operands: get_bool(value, "operands", path)?,
+            fields: get_strings(value, "fields", path)?,
+            actions,
+            default,
+            oneway: get_bool(value, "oneway", path)?.unwrap_or(false),
+        })
+    }
+}
+
+fn get_string(value: &JsonValue, key: &str, path: &str) -> Result<Option<String>, String> {
+    match &value[key] {
+        JsonValue::Null => Ok(None),
+        v => v
+            .as_str()
+            .map(|s| Some(s.to_string()))
+            .ok_or(format!("{}.{}: expected a string", path, key)),
+    }
+}
+
+fn get_bool(value: &JsonValue, key: &str, path: &str) -> Result<Option<bool>, String> {
+    match &value[key] {
+        JsonValue::Null => Ok(None),
+        v => v
+            .as_bool()
+            .map(Some)
+            .ok_or(format!("{}.{}: expected a boolean", path, key)),
+    }
+}
+
+fn get_strings(value: &JsonValue, key: &str, path: &str) -> Result<Vec<String>, String> {
+    match &value[key] {
+        JsonValue::Null => Ok(vec![]),
+        JsonValue::Array(items) => items
+            .iter()
+            .map(|i| i.as_str().map(String::from))
+            .collect::<Option<Vec<String>>>()
+            .ok_or(format!("{}.{}: expected a list of strings", path, key)),
+        _ => Err(format!("{}.{}: expected a list of strings", path, key)),
+    }
+}
+
+fn get_object(
+    value: &JsonValue,
+    key: &str,
+    path: &str,
+) -> Result<Vec<(String, JsonValue)>, String> {
+    match &value[key] {
+        JsonValue::Null => Ok(vec![]),
+        JsonValue::Object(obj) => Ok(obj
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()),
+        _ => Err(format!("{}.{}: expected an object", path, key)),
+    }
+}
+
+/// Ordered list of rules, the first one that matches an irep rewrites it.
+#[derive(Clone, Debug)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet::from_json(&json::parse(BUILTIN_RULES).unwrap()).unwrap()
+    }
+}
+
+impl RuleSet {
+    pub fn from_json(value: &JsonValue) -> Result<Self, String> {
+        if !value.is_array() {
+            return Err(String::from("$: expected a list of rules"));
+        }
+        let rules = value
+            .members()
+            .enumerate()
+            .map(|(i, rule)| Rule::from_json(rule, &format!("$[{}]", i)))
+            .collect::<Result<Vec<Rule>, String>>()?;
+        Ok(RuleSet { rules })
+    }
+
+    /// Built-in rules extended by the ones in `path`, which take precedence
+    pub fn load(path: &str) -> Result<Self, String> {
+        trace!("Loading rules from {}", path);
+        let content =
+            std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        let value = json::parse(&content).map_err(|e| format!("{}: {}", path, e))?;
+        let mut result = RuleSet::from_json(&value).map_err(|e| format!("{}: {}", path, e))?;
+        result.rules.extend(RuleSet::default().rules);
+        Ok(result)
+    }
+
+    /// Rules to go back from ESBMC into CBMC
+    pub fn inverted(&self) -> Self {
+        RuleSet {
+            rules: self
+                .rules
+                .iter()
+                .filter(|r| !r.oneway)
+                .map(Rule::inverted)
+                .collect(),
+        }
+    }
+
+    /// Rewrites the irep (but not its subs) with the first matching rule
+    pub fn apply(&self, irep: &mut Irept) -> bool {
+        match self.rules.iter().find(|r| r.matches(irep)) {
+            Some(rule) => {
+                rule.apply(irep);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+///////////
+// TESTS //
+///////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn irep(id: &str, subt: Vec<Irept>) -> Irept {
+        let mut result = Irept::from(id);
+        result.subt = subt;
+        result
+    }
+
+    #[test]
+    fn builtin_rules() {
+        let rules = RuleSet::default();
+        let inverted = rules.inverted();
+
+        let original = irep("+", vec![Irept::from("a"), Irept::from("b")]);
+        let mut plus = original.clone();
+        assert!(rules.apply(&mut plus));
+        assert!(plus.subt.is_empty());
+        assert_eq!(plus.named_subt["operands"].subt.len(), 2);
+        assert!(inverted.apply(&mut plus));
+        assert_eq!(plus, original);
+
+        // No rules for leaves
+        assert!(!rules.apply(&mut Irept::from("symbol")));
+        // Shape: only array literals have operands
+        assert!(!rules.apply(&mut Irept::from("array")));
+    }
+
+    #[test]
+    fn custom_rules() {
+        let value = json::parse(
+            r#"[{
+                "id": "foo",
+                "type": "bool",
+                "move_operand": "extra",
+                "rename_fields": {"a": "b"},
+                "add_fields": {"c": {"id": "1"}},
+                "rename": "bar",
+                "wrap_operands": true
+            }]"#,
+        )
+        .unwrap();
+        let rules = RuleSet::from_json(&value).unwrap();
+
+        let mut original = irep("foo", vec![Irept::from("x"), Irept::from("y")]);
+        original
+            .named_subt
+            .insert(String::from("a"), Irept::from("2"));
+        let mut other = original.clone();
+        assert!(!rules.apply(&mut other));
+
+        original
+            .named_subt
+            .insert(String::from("type"), Irept::from("bool"));
+        let mut foo = original.clone();
+        assert!(rules.apply(&mut foo));
+        assert_eq!(foo.id, "bar");
+        assert_eq!(foo.named_subt["extra"].id, "y");
+        assert_eq!(foo.named_subt["operands"].subt, vec![Irept::from("x")]);
+        assert_eq!(foo.named_subt["b"].id, "2");
+        assert_eq!(foo.named_subt["c"].id, "1");
+
+        // The moved operand is needed to match the inverted rule
+        let inverted = rules.inverted();
+        let mut missing = foo.clone();
+        missing.named_subt.remove("extra");
+        assert!(!inverted.apply(&mut missing));
+
+        assert!(inverted.apply(&mut foo));
+        assert_eq!(foo, original);
+    }
+
+    #[test]
+    fn load_rules() {
+        let path = "/tmp/test_load_rules.json";
+        std::fs::write(path, r#"[{"id": "+", "rename": "add"}]"#).unwrap();
+        let rules = RuleSet::load(path).unwrap();
+        std::fs::remove_file(path).ok();
+
+        // Loaded rules go before the built-in ones
+        let mut plus = irep("+", vec![Irept::from("a"), Irept::from("b")]);
+        assert!(rules.apply(&mut plus));
+        assert_eq!(plus.id, "add");
+        assert_eq!(plus.subt.len(), 2);
+
+        let mut minus = irep("-", vec![Irept::from("a"), Irept::from("b")]);
+        assert!(rules.apply(&mut minus));
+        assert!(minus.named_subt.contains_key("operands"));
+
+        assert!(RuleSet::load("/tmp/test_load_rules_missing.json")
+            .unwrap_err()
+            .starts_with("Could not read"));
+    }
+
+    #[test]
+    fn rule_errors() {
+        let error = |text: &str| RuleSet::from_json(&json::parse(text).unwrap()).unwrap_err();
+        assert_eq!(error("{}"), "$: expected a list of rules");
+        assert_eq!(error("[{}]"), "$[0]: missing id");
+        assert_eq!(
+            error(r#"[{"id": "a", "foo": 1}]"#),
+            "$[0].foo: unknown rule field"
+        );
+        assert_eq!(
+            error(r#"[{"id": ["a", "b"], "rename": "c"}]"#),
+            "$[0].rename: only rules with one id can rename"
+        );
+        assert_eq!(
+            error(r#"[{"id": "a", "default": {"id": "b"}}]"#),
+            "$[0].default: requires move_operand"
+        );
+        assert_eq!(
+            error(r#"[{"id": "a", "add_fields": {"b": 1}}]"#),
+            "$[0].add_fields.b: expected an irep object"
+        );
+    }
+}
@@ -0,0 +1,544 @@
+// This module lowers the threads of CBMC into the ones of ESBMC. CBMC runs
+// the instructions from the target of a START_THREAD up to its END_THREAD in
+// a new thread, which starts with a copy of the locals. ESBMC can only start
+// a function without arguments: __ESBMC_spawn_thread(&f).
+//
+// The instructions of the thread are moved into a new function and the
+// locals it reads are passed through globals:
+//
+//   START_THREAD 1            started := false
+//   GOTO 2                    g := x
+//1: ...x...             =>    CALL __ESBMC_spawn_thread(&f$thread0)
+//   END_THREAD                ASSUME started
+//2: ...                       GOTO 2
+//                          2: ...
+//
+//   f$thread0:
+//     DECL x
+//     x := g
+//     started := true
+//  1: ...x...
+//     CALL __ESBMC_terminate_thread()
+//     END_FUNCTION
+//
+// The ASSUME waits for the copy, in case the same thread is started again.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::cbmc::{
+    CBMCFunction, CBMCInstruction, CBMCInstructionKind, CBMCSymbol, NO_TARGET_NUMBER,
+};
+use crate::irep::Irept;
+use log::warn;
+
+const SPAWN_THREAD: &str = "c:@F@__ESBMC_spawn_thread";
+const TERMINATE_THREAD: &str = "c:@F@__ESBMC_terminate_thread";
+
+fn call(function: Irept, arguments: Vec<Irept>) -> Irept {
+    let arguments = Irept {
+        id: String::from("arguments"),
+        subt: arguments,
+        ..Default::default()
+    };
+    Irept::code(
+        "function_call",
+        vec![Irept::from("nil"), function, arguments],
+    )
+}
+
+fn instruction(kind: CBMCInstructionKind, code: Irept, like: &CBMCInstruction) -> CBMCInstruction {
+    CBMCInstruction {
+        code,
+        source_location: like.source_location.clone(),
+        instr_type: kind as u32,
+        guard: Irept::boolean(true),
+        target_number: NO_TARGET_NUMBER,
+        targets: vec![],
+        labels: vec![],
+        function: like.function.clone(),
+    }
+}
+
+fn assign(lhs: Irept, rhs: Irept, like: &CBMCInstruction) -> CBMCInstruction {
+    instruction(
+        CBMCInstructionKind::Assign,
+        Irept::code("assign", vec![lhs, rhs]),
+        like,
+    )
+}
+
+// Global variable, or function when `stype` is a code type
+fn global(name: &str, stype: Irept, value: Irept, like: &CBMCSymbol) -> CBMCSymbol {
+    let is_variable = stype.id != "code";
+    let mut result = CBMCSymbol {
+        stype,
+        value,
+        location: like.location.clone(),
+        name: String::from(name),
+        module: like.module.clone(),
+        base_name: String::from(name),
+        mode: like.mode.clone(),
+        pretty_name: String::from(name),
+        is_lvalue: is_variable,
+        is_static_lifetime: is_variable,
+        ..Default::default()
+    };
+    result.flags = result.pack_flags();
+    result
+}
+
+// Locals (of `locals`) read by `irep`
+fn used_locals(irep: &Irept, locals: &HashMap<String, Irept>, result: &mut BTreeSet<String>) {
+    if irep.id == "symbol" {
+        if let Some(identifier) = irep.named_subt.get("identifier") {
+            if locals.contains_key(&identifier.id) {
+                result.insert(identifier.id.clone());
+            }
+        }
+    }
+    for sub in irep.subt.iter().chain(irep.named_subt.values()) {
+        used_locals(sub, locals, result);
+    }
+}
+
+/// Moves every thread started by `functions` into a function of its own
+/// and starts it through __ESBMC_spawn_thread. Fails if a START_THREAD
+/// and its END_THREAD do not match.
+pub fn lower_threads(
+    symbols: &mut Vec<CBMCSymbol>,
+    functions: &mut Vec<CBMCFunction>,
+    pointer_width: u64,
+) -> Result<(), String> {
+    let locals: HashMap<String, Irept> = symbols
+        .iter()
+        .filter(|s| !s.is_static_lifetime && !s.is_type && s.stype.id != "code")
+        .map(|s| (s.name.clone(), s.stype.clone()))
+        .collect();
+
+    // The POSIX threads of goto-cc are declarations, CBMC adds its model later
+    for function in functions.iter() {
+        if function.name == "pthread_create" && function.instructions.is_empty() {
+            warn!("pthread_create has no body, link the CBMC model with goto-instrument --add-library");
+        }
+    }
+
+    let thread_type = Irept::code_type(vec![], Irept::from("empty"));
+    let thread_id = Irept::bitvector("unsignedbv", 32);
+    let thread_pointer = Irept::pointer(thread_type.clone(), pointer_width);
+    let mut count = 0;
+    let mut index = 0;
+    // Threads might start other threads, new functions are checked as well
+    while index < functions.len() {
+        let start = match functions[index]
+            .instructions
+            .iter()
+            .position(|i| i.instr_type == CBMCInstructionKind::StartThread as u32)
+        {
+            Some(start) => start,
+            None => {
+                let function = &functions[index];
+                if function
+                    .instructions
+                    .iter()
+                    .any(|i| i.instr_type == CBMCInstructionKind::EndThread as u32)
+                {
+                    return Err(format!(
+                        "Function {}: END_THREAD without START_THREAD",
+                        function.name
+                    ));
+                }
+                index += 1;
+                continue;
+            }
+        };
+
+        let function = &mut functions[index];
+        let like = symbols
+            .iter()
+            .find(|s| s.name == function.name)
+            .cloned()
+            .unwrap_or_default();
+        let thread = format!("{}$thread{}", function.name, count);
+        let started = format!("{}$started", thread);
+        count += 1;
+
+        // The thread goes from the target of START_THREAD to its END_THREAD
+        let start_instr = function.instructions[start].clone();
+        let first = start_instr
+            .targets
+            .first()
+            .and_then(|target| target.id.parse::<u32>().ok())
+            .and_then(|target| {
+                function
+                    .instructions
+                    .iter()
+                    .position(|i| i.target_number == target)
+            })
+            .ok_or_else(|| format!("Function {}: START_THREAD without target", function.name))?;
+        if first <= start {
+            return Err(format!(
+                "Function {}: thread before its START_THREAD",
+                function.name
+            ));
+        }
+        let last = function.instructions[first..]
+            .iter()
+            .position(|i| i.instr_type == CBMCInstructionKind::EndThread as u32)
+            .ok_or_else(|| format!("Function {}: thread without END_THREAD", function.name))?
+            + first;
+        let body: Vec<CBMCInstruction> = function.instructions.drain(first..last).collect();
+        let end_thread = function.instructions.remove(first);
+
+        let mut captured = BTreeSet::new();
+        for instr in &body {
+            used_locals(&instr.code, &locals, &mut captured);
+            used_locals(&instr.guard, &locals, &mut captured);
+        }
+        for instr in &body {
+            if instr.instr_type != CBMCInstructionKind::Decl as u32 {
+                continue;
+            }
+            if let Some(identifier) = instr
+                .code
+                .subt
+                .first()
+                .and_then(|local| local.named_subt.get("identifier"))
+            {
+                captured.remove(&identifier.id);
+            }
+        }
+
+        let bool_type = Irept::from("bool");
+        let mut spawn = vec![assign(
+            Irept::symbol(&started, bool_type.clone()),
+            Irept::boolean(false),
+            &start_instr,
+        )];
+        let mut prologue = vec![];
+        for local in &captured {
+            let stype = &locals[local];
+            let copy = format!("{}${}", thread, local);
+            spawn.push(assign(
+                Irept::symbol(&copy, stype.clone()),
+                Irept::symbol(local, stype.clone()),
+                &start_instr,
+            ));
+            prologue.push(instruction(
+                CBMCInstructionKind::Decl,
+                Irept::code("decl", vec![Irept::symbol(local, stype.clone())]),
+                &start_instr,
+            ));
+            prologue.push(assign(
+                Irept::symbol(local, stype.clone()),
+                Irept::symbol(&copy, stype.clone()),
+                &start_instr,
+            ));
+            symbols.push(global(&copy, stype.clone(), Irept::from("nil"), &like));
+        }
+        prologue.push(assign(
+            Irept::symbol(&started, bool_type.clone()),
+            Irept::boolean(true),
+            &start_instr,
+        ));
+        symbols.push(global(
+            &started,
+            bool_type.clone(),
+            Irept::boolean(false),
+            &like,
+        ));
+
+        let spawn_type = Irept::code_type(
+            vec![Irept::typed("parameter", thread_pointer.clone())],
+            thread_id.clone(),
+        );
+        let mut address = Irept::typed("address_of", thread_pointer.clone());
+        address
+            .subt
+            .push(Irept::symbol(&thread, thread_type.clone()));
+        spawn.push(instruction(
+            CBMCInstructionKind::FunctionCall,
+            call(
+                Irept::symbol(SPAWN_THREAD, spawn_type.clone()),
+                vec![address],
+            ),
+            &start_instr,
+        ));
+        let mut wait = instruction(
+            CBMCInstructionKind::Assume,
+            Irept::from("nil"),
+            &start_instr,
+        );
+        wait.guard = Irept::symbol(&started, bool_type.clone());
+        spawn.push(wait);
+
+        // Whatever pointed to START_THREAD now points to its replacement
+        spawn[0].target_number = start_instr.target_number;
+        spawn[0].labels = start_instr.labels.clone();
+        function.instructions.splice(start..=start, spawn);
+
+        // Same for END_THREAD
+        let terminate_type = Irept::code_type(vec![], Irept::from("empty"));
+        let mut terminate = instruction(
+            CBMCInstructionKind::FunctionCall,
+            call(
+                Irept::symbol(TERMINATE_THREAD, terminate_type.clone()),
+                vec![],
+            ),
+            &end_thread,
+        );
+        terminate.target_number = end_thread.target_number;
+        terminate.labels = end_thread.labels.clone();
+        let end_function = instruction(
+            CBMCInstructionKind::EndFunction,
+            Irept::from("nil"),
+            &end_thread,
+        );
+
+        let mut instructions = prologue;
+        instructions.extend(body);
+        instructions.push(terminate);
+        instructions.push(end_function);
+        functions.push(CBMCFunction {
+            name: thread.clone(),
+            instructions,
+        });
+        symbols.push(global(
+            &thread,
+            thread_type.clone(),
+            Irept::from("compiled"),
+            &like,
+        ));
+
+        for (name, stype) in [
+            (SPAWN_THREAD, spawn_type),
+            (TERMINATE_THREAD, terminate_type),
+        ] {
+            if !symbols.iter().any(|s| s.name == name) {
+                symbols.push(global(name, stype, Irept::from("nil"), &like));
+            }
+        }
+    }
+    Ok(())
+}
+
+///////////
+// TESTS //
+///////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cbmc_symbol(name: &str, stype: Irept, is_static_lifetime: bool) -> CBMCSymbol {
+        CBMCSymbol {
+            name: String::from(name),
+            stype,
+            is_static_lifetime,
+            ..Default::default()
+        }
+    }
+
+    fn numbered(
+        mut instr: CBMCInstruction,
+        target_number: u32,
+        targets: &[u32],
+    ) -> CBMCInstruction {
+        instr.target_number = target_number;
+        instr.targets = targets.iter().map(|t| Irept::from(t.to_string())).collect();
+        instr
+    }
+
+    // Instruction of main the others are built like
+    fn like() -> CBMCInstruction {
+        CBMCInstruction {
+            code: Irept::from("nil"),
+            source_location: Irept::default(),
+            instr_type: 0,
+            guard: Irept::boolean(true),
+            target_number: NO_TARGET_NUMBER,
+            targets: vec![],
+            labels: vec![],
+            function: Irept::from("main"),
+        }
+    }
+
+    fn kinds(function: &CBMCFunction) -> Vec<CBMCInstructionKind> {
+        function
+            .instructions
+            .iter()
+            .map(|i| CBMCInstructionKind::from_u32(i.instr_type).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn start_thread() {
+        let int = Irept::from("signedbv");
+        let id = Irept::symbol("main::1::id", int.clone());
+        let tmp = Irept::symbol("main::1::tmp", int.clone());
+        let x = Irept::symbol("x", int.clone());
+        let mut symbols = vec![
+            cbmc_symbol("main", Irept::code_type(vec![], Irept::from("empty")), true),
+            cbmc_symbol("x", int.clone(), true),
+            cbmc_symbol("main::1::id", int.clone(), false),
+            cbmc_symbol("main::1::tmp", int.clone(), false),
+        ];
+
+        let like = like();
+        use CBMCInstructionKind::*;
+        let mut functions = vec![CBMCFunction {
+            name: String::from("main"),
+            instructions: vec![
+                instruction(Decl, Irept::code("decl", vec![id.clone()]), &like),
+                numbered(instruction(StartThread, Irept::from("nil"), &like), 3, &[1]),
+                numbered(
+                    instruction(Goto, Irept::from("nil"), &like),
+                    NO_TARGET_NUMBER,
+                    &[2],
+                ),
+                numbered(
+                    instruction(Decl, Irept::code("decl", vec![tmp.clone()]), &like),
+                    1,
+                    &[],
+                ),
+                assign(tmp.clone(), id.clone(), &like),
+                assign(x.clone(), tmp.clone(), &like),
+                instruction(EndThread, Irept::from("nil"), &like),
+                numbered(instruction(EndFunction, Irept::from("nil"), &like), 2, &[]),
+            ],
+        }];
+
+        lower_threads(&mut symbols, &mut functions, 32).unwrap();
+
+        assert_eq!(functions.len(), 2);
+        assert_eq!(
+            kinds(&functions[0]),
+            [
+                Decl,
+                Assign,
+                Assign,
+                FunctionCall,
+                Assume,
+                Goto,
+                EndFunction
+            ]
+        );
+        let main = &functions[0].instructions;
+        // START_THREAD was a target
+        assert_eq!(main[1].target_number, 3);
+        assert_eq!(
+            main[1].code.subt[0].named_subt["identifier"].id,
+            "main$thread0$started"
+        );
+        assert_eq!(
+            main[2].code.subt[0].named_subt["identifier"].id,
+            "main$thread0$main::1::id"
+        );
+        assert_eq!(
+            main[3].code.subt[1].named_subt["identifier"].id,
+            SPAWN_THREAD
+        );
+        let argument = &main[3].code.subt[2].subt[0];
+        assert_eq!(argument.id, "address_of");
+        assert_eq!(argument.named_subt["type"].named_subt["width"].id, "32");
+        assert_eq!(argument.subt[0].named_subt["identifier"].id, "main$thread0");
+        assert_eq!(
+            main[4].guard.named_subt["identifier"].id,
+            "main$thread0$started"
+        );
+        assert_eq!(main[5].targets[0].id, "2");
+
+        let thread = &functions[1];
+        assert_eq!(thread.name, "main$thread0");
+        assert_eq!(
+            kinds(thread),
+            [
+                Decl,
+                Assign,
+                Assign,
+                Decl,
+                Assign,
+                Assign,
+                FunctionCall,
+                EndFunction
+            ]
+        );
+        // Only the local declared outside of the thread is copied
+        assert_eq!(thread.instructions[0].code.subt[0], id);
+        assert_eq!(thread.instructions[3].target_number, 1);
+        assert_eq!(
+            thread.instructions[6].code.subt[1].named_subt["identifier"].id,
+            TERMINATE_THREAD
+        );
+
+        let added: Vec<&str> = symbols[4..].iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            added,
+            [
+                "main$thread0$main::1::id",
+                "main$thread0$started",
+                "main$thread0",
+                SPAWN_THREAD,
+                TERMINATE_THREAD
+            ]
+        );
+        assert!(symbols[4].is_static_lifetime && symbols[4].is_lvalue);
+        // Functions are neither
+        for function in &symbols[6..] {
+            assert!(!function.is_static_lifetime && !function.is_lvalue);
+        }
+    }
+
+    #[test]
+    fn unbalanced_threads() {
+        let like = like();
+        use CBMCInstructionKind::*;
+        let lower = |instructions: Vec<CBMCInstruction>| {
+            let mut functions = vec![CBMCFunction {
+                name: String::from("main"),
+                instructions,
+            }];
+            lower_threads(&mut vec![], &mut functions, 64)
+                .err()
+                .unwrap()
+        };
+
+        assert_eq!(
+            lower(vec![
+                instruction(StartThread, Irept::from("nil"), &like),
+                instruction(EndFunction, Irept::from("nil"), &like),
+            ]),
+            "Function main: START_THREAD without target"
+        );
+        assert_eq!(
+            lower(vec![
+                numbered(
+                    instruction(StartThread, Irept::from("nil"), &like),
+                    NO_TARGET_NUMBER,
+                    &[1]
+                ),
+                numbered(instruction(Skip, Irept::from("nil"), &like), 1, &[]),
+                instruction(EndFunction, Irept::from("nil"), &like),
+            ]),
+            "Function main: thread without END_THREAD"
+        );
+        assert_eq!(
+            lower(vec![
+                numbered(instruction(Skip, Irept::from("nil"), &like), 1, &[]),
+                numbered(
+                    instruction(StartThread, Irept::from("nil"), &like),
+                    NO_TARGET_NUMBER,
+                    &[1]
+                ),
+                instruction(EndThread, Irept::from("nil"), &like),
+            ]),
+            "Function main: thread before its START_THREAD"
+        );
+        assert_eq!(
+            lower(vec![
+                instruction(EndThread, Irept::from("nil"), &like),
+                instruction(EndFunction, Irept::from("nil"), &like),
+            ]),
+            "Function main: END_THREAD without START_THREAD"
+        );
+    }
+}